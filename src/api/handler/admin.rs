@@ -0,0 +1,33 @@
+//! Administrative handlers.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::{ApiError, ApiResult};
+use crate::api::model::response::BreakersResponse;
+
+/// List the circuit breaker state of every tracked host.
+pub async fn list_breakers_handler(State(state): State<Arc<AppState>>) -> Json<BreakersResponse> {
+    Json(BreakersResponse {
+        breakers: state.fetch_service.breakers.status(),
+    })
+}
+
+/// Close the circuit breaker of a host.
+pub async fn reset_breaker_handler(
+    State(state): State<Arc<AppState>>,
+    Path(host): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !state.fetch_service.breakers.reset(&host) {
+        return Err(ApiError::NotFound(format!(
+            "No circuit breaker for {}",
+            host
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
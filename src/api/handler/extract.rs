@@ -5,7 +5,8 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::AppState;
-use crate::common::error::CommonError;
+use crate::api::handler::source;
+use crate::api::model::error::ApiError;
 use crate::domain::extract::config::ExtractConfig;
 use crate::domain::extract::rules::ExtractionRule;
 use crate::domain::extract::service::{ExtractResult, ExtractService};
@@ -14,7 +15,12 @@ use crate::domain::extract::service::{ExtractResult, ExtractService};
 #[derive(Debug, Deserialize)]
 pub struct ExtractRequest {
     /// HTML content to extract from
+    #[serde(default)]
     pub html: String,
+    /// `warc://file#record-id`, `har://file#index` (one document) or `warc://file`,
+    /// `har://file` (every document) instead of `html`
+    #[serde(default)]
+    pub source: Option<String>,
     /// Extraction rules
     pub rules: Vec<ExtractionRule>,
     /// Configuration options
@@ -29,6 +35,21 @@ pub struct ExtractResponse {
     pub id: String,
     /// Timestamp
     pub timestamp: String,
+    /// Extracted result (absent when `source` names a whole archive)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ExtractResult>,
+    /// Per-record results when `source` names a whole archive
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub records: Vec<RecordExtraction>,
+}
+
+/// Result extracted from one archived record.
+#[derive(Debug, Serialize)]
+pub struct RecordExtraction {
+    /// `WARC-Record-ID` of the record
+    pub record_id: Option<String>,
+    /// `WARC-Target-URI` of the record
+    pub target_uri: Option<String>,
     /// Extracted result
     pub result: ExtractResult,
 }
@@ -37,16 +58,56 @@ pub struct ExtractResponse {
 pub async fn extract_handler(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ExtractRequest>,
-) -> Result<Json<ExtractResponse>, CommonError> {
-    let result = state
-        .extract_service
-        .extract(&request.html, &request.rules, &request.config)
-        .await
-        .map_err(|e| CommonError::internal(e.to_string()))?;
+) -> Result<Json<ExtractResponse>, ApiError> {
+    let (result, records) = match &request.source {
+        None => (
+            Some(extract(&state, &request, &request.html).await?),
+            Vec::new(),
+        ),
+        Some(_) if !request.html.is_empty() => {
+            return Err(ApiError::BadRequest(
+                "give either html or source, not both".to_string(),
+            ));
+        }
+        Some(source) if source::is_archive(source) => {
+            let mut records = Vec::new();
+            for document in source::load(&state, source).await? {
+                records.push(RecordExtraction {
+                    record_id: document.record_id,
+                    target_uri: document.target_uri,
+                    result: extract(&state, &request, &document.html).await?,
+                });
+            }
+            (None, records)
+        }
+        Some(source) => {
+            let document = source::load(&state, source)
+                .await?
+                .pop()
+                .ok_or_else(|| ApiError::NotFound(source.clone()))?;
+            (
+                Some(extract(&state, &request, &document.html).await?),
+                Vec::new(),
+            )
+        }
+    };
 
     Ok(Json(ExtractResponse {
         id: uuid::Uuid::new_v4().to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         result,
+        records,
     }))
 }
+
+async fn extract(
+    state: &AppState,
+    request: &ExtractRequest,
+    html: &str,
+) -> Result<ExtractResult, ApiError> {
+    state
+        .extract_service
+        .extract(html, &request.rules, &request.config)
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))
+}
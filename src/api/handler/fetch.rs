@@ -2,13 +2,19 @@
 
 use axum::{
     extract::State,
-    http::StatusCode,
     response::{IntoResponse, Json},
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::AppState;
+use crate::api::model::error::ApiError;
+use crate::api::model::request::RetryOptions;
+use crate::api::model::response::{FetchResponse, ResponseMetadata};
+use crate::domain::fetch::config::{FetchConfig, HttpMethod, RequestBody, StatusRange};
+use crate::domain::fetch::proxy::ProxyConfig;
+use crate::domain::fetch::redirect::HtmlRedirectConfig;
 use crate::domain::fetch::service::FetchService;
 
 /// Fetch request payload.
@@ -20,57 +26,164 @@ pub struct FetchRequest {
     pub timeout_ms: Option<u64>,
     /// Optional user agent
     pub user_agent: Option<String>,
+    /// HTTP method (defaults to GET)
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookies to send
+    #[serde(default)]
+    pub cookies: HashMap<String, String>,
+    /// Optional request body (raw, form or json)
+    pub body: Option<RequestBody>,
+    /// Stream the body transcoded to UTF-8
+    #[serde(default)]
+    pub transcode: bool,
+    /// Optional retry policy overrides
+    pub retry: Option<RetryOptions>,
+    /// Proxy for this request (overrides configured proxies)
+    pub proxy: Option<ProxyConfig>,
+    /// Non-2xx statuses to return instead of failing (e.g. `[404, "410", "5xx"]`)
+    #[serde(default)]
+    pub accept_statuses: Vec<StatusRange>,
+    /// Session whose cookies, headers and proxy apply to this request
+    pub session_id: Option<String>,
+    /// Return a JSON envelope (body, status, headers, redirects) instead of
+    /// streaming the raw body
+    #[serde(default)]
+    pub envelope: bool,
+    /// Follow meta refresh (and optionally canonical / `og:url`) redirects
+    pub html_redirects: Option<HtmlRedirectConfig>,
 }
 
-/// Fetch response payload.
-#[derive(Debug, Serialize)]
-pub struct FetchResponse {
-    /// HTML content
-    pub content: String,
-    /// Content length in bytes
-    pub length: usize,
-    /// HTTP status code
-    pub status_code: u16,
-    /// Final URL after redirects
-    pub final_url: String,
-    /// Request metadata
-    pub metadata: ResponseMetadata,
-}
-
-/// Response metadata (common to all responses).
-#[derive(Debug, Serialize)]
-pub struct ResponseMetadata {
-    /// Request ID
-    pub request_id: String,
-    /// Timestamp
-    pub timestamp: String,
-    /// Duration in milliseconds
-    pub duration_ms: u128,
-}
-
-/// Fetch HTML content from a URL with streaming.
+/// Fetch HTML content from a URL, streaming the body or wrapping it in a
+/// JSON envelope.
 pub async fn fetch_handler(
     State(state): State<Arc<AppState>>,
     Json(request): Json<FetchRequest>,
 ) -> impl IntoResponse {
-    let config = crate::domain::fetch::config::FetchConfig {
+    let mut config = FetchConfig {
         timeout: std::time::Duration::from_millis(request.timeout_ms.unwrap_or(30000)),
         user_agent: request
             .user_agent
             .unwrap_or_else(|| "SCAPI/1.0".to_string()),
+        method: request.method,
+        headers: request.headers,
+        cookies: request.cookies,
+        body: request.body,
+        transcode_to_utf8: request.transcode,
+        retry: request
+            .retry
+            .unwrap_or_default()
+            .apply(state.retry_policy.clone()),
+        proxy: request.proxy,
+        accept_statuses: request.accept_statuses,
+        html_redirects: request.html_redirects,
         ..Default::default()
     };
 
-    match state
-        .fetch_service
-        .fetch_stream(&request.url, &config)
-        .await
-    {
+    let session = match request.session_id.as_deref() {
+        Some(id) => match state.session_store.get(id) {
+            Ok(session) => {
+                session.apply(&mut config);
+                Some(session)
+            }
+            Err(e) => return ApiError::NotFound(e.to_string()).into_response(),
+        },
+        None => None,
+    };
+
+    let response = if request.envelope {
+        envelope_response(&state, &request.url, &config).await
+    } else {
+        stream_response(&state, &request.url, &config).await
+    };
+
+    // Cookies are captured with the response headers, so persist them now
+    if let Some(session) = session {
+        state.session_store.save_in_background(session);
+    }
+
+    response
+}
+
+/// Buffer the body and return it with the response metadata as JSON.
+async fn envelope_response(
+    state: &AppState,
+    url: &str,
+    config: &FetchConfig,
+) -> axum::response::Response {
+    let started = std::time::Instant::now();
+    match state.fetch_service.fetch(url, config).await {
+        Ok(result) => Json(FetchResponse {
+            length: result.length,
+            content: result.content,
+            status_code: result.status_code,
+            final_url: result.final_url,
+            encoding: result.encoding,
+            attempts: result.attempts,
+            proxy: result.proxy,
+            cache_status: result
+                .cache_status
+                .map(|status| status.as_str().to_string()),
+            redirects: result.redirects,
+            headers: result.headers,
+            dns: result.dns,
+            metadata: ResponseMetadata {
+                request_id: uuid::Uuid::new_v4().to_string(),
+                timestamp: result.timestamp.to_rfc3339(),
+                duration_ms: started.elapsed().as_millis(),
+            },
+        })
+        .into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+/// Hop-by-hop and framing headers of the upstream response that are not
+/// forwarded; the body is re-framed for our client.
+const UNFORWARDED_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+];
+
+/// Stream the raw body with the upstream status and headers, plus metadata
+/// in `X-Scapi-*` headers.
+async fn stream_response(
+    state: &AppState,
+    url: &str,
+    config: &FetchConfig,
+) -> axum::response::Response {
+    match state.fetch_service.fetch_stream(url, config).await {
         Ok(result) => {
             // Convert ResponseStream to Axum Body
             let body = axum::body::Body::from_stream(result.stream);
 
             let mut response = body.into_response();
+            *response.status_mut() = axum::http::StatusCode::from_u16(result.status_code)
+                .unwrap_or(axum::http::StatusCode::OK);
+            for (name, value) in &result.headers {
+                if UNFORWARDED_HEADERS
+                    .iter()
+                    .any(|unforwarded| name.eq_ignore_ascii_case(unforwarded))
+                    || name.to_ascii_lowercase().starts_with("x-scapi-")
+                {
+                    continue;
+                }
+                if let (Ok(name), Ok(value)) = (
+                    axum::http::HeaderName::from_bytes(name.as_bytes()),
+                    axum::http::HeaderValue::from_str(value),
+                ) {
+                    response.headers_mut().append(name, value);
+                }
+            }
 
             // Set headers for metadata
             response.headers_mut().insert(
@@ -78,19 +191,82 @@ pub async fn fetch_handler(
                 axum::http::HeaderValue::from(result.status_code),
             );
 
+            response.headers_mut().insert(
+                "X-Scapi-Attempts",
+                axum::http::HeaderValue::from(result.attempts),
+            );
+
+            response.headers_mut().insert(
+                "X-Scapi-Redirects",
+                axum::http::HeaderValue::from(result.redirects.len()),
+            );
+
             if let Ok(url_header) = axum::http::HeaderValue::from_str(&result.final_url) {
                 response
                     .headers_mut()
                     .insert("X-Scapi-Final-Url", url_header);
             }
 
-            if let Some(len) = result.content_length {
+            if config.transcode_to_utf8 {
+                // Length changes when transcoding, so only the type is kept
+                let mime = result
+                    .content_type
+                    .as_deref()
+                    .and_then(|ct| ct.split(';').next())
+                    .map(str::trim)
+                    .filter(|mime| !mime.is_empty())
+                    .unwrap_or("text/html");
+                if let Ok(content_type) =
+                    axum::http::HeaderValue::from_str(&format!("{}; charset=utf-8", mime))
+                {
+                    response
+                        .headers_mut()
+                        .insert(axum::http::header::CONTENT_TYPE, content_type);
+                }
+            } else if let Some(len) = result.content_length {
                 response.headers_mut().insert(
                     axum::http::header::CONTENT_LENGTH,
                     axum::http::HeaderValue::from(len),
                 );
             }
 
+            if let Some(encoding) = result
+                .encoding
+                .as_deref()
+                .and_then(|e| axum::http::HeaderValue::from_str(e).ok())
+            {
+                response.headers_mut().insert("X-Scapi-Encoding", encoding);
+            }
+
+            if let Some(proxy) = result
+                .proxy
+                .as_deref()
+                .and_then(|p| axum::http::HeaderValue::from_str(p).ok())
+            {
+                response.headers_mut().insert("X-Scapi-Proxy", proxy);
+            }
+
+            if let Some(cache_status) = result.cache_status {
+                response.headers_mut().insert(
+                    "X-Scapi-Cache",
+                    axum::http::HeaderValue::from_static(cache_status.as_str()),
+                );
+            }
+
+            if let Some(dns) = &result.dns {
+                if let Some(ip) = dns
+                    .remote_ip
+                    .and_then(|ip| axum::http::HeaderValue::from_str(&ip.to_string()).ok())
+                {
+                    response.headers_mut().insert("X-Scapi-Remote-Ip", ip);
+                }
+                if let Ok(duration) =
+                    axum::http::HeaderValue::from_str(&format!("{:.3}", dns.duration_ms))
+                {
+                    response.headers_mut().insert("X-Scapi-Dns-Ms", duration);
+                }
+            }
+
             response.headers_mut().insert(
                 "X-Scapi-Timestamp",
                 axum::http::HeaderValue::from_str(&result.timestamp.to_rfc3339())
@@ -99,13 +275,6 @@ pub async fn fetch_handler(
 
             response
         }
-        Err(e) => {
-            // TODO: specific error mapping
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Fetch failed: {}", e),
-            )
-                .into_response()
-        }
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
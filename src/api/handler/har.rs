@@ -0,0 +1,53 @@
+//! HAR export handlers.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json},
+};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::{ApiError, ApiResult};
+use crate::infra::har::HarRecorder;
+
+/// Export the recorded fetches as a HAR 1.2 file.
+///
+/// Requires the configured export token as a bearer token.
+pub async fn export_har_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let har = recorder(&state, &headers)?.export();
+    let filename = format!(
+        "attachment; filename=\"scapi-{}.har\"",
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    Ok(([(header::CONTENT_DISPOSITION, filename)], Json(har)))
+}
+
+/// Drop the recorded fetches.
+pub async fn clear_har_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> ApiResult<StatusCode> {
+    recorder(&state, &headers)?.clear();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn recorder<'a>(state: &'a AppState, headers: &HeaderMap) -> Result<&'a HarRecorder, ApiError> {
+    let recorder = state
+        .har_recorder
+        .as_deref()
+        .ok_or_else(|| ApiError::NotFound("HAR recording is disabled".to_string()))?;
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !recorder.authorize(token) {
+        return Err(ApiError::Unauthorized(
+            "HAR export requires the configured export token".to_string(),
+        ));
+    }
+    Ok(recorder)
+}
@@ -0,0 +1,14 @@
+//! Service metrics handler.
+
+use axum::{extract::State, response::Json};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::response::MetricsResponse;
+
+/// Report fetch counters, such as how many fetches were coalesced.
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> Json<MetricsResponse> {
+    Json(MetricsResponse {
+        coalescing: state.fetch_service.coalesce_stats(),
+    })
+}
@@ -5,7 +5,14 @@ pub mod fetch;
 pub mod health;
 pub mod parse;
 
+pub mod admin;
 pub mod extract;
+pub mod har;
+pub mod metrics;
+pub mod probe;
 pub mod scrape;
 pub mod select;
 pub mod select_stream;
+pub mod session;
+pub mod sitemap;
+pub mod source;
@@ -31,6 +31,8 @@ pub struct ParseResponse {
     pub max_depth: usize,
     /// DOM structure information
     pub structure: DomStructure,
+    /// Detected document charset (when encoding detection is enabled)
+    pub encoding: Option<String>,
     /// Request metadata
     pub metadata: ResponseMetadata,
 }
@@ -61,8 +63,13 @@ pub async fn parse_handler(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ParseRequest>,
 ) -> impl IntoResponse {
+    let defaults = crate::domain::parse::config::ParseConfig::default();
     let config = crate::domain::parse::config::ParseConfig {
-        ..Default::default()
+        detect_encoding: request.detect_encoding.unwrap_or(defaults.detect_encoding),
+        handle_malformed: request
+            .handle_malformed
+            .unwrap_or(defaults.handle_malformed),
+        ..defaults
     };
 
     match state.parse_service.parse(&request.html, &config).await {
@@ -75,6 +82,7 @@ pub async fn parse_handler(
                     child_count: result.structure.child_count,
                     well_formed: result.structure.well_formed,
                 },
+                encoding: result.encoding,
                 metadata: ResponseMetadata {
                     request_id: "TODO".to_string(),
                     timestamp: chrono::Utc::now().to_rfc3339(),
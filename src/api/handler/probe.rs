@@ -0,0 +1,61 @@
+//! URL probe handler.
+
+use axum::{extract::State, response::Json};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::{ApiError, ApiResult};
+use crate::api::model::request::ProbeRequest;
+use crate::api::model::response::{ProbeResponse, ResponseMetadata};
+use crate::domain::fetch::config::FetchConfig;
+
+/// Report the status, headers, type, length, redirects and charset of a URL
+/// without downloading its body.
+///
+/// Error statuses (404, 410, 5xx) are reported in the response; the request
+/// only fails when no response could be obtained.
+pub async fn probe_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProbeRequest>,
+) -> ApiResult<Json<ProbeResponse>> {
+    let mut config = FetchConfig {
+        timeout: std::time::Duration::from_millis(request.timeout_ms.unwrap_or(30000)),
+        user_agent: request
+            .user_agent
+            .unwrap_or_else(|| "SCAPI/1.0".to_string()),
+        headers: request.headers,
+        cookies: request.cookies,
+        proxy: request.proxy,
+        retry: state.retry_policy.clone(),
+        ..Default::default()
+    };
+
+    let session = match request.session_id.as_deref() {
+        Some(id) => {
+            let session = state
+                .session_store
+                .get(id)
+                .map_err(|e| ApiError::NotFound(e.to_string()))?;
+            session.apply(&mut config);
+            Some(session)
+        }
+        None => None,
+    };
+
+    let started = std::time::Instant::now();
+    let result = state.fetch_service.probe(&request.url, &config).await;
+
+    if let Some(session) = session {
+        state.session_store.save_in_background(session);
+    }
+
+    let probe = result?;
+    Ok(Json(ProbeResponse {
+        probe,
+        metadata: ResponseMetadata {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            duration_ms: started.elapsed().as_millis(),
+        },
+    }))
+}
@@ -1,16 +1,23 @@
 use crate::api::AppState;
+use crate::api::handler::source;
+use crate::api::model::error::ApiError;
 use crate::domain::select::service::{SelectConfig, SelectService};
 use axum::{
     extract::{Json, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
 pub struct SelectRequest {
+    #[serde(default)]
     pub html: String,
+    /// `warc://file#record-id`, `har://file#index` (one document) or `warc://file`,
+    /// `har://file` (every document) instead of `html`
+    #[serde(default)]
+    pub source: Option<String>,
     pub selector: String,
 }
 
@@ -18,29 +25,86 @@ pub struct SelectRequest {
 pub struct SelectResponse {
     pub count: usize,
     pub matches: Vec<crate::domain::select::service::SelectedElement>,
+    /// Per-record match counts when `source` names a whole archive; `matches`
+    /// holds the records' matches in the same order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub records: Vec<RecordMatches>,
+}
+
+/// Matches found in one archived record.
+#[derive(Debug, Serialize)]
+pub struct RecordMatches {
+    pub record_id: Option<String>,
+    pub target_uri: Option<String>,
+    pub count: usize,
 }
 
 pub async fn select_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SelectRequest>,
 ) -> impl IntoResponse {
+    select_response(&state, payload).await
+}
+
+/// Run the selection over the request's HTML or source documents.
+pub(crate) async fn select_response(state: &AppState, payload: SelectRequest) -> Response {
     let config = SelectConfig {
         selector: payload.selector.clone(),
     };
 
-    // Use the smart selection logic (selects engine based on size)
-    // Note: Since we are in the handler receiving a String, we have already buffered the input.
-    // So "streaming" here just refers to the *engine* used (lol_html vs htmler),
-    // not network streaming.
-    match state.select_service.select(&payload.html, &config) {
-        Ok(matches) => (
-            StatusCode::OK,
-            Json(SelectResponse {
-                count: matches.len(),
-                matches,
-            }),
-        )
-            .into_response(),
-        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    let Some(source) = &payload.source else {
+        // Use the smart selection logic (selects engine based on size)
+        // Note: Since we are in the handler receiving a String, we have already buffered the input.
+        // So "streaming" here just refers to the *engine* used (lol_html vs htmler),
+        // not network streaming.
+        return match state.select_service.select(&payload.html, &config) {
+            Ok(matches) => (
+                StatusCode::OK,
+                Json(SelectResponse {
+                    count: matches.len(),
+                    matches,
+                    records: Vec::new(),
+                }),
+            )
+                .into_response(),
+            Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        };
+    };
+    if !payload.html.is_empty() {
+        return ApiError::BadRequest("give either html or source, not both".to_string())
+            .into_response();
     }
+
+    let documents = match source::load(state, source).await {
+        Ok(documents) => documents,
+        Err(e) => return e.into_response(),
+    };
+    let per_record = source::is_archive(source);
+    let mut matches = Vec::new();
+    let mut records = Vec::new();
+    for document in documents {
+        match state.select_service.select(&document.html, &config) {
+            Ok(found) => {
+                if per_record {
+                    records.push(RecordMatches {
+                        record_id: document.record_id,
+                        target_uri: document.target_uri,
+                        count: found.len(),
+                    });
+                }
+                matches.extend(found);
+            }
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    }
+
+    (
+        StatusCode::OK,
+        Json(SelectResponse {
+            count: matches.len(),
+            matches,
+            records,
+        }),
+    )
+        .into_response()
 }
@@ -1,9 +1,7 @@
 use crate::api::AppState;
-use crate::api::handler::select::{SelectRequest, SelectResponse}; // Reuse types
-use crate::domain::select::service::{SelectConfig, SelectService};
+use crate::api::handler::select::{SelectRequest, select_response}; // Reuse types
 use axum::{
     extract::{Json, State},
-    http::StatusCode,
     response::IntoResponse,
 };
 use std::sync::Arc;
@@ -37,19 +35,5 @@ pub async fn select_stream_handler(
     // For this beta implementation, we map it to the same service method.
     // The "Streaming" value prop is primarily the ENGINE used.
 
-    let config = SelectConfig {
-        selector: payload.selector.clone(),
-    };
-
-    match state.select_service.select(&payload.html, &config) {
-        Ok(matches) => (
-            StatusCode::OK,
-            Json(SelectResponse {
-                count: matches.len(),
-                matches,
-            }),
-        )
-            .into_response(),
-        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
-    }
+    select_response(&state, payload).await
 }
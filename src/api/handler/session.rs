@@ -0,0 +1,73 @@
+//! Session management handlers.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::{ApiError, ApiResult};
+use crate::api::model::request::CreateSessionRequest;
+use crate::api::model::response::SessionResponse;
+use crate::domain::session::{NewSession, Session, SessionError};
+
+/// Create a session.
+pub async fn create_session_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateSessionRequest>,
+) -> ApiResult<(StatusCode, Json<SessionResponse>)> {
+    let session = state
+        .session_store
+        .create(NewSession {
+            ttl: request.ttl_secs.map(std::time::Duration::from_secs),
+            headers: request.headers,
+            cookies: request.cookies,
+            proxy: request.proxy,
+        })
+        .map_err(api_error)?;
+
+    Ok((StatusCode::CREATED, Json(session_response(&session))))
+}
+
+/// Describe a session.
+pub async fn get_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SessionResponse>> {
+    let session = state.session_store.get(&id).map_err(api_error)?;
+    Ok(Json(session_response(&session)))
+}
+
+/// Delete a session and its persisted state.
+pub async fn delete_session_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    state.session_store.delete(&id).map_err(api_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn session_response(session: &Session) -> SessionResponse {
+    let mut headers: Vec<String> = session.headers.keys().cloned().collect();
+    headers.sort();
+    SessionResponse {
+        session_id: session.id.clone(),
+        created_at: session.created_at.to_rfc3339(),
+        expires_at: session.expires_at().to_rfc3339(),
+        ttl_secs: session.ttl.as_secs(),
+        headers,
+        cookie_count: session.cookies.len(),
+        proxy: session.proxy.as_ref().map(|proxy| proxy.display_url()),
+    }
+}
+
+fn api_error(error: SessionError) -> ApiError {
+    match error {
+        SessionError::NotFound(_) => ApiError::NotFound(error.to_string()),
+        SessionError::InvalidRequest(_) => ApiError::ValidationError(error.to_string()),
+        SessionError::LimitReached(_) => ApiError::ServiceUnavailable(error.to_string()),
+        SessionError::Persistence(_) => ApiError::InternalError(error.to_string()),
+    }
+}
@@ -0,0 +1,75 @@
+//! Sitemap discovery handler.
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Json},
+};
+use futures::StreamExt;
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::{ApiError, ApiResult};
+use crate::api::model::request::SitemapRequest;
+use crate::domain::fetch::config::FetchConfig;
+use crate::domain::sitemap::SitemapError;
+
+/// Stream the URLs listed in a site's sitemaps as NDJSON.
+///
+/// A root URL (`https://example.com/`) discovers the sitemaps from
+/// robots.txt and well-known paths; any other URL is read as a sitemap.
+/// Each line is an entry (`loc`, `lastmod`, `changefreq`, `priority`,
+/// `sitemap`) or an `{"error", "sitemap"}` object for a sitemap that failed.
+pub async fn sitemap_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SitemapRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let url = url::Url::parse(&request.url).map_err(|e| ApiError::InvalidUrl(e.to_string()))?;
+    let config = FetchConfig {
+        timeout: std::time::Duration::from_millis(request.timeout_ms.unwrap_or(30000)),
+        user_agent: request
+            .user_agent
+            .unwrap_or_else(|| "SCAPI/1.0".to_string()),
+        retry: state.retry_policy.clone(),
+        ..Default::default()
+    };
+
+    let sitemaps = if matches!(url.path(), "" | "/") && url.query().is_none() {
+        let sitemaps = state
+            .sitemap_service
+            .discover(url.as_str(), &config)
+            .await
+            .map_err(|e| match e {
+                SitemapError::InvalidUrl(message) => ApiError::InvalidUrl(message),
+                other => ApiError::InternalError(other.to_string()),
+            })?;
+        if sitemaps.is_empty() {
+            return Err(ApiError::NotFound(format!("No sitemap found for {}", url)));
+        }
+        sitemaps
+    } else {
+        vec![url.to_string()]
+    };
+
+    let limit = request.limit.unwrap_or(usize::MAX);
+    let lines = state
+        .sitemap_service
+        .entries(sitemaps, config)
+        .take(limit)
+        .map(|item| {
+            let mut line = match item {
+                Ok(entry) => serde_json::to_vec(&entry),
+                Err(e) => serde_json::to_vec(&serde_json::json!({
+                    "error": e.to_string(),
+                    "sitemap": e.sitemap(),
+                })),
+            }?;
+            line.push(b'\n');
+            Ok::<_, serde_json::Error>(line)
+        });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(lines),
+    ))
+}
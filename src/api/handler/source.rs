@@ -0,0 +1,158 @@
+//! Documents loaded from `warc://` and `har://` sources.
+//!
+//! `warc://file#record-id` names one archived response; `warc://file` names
+//! every textual response and resource record in the archive file.
+//! `har://file#index` names one entry of a HAR file; `har://file` names
+//! every entry with a textual response body.
+
+use std::sync::Arc;
+
+use crate::AppState;
+use crate::api::model::error::ApiError;
+use crate::infra::encoding::detector;
+use crate::infra::har::{HarArchive, HarEntry, HarError, HarSource};
+use crate::infra::http::streaming::is_textual;
+use crate::infra::warc::{WarcArchive, WarcError, WarcRecord, WarcSource};
+
+/// Maximum number of records read when a source names a whole archive or HAR file.
+const MAX_ARCHIVE_DOCUMENTS: usize = 1000;
+
+/// A document read from an archive.
+#[derive(Debug)]
+pub struct SourceDocument {
+    /// `WARC-Record-ID` of the record, or the index of the HAR entry
+    pub record_id: Option<String>,
+    /// `WARC-Target-URI` of the record, or the request URL of the HAR entry
+    pub target_uri: Option<String>,
+    /// Decoded payload
+    pub html: String,
+}
+
+/// Whether `source` names a whole archive or HAR file rather than one record.
+pub fn is_archive(source: &str) -> bool {
+    if HarSource::is_har(source) {
+        return source
+            .parse::<HarSource>()
+            .is_ok_and(|source| source.entry.is_none());
+    }
+    source
+        .parse::<WarcSource>()
+        .is_ok_and(|source| source.record_id.is_none())
+}
+
+/// Load the documents named by `source`.
+pub async fn load(state: &AppState, source: &str) -> Result<Vec<SourceDocument>, ApiError> {
+    if HarSource::is_har(source) {
+        load_har(state.har_archive.clone(), source).await
+    } else {
+        load_warc(state.warc_archive.clone(), source).await
+    }
+}
+
+async fn load_warc(
+    archive: Arc<WarcArchive>,
+    source: &str,
+) -> Result<Vec<SourceDocument>, ApiError> {
+    let source: WarcSource = source.parse().map_err(api_error)?;
+    tokio::task::spawn_blocking(move || match &source.record_id {
+        Some(record_id) => {
+            let record = archive.find(&source.file, record_id)?;
+            Ok(vec![document(&record)?])
+        }
+        None => {
+            let mut documents = Vec::new();
+            for record in archive.documents(&source.file)? {
+                let record = record?;
+                let payload = record.payload()?;
+                if !is_textual(payload.header("content-type")) {
+                    continue;
+                }
+                if documents.len() == MAX_ARCHIVE_DOCUMENTS {
+                    return Err(WarcError::InvalidSource(format!(
+                        "{} holds more than {} documents",
+                        source.file, MAX_ARCHIVE_DOCUMENTS
+                    )));
+                }
+                documents.push(document(&record)?);
+            }
+            Ok(documents)
+        }
+    })
+    .await
+    .map_err(|e| ApiError::InternalError(e.to_string()))?
+    .map_err(api_error)
+}
+
+fn document(record: &WarcRecord) -> Result<SourceDocument, WarcError> {
+    let payload = record.payload()?;
+    let detected = detector::detect(payload.header("content-type"), &payload.body);
+    let (html, _) = detector::decode(&payload.body, &detected);
+    Ok(SourceDocument {
+        record_id: record.record_id().map(str::to_string),
+        target_uri: record.target_uri().map(str::to_string),
+        html,
+    })
+}
+
+async fn load_har(archive: Arc<HarArchive>, source: &str) -> Result<Vec<SourceDocument>, ApiError> {
+    let source: HarSource = source.parse().map_err(har_api_error)?;
+    tokio::task::spawn_blocking(move || match source.entry {
+        Some(index) => Ok(vec![har_document(
+            index,
+            &archive.find(&source.file, index)?,
+        )?]),
+        None => {
+            let mut documents = Vec::new();
+            for (index, entry) in archive.documents(&source.file)? {
+                let mime_type = entry.response.content.mime_type.as_str();
+                if !is_textual(Some(mime_type).filter(|mime| !mime.is_empty())) {
+                    continue;
+                }
+                if documents.len() == MAX_ARCHIVE_DOCUMENTS {
+                    return Err(HarError::InvalidSource(format!(
+                        "{} holds more than {} documents",
+                        source.file, MAX_ARCHIVE_DOCUMENTS
+                    )));
+                }
+                documents.push(har_document(index, &entry)?);
+            }
+            Ok(documents)
+        }
+    })
+    .await
+    .map_err(|e| ApiError::InternalError(e.to_string()))?
+    .map_err(har_api_error)
+}
+
+fn har_document(index: usize, entry: &HarEntry) -> Result<SourceDocument, HarError> {
+    let content = &entry.response.content;
+    let body = content
+        .body()
+        .map_err(|e| HarError::Format(format!("entry {}: {}", index, e)))?;
+    let content_type = Some(content.mime_type.as_str()).filter(|mime| !mime.is_empty());
+    let detected = detector::detect(content_type, &body);
+    let (html, _) = detector::decode(&body, &detected);
+    Ok(SourceDocument {
+        record_id: Some(index.to_string()),
+        target_uri: Some(entry.request.url.clone()).filter(|url| !url.is_empty()),
+        html,
+    })
+}
+
+fn har_api_error(error: HarError) -> ApiError {
+    match error {
+        HarError::NotFound(_) => ApiError::NotFound(error.to_string()),
+        HarError::InvalidSource(_) | HarError::Format(_) => ApiError::BadRequest(error.to_string()),
+        HarError::Io(_) => ApiError::InternalError(error.to_string()),
+    }
+}
+
+fn api_error(error: WarcError) -> ApiError {
+    match error {
+        WarcError::NotFound(_) => ApiError::NotFound(error.to_string()),
+        WarcError::InvalidSource(_) | WarcError::Format(_) => {
+            ApiError::BadRequest(error.to_string())
+        }
+        WarcError::Io(_) => ApiError::InternalError(error.to_string()),
+    }
+}
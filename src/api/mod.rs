@@ -7,7 +7,7 @@ pub mod middleware;
 pub mod model;
 
 use axum::Router;
-use axum::routing::post;
+use axum::routing::{delete, get, post};
 use std::sync::Arc;
 
 use crate::AppState;
@@ -27,6 +27,30 @@ pub fn create_router(state: AppState) -> Router {
             "/api/v1/select-stream",
             post(handler::select_stream::select_stream_handler),
         )
+        .route(
+            "/api/v1/sessions",
+            post(handler::session::create_session_handler),
+        )
+        .route(
+            "/api/v1/sessions/:id",
+            get(handler::session::get_session_handler)
+                .delete(handler::session::delete_session_handler),
+        )
+        .route(
+            "/api/v1/har",
+            get(handler::har::export_har_handler).delete(handler::har::clear_har_handler),
+        )
+        .route("/api/v1/sitemap", post(handler::sitemap::sitemap_handler))
+        .route("/api/v1/probe", post(handler::probe::probe_handler))
+        .route("/api/v1/metrics", get(handler::metrics::metrics_handler))
+        .route(
+            "/api/v1/admin/breakers",
+            get(handler::admin::list_breakers_handler),
+        )
+        .route(
+            "/api/v1/admin/breakers/:host",
+            delete(handler::admin::reset_breaker_handler),
+        )
         .layer(axum::extract::DefaultBodyLimit::max(50 * 1024 * 1024)) // 50MB limit
         .with_state(Arc::new(state))
     // Middleware layers will be added when middleware is implemented
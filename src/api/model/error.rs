@@ -7,6 +7,8 @@ use axum::{
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::domain::fetch::error::FetchError;
+
 /// API error response format.
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
@@ -60,6 +62,14 @@ pub enum ApiError {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    /// Fetch target disallowed, e.g. by robots.txt (403)
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Fetch target blocked by the egress policy (403)
+    #[error("Egress denied: {0}")]
+    EgressDenied(String),
+
     /// Internal server error (500)
     #[error("Internal server error: {0}")]
     InternalError(String),
@@ -85,6 +95,8 @@ impl ApiError {
             ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
             ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::EgressDenied(_) => StatusCode::FORBIDDEN,
             ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
@@ -102,6 +114,8 @@ impl ApiError {
             ApiError::Unauthorized(_) => "UNAUTHORIZED",
             ApiError::RateLimited(_) => "RATE_LIMITED",
             ApiError::NotFound(_) => "NOT_FOUND",
+            ApiError::Forbidden(_) => "FORBIDDEN",
+            ApiError::EgressDenied(_) => "EGRESS_DENIED",
             ApiError::InternalError(_) => "INTERNAL_ERROR",
             ApiError::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
             ApiError::Timeout(_) => "TIMEOUT",
@@ -126,5 +140,19 @@ impl IntoResponse for ApiError {
     }
 }
 
+impl From<FetchError> for ApiError {
+    fn from(e: FetchError) -> Self {
+        match e {
+            FetchError::InvalidUrl(message) => ApiError::InvalidUrl(message),
+            FetchError::InvalidRequest(message) => ApiError::BadRequest(message),
+            FetchError::Timeout(message) => ApiError::Timeout(message),
+            FetchError::RobotsDisallowed(message) => ApiError::Forbidden(message),
+            FetchError::EgressDenied(message) => ApiError::EgressDenied(message),
+            FetchError::CircuitOpen(message) => ApiError::ServiceUnavailable(message),
+            other => ApiError::InternalError(format!("Fetch failed: {}", other)),
+        }
+    }
+}
+
 /// Convenience type alias for API results.
 pub type ApiResult<T> = Result<T, ApiError>;
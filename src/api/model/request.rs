@@ -1,6 +1,13 @@
 //! Request types for SCAPI endpoints.
 
 use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::domain::fetch::config::{HttpMethod, RequestBody, StatusRange};
+use crate::domain::fetch::cookies::Cookie;
+use crate::domain::fetch::error::FetchErrorKind;
+use crate::domain::fetch::proxy::ProxyConfig;
+use crate::domain::fetch::retry::RetryPolicy;
 
 /// Fetch request type.
 #[derive(Debug, Deserialize)]
@@ -11,6 +18,80 @@ pub struct FetchRequest {
     pub timeout_ms: Option<u64>,
     /// Optional user agent
     pub user_agent: Option<String>,
+    /// HTTP method (defaults to GET)
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookies to send
+    #[serde(default)]
+    pub cookies: HashMap<String, String>,
+    /// Optional request body (raw, form or json)
+    pub body: Option<RequestBody>,
+    /// Stream the body transcoded to UTF-8
+    #[serde(default)]
+    pub transcode: bool,
+    /// Optional retry policy overrides
+    pub retry: Option<RetryOptions>,
+    /// Proxy for this request (overrides configured proxies)
+    pub proxy: Option<ProxyConfig>,
+    /// Non-2xx statuses to return instead of failing (e.g. `[404, "410", "5xx"]`)
+    #[serde(default)]
+    pub accept_statuses: Vec<StatusRange>,
+    /// Session whose cookies, headers and proxy apply to this request
+    pub session_id: Option<String>,
+    /// Return a JSON envelope (body, status, headers, redirects) instead of
+    /// streaming the raw body
+    #[serde(default)]
+    pub envelope: bool,
+}
+
+/// Retry policy overrides for a single fetch.
+#[derive(Debug, Default, Deserialize)]
+pub struct RetryOptions {
+    /// Total number of attempts, including the first
+    pub max_attempts: Option<u32>,
+    /// Delay before the first retry in milliseconds
+    pub base_delay_ms: Option<u64>,
+    /// Upper bound for any single delay in milliseconds
+    pub max_delay_ms: Option<u64>,
+    /// Fraction of each delay (0.0 - 1.0) that is randomized
+    pub jitter: Option<f64>,
+    /// Error kinds that are retried
+    pub retry_on: Option<Vec<FetchErrorKind>>,
+    /// HTTP status codes that are retried
+    pub retry_statuses: Option<Vec<u16>>,
+    /// Honor `Retry-After` on 429 and 503 responses
+    pub respect_retry_after: Option<bool>,
+    /// Also retry non-idempotent methods (POST)
+    pub retry_non_idempotent: Option<bool>,
+}
+
+impl RetryOptions {
+    /// Apply these overrides on top of `policy`.
+    pub fn apply(self, policy: RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts.unwrap_or(policy.max_attempts),
+            base_delay: self
+                .base_delay_ms
+                .map(std::time::Duration::from_millis)
+                .unwrap_or(policy.base_delay),
+            max_delay: self
+                .max_delay_ms
+                .map(std::time::Duration::from_millis)
+                .unwrap_or(policy.max_delay),
+            jitter: self.jitter.unwrap_or(policy.jitter),
+            retry_on: self.retry_on.unwrap_or(policy.retry_on),
+            retry_statuses: self.retry_statuses.unwrap_or(policy.retry_statuses),
+            respect_retry_after: self
+                .respect_retry_after
+                .unwrap_or(policy.respect_retry_after),
+            retry_non_idempotent: self
+                .retry_non_idempotent
+                .unwrap_or(policy.retry_non_idempotent),
+        }
+    }
 }
 
 /// Parse request type.
@@ -60,6 +141,23 @@ pub struct ScrapeRequest {
     pub timeout_ms: Option<u64>,
     /// Optional user agent
     pub user_agent: Option<String>,
+    /// Session whose cookies, headers and proxy apply to the fetch
+    pub session_id: Option<String>,
+}
+
+/// Session creation request type.
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateSessionRequest {
+    /// Idle TTL in seconds (defaults to the configured TTL)
+    pub ttl_secs: Option<u64>,
+    /// Headers sent with every request of the session
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookies the session starts with
+    #[serde(default)]
+    pub cookies: Vec<Cookie>,
+    /// Proxy used for every request of the session
+    pub proxy: Option<ProxyConfig>,
 }
 
 /// Extract request type.
@@ -88,4 +186,38 @@ pub struct ExtractionRule {
     pub data_type: String,
     /// Whether the field is required
     pub required: bool,
-}
\ No newline at end of file
+}
+
+/// Sitemap request type.
+#[derive(Debug, Deserialize)]
+pub struct SitemapRequest {
+    /// Site to discover sitemaps for (root URL) or a sitemap URL to read
+    pub url: String,
+    /// Maximum number of URL entries to return (capped by the server limit)
+    pub limit: Option<usize>,
+    /// Optional timeout per sitemap in milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Optional user agent
+    pub user_agent: Option<String>,
+}
+
+/// Probe request type.
+#[derive(Debug, Deserialize)]
+pub struct ProbeRequest {
+    /// URL to probe
+    pub url: String,
+    /// Optional timeout in milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Optional user agent
+    pub user_agent: Option<String>,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookies to send
+    #[serde(default)]
+    pub cookies: HashMap<String, String>,
+    /// Proxy for this request (overrides configured proxies)
+    pub proxy: Option<ProxyConfig>,
+    /// Session whose cookies, headers and proxy apply to this request
+    pub session_id: Option<String>,
+}
@@ -2,6 +2,12 @@
 
 use serde::Serialize;
 
+use crate::domain::fetch::breaker::BreakerStatus;
+use crate::domain::fetch::coalesce::CoalesceStats;
+use crate::domain::fetch::probe::ProbeResult;
+use crate::domain::fetch::redirect::RedirectHop;
+use crate::infra::http::resolver::Resolution;
+
 /// Common response metadata.
 #[derive(Debug, Serialize)]
 pub struct ResponseMetadata {
@@ -24,10 +30,43 @@ pub struct FetchResponse {
     pub status_code: u16,
     /// Final URL after redirects
     pub final_url: String,
+    /// Charset the content was decoded from
+    pub encoding: Option<String>,
+    /// Number of fetch attempts made
+    pub attempts: u32,
+    /// Proxy the fetch went through (credentials masked)
+    pub proxy: Option<String>,
+    /// Cache outcome (HIT, MISS, REVALIDATED or BYPASS)
+    pub cache_status: Option<String>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Final response headers as `[name, value]` pairs in received order
+    pub headers: Vec<(String, String)>,
+    /// How the final host was resolved (addresses, remote IP, timing)
+    pub dns: Option<Resolution>,
     /// Response metadata
     pub metadata: ResponseMetadata,
 }
 
+/// Session response type.
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    /// Session identifier to pass as `session_id`
+    pub session_id: String,
+    /// Creation time in RFC3339 format
+    pub created_at: String,
+    /// Expiry time in RFC3339 format (extended on every use)
+    pub expires_at: String,
+    /// Idle TTL in seconds
+    pub ttl_secs: u64,
+    /// Names of the default headers
+    pub headers: Vec<String>,
+    /// Number of cookies in the jar
+    pub cookie_count: usize,
+    /// Sticky proxy (credentials masked)
+    pub proxy: Option<String>,
+}
+
 /// Parse response type.
 #[derive(Debug, Serialize)]
 pub struct ParseResponse {
@@ -37,6 +76,8 @@ pub struct ParseResponse {
     pub max_depth: usize,
     /// DOM structure information
     pub structure: DomStructure,
+    /// Detected document charset
+    pub encoding: Option<String>,
     /// Response metadata
     pub metadata: ResponseMetadata,
 }
@@ -148,4 +189,28 @@ pub struct ExtractionStats {
     pub failed: usize,
     /// Time taken in milliseconds
     pub time_ms: u128,
-}
\ No newline at end of file
+}
+
+/// Probe response type.
+#[derive(Debug, Serialize)]
+pub struct ProbeResponse {
+    /// What the probe found out about the URL
+    #[serde(flatten)]
+    pub probe: ProbeResult,
+    /// Response metadata
+    pub metadata: ResponseMetadata,
+}
+
+/// Metrics response type.
+#[derive(Debug, Serialize)]
+pub struct MetricsResponse {
+    /// Upstream, coalesced and in-flight fetch counts
+    pub coalescing: CoalesceStats,
+}
+
+/// Circuit breakers response type.
+#[derive(Debug, Serialize)]
+pub struct BreakersResponse {
+    /// Breaker state of every tracked host
+    pub breakers: Vec<BreakerStatus>,
+}
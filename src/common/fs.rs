@@ -0,0 +1,68 @@
+//! File helpers shared by the on-disk stores.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Write `path` through a uniquely named temporary file and rename it into
+/// place, so readers never see partial data and concurrent writers of the
+/// same path cannot interleave.
+pub fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let temp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+    let result = (|| {
+        let mut file = BufWriter::new(File::create(&temp)?);
+        write(&mut file)?;
+        file.flush()?;
+        file.into_inner()?.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Run blocking file I/O on the blocking thread pool when called from the
+/// runtime, or inline otherwise.
+pub fn spawn_blocking_io(io: impl FnOnce() + Send + 'static) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn_blocking(io);
+        }
+        Err(_) => io(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_writes_do_not_interleave() {
+        let dir = std::env::temp_dir().join(format!("scapi-fs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("entry.cache");
+
+        let writers: Vec<_> = (0..8u8)
+            .map(|byte| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    write_atomic(&path, |file| file.write_all(&[byte; 64 * 1024])).unwrap()
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(written.len(), 64 * 1024);
+        assert!(written.iter().all(|&byte| byte == written[0]));
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
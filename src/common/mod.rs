@@ -1,6 +1,7 @@
 //! Common utilities and shared types for SCAPI.
 
 pub mod error;
+pub mod fs;
 pub mod metrics;
 pub mod types;
 
@@ -131,4 +131,12 @@ impl std::fmt::Display for ContentSize {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} bytes", self.0)
     }
-}
\ No newline at end of file
+}
+/// First value of header `name` among `(name, value)` pairs, matched
+/// case-insensitively.
+pub fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
@@ -0,0 +1,455 @@
+//! HTTP authentication and the per-host credential store.
+//!
+//! Credentials live on the server, keyed by host pattern, and are attached
+//! by the fetch service to requests for matching hosts; clients never send
+//! them. Secrets are masked in `Debug` output and recorded exchanges, and
+//! never appear in error messages.
+
+use base64::Engine;
+use md5::Md5;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Replacement for secrets in logs, errors and recorded exchanges.
+pub const REDACTED: &str = "***";
+
+/// Credentials for one host pattern.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Credentials {
+    /// `Authorization: Basic`
+    Basic {
+        /// User name
+        username: String,
+        /// Password
+        password: String,
+    },
+    /// `Authorization: Bearer`
+    Bearer {
+        /// Bearer token
+        token: String,
+    },
+    /// `Authorization: Digest`, answered after the server's 401 challenge
+    Digest {
+        /// User name
+        username: String,
+        /// Password
+        password: String,
+    },
+    /// API key sent in a custom header, e.g. `X-Api-Key`
+    ApiKey {
+        /// Header name
+        header: String,
+        /// Key
+        value: String,
+    },
+}
+
+impl Credentials {
+    /// Scheme name, safe for logs.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Credentials::Basic { .. } => "basic",
+            Credentials::Bearer { .. } => "bearer",
+            Credentials::Digest { .. } => "digest",
+            Credentials::ApiKey { .. } => "api_key",
+        }
+    }
+
+    /// Header carrying the credentials.
+    pub fn header_name(&self) -> &str {
+        match self {
+            Credentials::ApiKey { header, .. } => header,
+            _ => "authorization",
+        }
+    }
+
+    /// Header to send up front; Digest needs a challenge first and has none.
+    pub fn header(&self) -> Option<(&str, String)> {
+        let value = match self {
+            Credentials::Basic { username, password } => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password))
+            ),
+            Credentials::Bearer { token } => format!("Bearer {}", token),
+            Credentials::ApiKey { value, .. } => value.clone(),
+            Credentials::Digest { .. } => return None,
+        };
+        Some((self.header_name(), value))
+    }
+}
+
+/// Secrets are masked so configs can be logged safely.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Basic { username, .. } | Credentials::Digest { username, .. } => f
+                .debug_struct(self.scheme())
+                .field("username", username)
+                .field("password", &REDACTED)
+                .finish(),
+            Credentials::Bearer { .. } => f
+                .debug_struct(self.scheme())
+                .field("token", &REDACTED)
+                .finish(),
+            Credentials::ApiKey { header, .. } => f
+                .debug_struct(self.scheme())
+                .field("header", header)
+                .field("value", &REDACTED)
+                .finish(),
+        }
+    }
+}
+
+/// Server-side credentials keyed by host pattern.
+///
+/// A pattern is a host (`portal.example.com`), a host and port
+/// (`portal.example.com:8443`) or a wildcard for subdomains
+/// (`*.example.com`, which does not match `example.com` itself). The most
+/// specific pattern wins: host and port, then host, then the closest
+/// wildcard.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct CredentialStore {
+    hosts: HashMap<String, Credentials>,
+}
+
+impl CredentialStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a JSON object mapping host patterns to credentials.
+    ///
+    /// ```json
+    /// {
+    ///   "portal.example.com": { "type": "basic", "username": "scapi", "password": "..." },
+    ///   "*.api.example.net": { "type": "api_key", "header": "X-Api-Key", "value": "..." }
+    /// }
+    /// ```
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let store: Self = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(store.normalized())
+    }
+
+    /// Add credentials for a host pattern.
+    pub fn with_host(mut self, pattern: impl Into<String>, credentials: Credentials) -> Self {
+        self.hosts
+            .insert(pattern.into().trim().to_ascii_lowercase(), credentials);
+        self
+    }
+
+    /// Configured host patterns.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.hosts.keys().map(String::as_str)
+    }
+
+    /// Number of host patterns.
+    pub fn len(&self) -> usize {
+        self.hosts.len()
+    }
+
+    /// Whether no credentials are configured.
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Credentials for `url`, if a pattern matches its host.
+    pub fn find(&self, url: &str) -> Option<&Credentials> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+        let url = url::Url::parse(url).ok()?;
+        match_host(&self.hosts, &url).map(|(_, credentials)| credentials)
+    }
+
+    fn normalized(self) -> Self {
+        self.hosts
+            .into_iter()
+            .fold(Self::default(), |store, (pattern, credentials)| {
+                store.with_host(pattern, credentials)
+            })
+    }
+}
+
+/// Find the most specific host pattern in `hosts` matching `url`.
+///
+/// Patterns are lowercase hosts, `host:port` pairs or `*.domain` wildcards;
+/// host and port wins over host, which wins over the closest wildcard.
+pub(crate) fn match_host<'a, T>(
+    hosts: &'a HashMap<String, T>,
+    url: &url::Url,
+) -> Option<(&'a str, &'a T)> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let lookup = |pattern: &str| hosts.get_key_value(pattern);
+
+    let found = url
+        .port()
+        .and_then(|port| lookup(&format!("{}:{}", host, port)))
+        .or_else(|| lookup(&host))
+        .or_else(|| {
+            let mut domain = host.as_str();
+            while let Some((_, parent)) = domain.split_once('.') {
+                if let Some(found) = lookup(&format!("*.{}", parent)) {
+                    return Some(found);
+                }
+                domain = parent;
+            }
+            None
+        });
+    found.map(|(pattern, value)| (pattern.as_str(), value))
+}
+
+/// A `WWW-Authenticate: Digest` challenge (RFC 7616).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    /// Whether the server offers `qop=auth`
+    qop_auth: bool,
+}
+
+impl DigestChallenge {
+    /// Find a Digest challenge with a supported algorithm among
+    /// `WWW-Authenticate` header values.
+    pub fn from_headers<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        values.into_iter().find_map(Self::parse)
+    }
+
+    /// Parse one `WWW-Authenticate` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim_start();
+        let params = value
+            .get(..6)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("digest"))
+            .map(|_| auth_params(&value[6..]))?;
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+
+        let algorithm = param("algorithm").unwrap_or_else(|| "MD5".to_string());
+        if !matches!(
+            algorithm.to_ascii_uppercase().as_str(),
+            "MD5" | "MD5-SESS" | "SHA-256" | "SHA-256-SESS"
+        ) {
+            return None;
+        }
+        Some(Self {
+            realm: param("realm").unwrap_or_default(),
+            nonce: param("nonce")?,
+            opaque: param("opaque"),
+            algorithm,
+            qop_auth: param("qop").is_some_and(|qop| {
+                qop.split(',')
+                    .any(|qop| qop.trim().eq_ignore_ascii_case("auth"))
+            }),
+        })
+    }
+
+    /// `Authorization` value answering the challenge for `method` on `uri`
+    /// (path and query of the request URL).
+    pub fn authorization(&self, username: &str, password: &str, method: &str, uri: &str) -> String {
+        let cnonce = format!("{:016x}", rand::random::<u64>());
+        self.authorization_with(username, password, method, uri, &cnonce)
+    }
+
+    fn authorization_with(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+    ) -> String {
+        let algorithm = self.algorithm.to_ascii_uppercase();
+        let hash = |data: String| {
+            if algorithm.starts_with("SHA-256") {
+                format!("{:x}", Sha256::digest(data))
+            } else {
+                format!("{:x}", Md5::digest(data))
+            }
+        };
+        // Each challenge is answered once, so the nonce count is always 1
+        let nc = "00000001";
+
+        let mut ha1 = hash(format!("{}:{}:{}", username, self.realm, password));
+        if algorithm.ends_with("-SESS") {
+            ha1 = hash(format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = hash(format!("{}:{}", method, uri));
+        let response = if self.qop_auth {
+            hash(format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, self.nonce, nc, cnonce, ha2
+            ))
+        } else {
+            hash(format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            username, self.realm, self.nonce, uri, self.algorithm, response
+        );
+        if self.qop_auth {
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        header
+    }
+}
+
+/// Parse comma-separated `name=value` / `name="quoted value"` parameters.
+fn auth_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = input.trim_start_matches([' ', ',']);
+    while let Some((name, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = index + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        params.push((name.trim().to_string(), value));
+        rest = remaining.trim_start_matches([' ', ',']);
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_matching_and_redaction() {
+        let store = CredentialStore::new()
+            .with_host(
+                "Portal.Example.com",
+                Credentials::Basic {
+                    username: "scapi".to_string(),
+                    password: "s3cret".to_string(),
+                },
+            )
+            .with_host(
+                "portal.example.com:8443",
+                Credentials::Bearer {
+                    token: "t0ken".to_string(),
+                },
+            )
+            .with_host(
+                "*.example.com",
+                Credentials::ApiKey {
+                    header: "X-Api-Key".to_string(),
+                    value: "k3y".to_string(),
+                },
+            );
+
+        let scheme = |url: &str| store.find(url).map(Credentials::scheme);
+        assert_eq!(scheme("https://portal.example.com/a"), Some("basic"));
+        assert_eq!(scheme("https://portal.example.com:8443/a"), Some("bearer"));
+        assert_eq!(scheme("https://a.b.example.com/"), Some("api_key"));
+        assert_eq!(scheme("https://example.com/"), None);
+        assert_eq!(scheme("https://example.org/"), None);
+
+        let (name, value) = store
+            .find("https://portal.example.com/")
+            .unwrap()
+            .header()
+            .unwrap();
+        assert_eq!(name, "authorization");
+        assert_eq!(value, "Basic c2NhcGk6czNjcmV0");
+
+        let logged = format!("{:?}", store);
+        for secret in ["s3cret", "t0ken", "k3y"] {
+            assert!(!logged.contains(secret), "{} leaked in {}", secret, logged);
+        }
+    }
+
+    #[test]
+    fn test_digest_response() {
+        // RFC 2617 section 3.5 example
+        let challenge = DigestChallenge::from_headers([
+            "Basic realm=\"fallback\"",
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+             opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        ])
+        .unwrap();
+        let header = challenge.authorization_with(
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            "0a4f113b",
+        );
+        assert!(header.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(header.contains("qop=auth, nc=00000001, cnonce=\"0a4f113b\""));
+        assert!(header.contains("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+
+        assert!(
+            DigestChallenge::parse("Digest realm=\"r\", nonce=\"n\", algorithm=SHA-512").is_none()
+        );
+        assert!(DigestChallenge::parse("Bearer realm=\"r\"").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stored_credentials_are_not_sent_through_request_proxies() {
+        use crate::domain::fetch::config::FetchConfig;
+        use crate::domain::fetch::error::FetchError;
+        use crate::domain::fetch::proxy::ProxyConfig;
+        use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+        use crate::infra::http::HttpClient;
+
+        let service = DefaultFetchService::new(HttpClient::new().unwrap()).with_credentials(
+            CredentialStore::new().with_host(
+                "portal.example.com",
+                Credentials::Bearer {
+                    token: "t0ken".to_string(),
+                },
+            ),
+        );
+        let config = FetchConfig {
+            respect_robots: false,
+            proxy: Some(ProxyConfig::new("http://127.0.0.1:1")),
+            ..FetchConfig::default()
+        };
+        let error = service
+            .fetch("http://portal.example.com/account", &config)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, FetchError::InvalidRequest(message) if !message.contains("t0ken")),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,576 @@
+//! Per-host circuit breaker.
+//!
+//! A breaker starts closed. It opens after `consecutive_failures` failures
+//! in a row, or once the failure rate over the last `window` outcomes reaches
+//! `failure_rate`. While open, requests to the host fail fast with
+//! `FetchError::CircuitOpen` instead of waiting out their timeout. After
+//! `cooldown` the breaker is half-open and lets `half_open_probes` requests
+//! through: a success closes it, a failure opens it again.
+//!
+//! Connection failures, timeouts and `failure_statuses` count as failures;
+//! errors raised before the host is contacted are ignored.
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::error::{FetchError, FetchErrorKind};
+
+/// Maximum number of hosts tracked at once.
+const MAX_TRACKED_HOSTS: usize = 10_000;
+
+/// Circuit breaker configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BreakerConfig {
+    /// Whether breakers are applied at all
+    pub enabled: bool,
+    /// Consecutive failures that open the breaker
+    pub consecutive_failures: u32,
+    /// Failure rate (0.0-1.0) over the window that opens the breaker
+    pub failure_rate: f64,
+    /// Number of recent outcomes the failure rate is computed over
+    pub window: usize,
+    /// Outcomes needed in the window before the failure rate applies
+    pub min_requests: usize,
+    /// How long an open breaker fails fast before probing the host again
+    pub cooldown: Duration,
+    /// Requests let through at once while half-open
+    pub half_open_probes: u32,
+    /// Response statuses counted as failures
+    pub failure_statuses: Vec<u16>,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            consecutive_failures: 5,
+            failure_rate: 0.5,
+            window: 20,
+            min_requests: 10,
+            cooldown: Duration::from_secs(30),
+            half_open_probes: 1,
+            failure_statuses: vec![502, 503, 504],
+        }
+    }
+}
+
+/// State of a host's breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests flow normally
+    Closed,
+    /// Requests fail fast until the cooldown ends
+    Open,
+    /// A limited number of probe requests decide whether to close
+    HalfOpen,
+}
+
+/// Snapshot of a host's breaker.
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerStatus {
+    /// Host name
+    pub host: String,
+    /// Current state
+    pub state: BreakerState,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    /// Failure rate over the recent outcomes
+    pub failure_rate: f64,
+    /// Number of recent outcomes the rate is based on
+    pub recent_requests: usize,
+    /// When the breaker last opened
+    pub opened_at: Option<DateTime<Utc>>,
+    /// Milliseconds until an open breaker lets a probe through
+    pub retry_in_ms: Option<u64>,
+    /// Times the breaker opened
+    pub trips: u64,
+}
+
+/// Breaker state of a single host.
+#[derive(Debug)]
+struct HostBreaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    /// Recent outcomes, `true` for failures
+    recent: VecDeque<bool>,
+    open_until: Option<Instant>,
+    opened_at: Option<DateTime<Utc>>,
+    probes_in_flight: u32,
+    trips: u64,
+}
+
+impl HostBreaker {
+    fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            recent: VecDeque::new(),
+            open_until: None,
+            opened_at: None,
+            probes_in_flight: 0,
+            trips: 0,
+        }
+    }
+
+    fn failure_rate(&self) -> f64 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.recent.iter().filter(|&&failed| failed).count() as f64 / self.recent.len() as f64
+    }
+
+    /// State as seen by the next request.
+    fn current_state(&self, now: Instant) -> BreakerState {
+        match self.open_until {
+            Some(until) if self.state == BreakerState::Open && until <= now => {
+                BreakerState::HalfOpen
+            }
+            _ => self.state,
+        }
+    }
+
+    fn open(&mut self, config: &BreakerConfig) {
+        self.state = BreakerState::Open;
+        self.open_until = Some(Instant::now() + config.cooldown);
+        self.opened_at = Some(Utc::now());
+        self.consecutive_failures = 0;
+        self.recent.clear();
+        self.trips += 1;
+    }
+
+    fn close(&mut self) {
+        self.state = BreakerState::Closed;
+        self.open_until = None;
+        self.consecutive_failures = 0;
+        self.recent.clear();
+    }
+}
+
+/// Circuit breakers for every host fetched from.
+#[derive(Debug)]
+pub struct CircuitBreakers {
+    config: Arc<BreakerConfig>,
+    hosts: Mutex<LruCache<String, Arc<Mutex<HostBreaker>>>>,
+}
+
+impl CircuitBreakers {
+    /// Create breakers with the given configuration.
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            hosts: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_TRACKED_HOSTS).unwrap())),
+        }
+    }
+
+    /// Get the breaker configuration.
+    pub fn config(&self) -> &BreakerConfig {
+        &self.config
+    }
+
+    /// Fail fast if the breaker for the host of `url` is open.
+    ///
+    /// Unlike [`CircuitBreakers::allow`] this does not take a half-open
+    /// probe slot, so it suits work done before the request (robots.txt).
+    pub fn check(&self, url: &str) -> Result<(), FetchError> {
+        let Some(host) = self.host_of(url) else {
+            return Ok(());
+        };
+        let Some(breaker) = self.hosts.lock().unwrap().get(&host).cloned() else {
+            return Ok(());
+        };
+        let breaker = breaker.lock().unwrap();
+        match breaker.current_state(Instant::now()) {
+            BreakerState::Open => Err(open_error(&host, &breaker)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ask to send one request to the host of `url`.
+    ///
+    /// Returns `None` when breakers are disabled or the URL has no host;
+    /// report the outcome with [`BreakerPermit::record`].
+    pub fn allow(&self, url: &str) -> Result<Option<BreakerPermit>, FetchError> {
+        let Some(host) = self.host_of(url) else {
+            return Ok(None);
+        };
+        let breaker = self.state_for(&host);
+        let probe = {
+            let mut state = breaker.lock().unwrap();
+            match state.current_state(Instant::now()) {
+                BreakerState::Closed => false,
+                BreakerState::Open => return Err(open_error(&host, &state)),
+                BreakerState::HalfOpen => {
+                    if state.probes_in_flight >= self.config.half_open_probes.max(1) {
+                        return Err(open_error(&host, &state));
+                    }
+                    if state.state == BreakerState::Open {
+                        tracing::info!("Circuit for {} is half-open, probing", host);
+                        state.state = BreakerState::HalfOpen;
+                    }
+                    state.probes_in_flight += 1;
+                    true
+                }
+            }
+        };
+
+        Ok(Some(BreakerPermit {
+            host,
+            breaker,
+            config: self.config.clone(),
+            probe,
+        }))
+    }
+
+    /// Breaker state of every tracked host.
+    pub fn status(&self) -> Vec<BreakerStatus> {
+        let now = Instant::now();
+        let hosts = self.hosts.lock().unwrap();
+        let mut status: Vec<BreakerStatus> = hosts
+            .iter()
+            .map(|(host, breaker)| {
+                let breaker = breaker.lock().unwrap();
+                let state = breaker.current_state(now);
+                BreakerStatus {
+                    host: host.clone(),
+                    state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    failure_rate: breaker.failure_rate(),
+                    recent_requests: breaker.recent.len(),
+                    opened_at: breaker.opened_at,
+                    retry_in_ms: breaker
+                        .open_until
+                        .filter(|_| state == BreakerState::Open)
+                        .map(|until| (until - now).as_millis() as u64),
+                    trips: breaker.trips,
+                }
+            })
+            .collect();
+        status.sort_by(|a, b| a.host.cmp(&b.host));
+        status
+    }
+
+    /// Close the breaker of `host`, returning whether it was tracked.
+    pub fn reset(&self, host: &str) -> bool {
+        let hosts = self.hosts.lock().unwrap();
+        let Some(breaker) = hosts.peek(&host.to_ascii_lowercase()) else {
+            return false;
+        };
+        breaker.lock().unwrap().close();
+        tracing::info!("Circuit for {} reset", host);
+        true
+    }
+
+    fn host_of(&self, url: &str) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        url::Url::parse(url)
+            .ok()?
+            .host_str()
+            .map(str::to_ascii_lowercase)
+    }
+
+    fn state_for(&self, host: &str) -> Arc<Mutex<HostBreaker>> {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(breaker) = hosts.get(host) {
+            return breaker.clone();
+        }
+        let breaker = Arc::new(Mutex::new(HostBreaker::new()));
+        hosts.put(host.to_string(), breaker.clone());
+        breaker
+    }
+}
+
+impl Default for CircuitBreakers {
+    fn default() -> Self {
+        Self::new(BreakerConfig::default())
+    }
+}
+
+fn open_error(host: &str, breaker: &HostBreaker) -> FetchError {
+    let retry_in = breaker
+        .open_until
+        .map(|until| until.saturating_duration_since(Instant::now()))
+        .unwrap_or_default();
+    FetchError::CircuitOpen(format!(
+        "{} is failing, retrying in {}ms",
+        host,
+        retry_in.as_millis()
+    ))
+}
+
+/// Permission to send one request through a host's breaker.
+///
+/// Report the outcome with [`BreakerPermit::record`]; a permit dropped
+/// without an outcome frees its half-open probe slot.
+#[derive(Debug)]
+pub struct BreakerPermit {
+    host: String,
+    breaker: Arc<Mutex<HostBreaker>>,
+    config: Arc<BreakerConfig>,
+    probe: bool,
+}
+
+impl BreakerPermit {
+    /// Feed the response status, or the error, back into the breaker.
+    pub fn record(mut self, outcome: Result<u16, &FetchError>) {
+        let Some(failed) = self.is_failure(outcome) else {
+            return;
+        };
+        let probe = std::mem::take(&mut self.probe);
+        let config = &self.config;
+        let mut breaker = self.breaker.lock().unwrap();
+        if probe {
+            breaker.probes_in_flight = breaker.probes_in_flight.saturating_sub(1);
+        }
+
+        if breaker.state == BreakerState::HalfOpen {
+            // Only probes decide; older requests may finish in the meantime
+            if !probe {
+                return;
+            }
+            if failed {
+                tracing::warn!("Probe to {} failed, circuit open again", self.host);
+                breaker.open(config);
+            } else {
+                tracing::info!("Probe to {} succeeded, circuit closed", self.host);
+                breaker.close();
+            }
+            return;
+        }
+        if breaker.state == BreakerState::Open {
+            // Requests admitted before the breaker opened
+            return;
+        }
+
+        breaker.recent.push_back(failed);
+        while breaker.recent.len() > config.window.max(1) {
+            breaker.recent.pop_front();
+        }
+        if !failed {
+            breaker.consecutive_failures = 0;
+            return;
+        }
+        breaker.consecutive_failures += 1;
+
+        let rate = breaker.failure_rate();
+        if breaker.consecutive_failures >= config.consecutive_failures.max(1)
+            || (breaker.recent.len() >= config.min_requests.max(1) && rate >= config.failure_rate)
+        {
+            tracing::warn!(
+                "Opening circuit for {} for {:?} ({} consecutive failures, failure rate {:.2})",
+                self.host,
+                config.cooldown,
+                breaker.consecutive_failures,
+                rate
+            );
+            breaker.open(config);
+        }
+    }
+
+    /// Whether `outcome` is a failure, or `None` when the host was never reached.
+    fn is_failure(&self, outcome: Result<u16, &FetchError>) -> Option<bool> {
+        let status = match outcome {
+            Ok(status) => status,
+            Err(e) => match e.status_code() {
+                Some(status) => status,
+                None => {
+                    return matches!(
+                        e.kind(),
+                        FetchErrorKind::NetworkError | FetchErrorKind::Timeout
+                    )
+                    .then_some(true);
+                }
+            },
+        };
+        Some(self.config.failure_statuses.contains(&status))
+    }
+}
+
+impl Drop for BreakerPermit {
+    fn drop(&mut self) {
+        if self.probe {
+            let mut breaker = self.breaker.lock().unwrap();
+            breaker.probes_in_flight = breaker.probes_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network_error() -> FetchError {
+        FetchError::NetworkError("connection refused".to_string())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_opens_after_consecutive_failures_and_probes_after_cooldown() {
+        let breakers = CircuitBreakers::new(BreakerConfig {
+            consecutive_failures: 3,
+            cooldown: Duration::from_secs(10),
+            ..BreakerConfig::default()
+        });
+        let url = "https://down.example/page";
+
+        for _ in 0..3 {
+            breakers
+                .allow(url)
+                .unwrap()
+                .unwrap()
+                .record(Err(&network_error()));
+        }
+        assert!(matches!(
+            breakers.allow(url),
+            Err(FetchError::CircuitOpen(_))
+        ));
+        assert!(matches!(
+            breakers.check(url),
+            Err(FetchError::CircuitOpen(_))
+        ));
+        // Other hosts are unaffected
+        assert!(breakers.allow("https://up.example/").unwrap().is_some());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let probe = breakers.allow(url).unwrap().unwrap();
+        // Only one probe at a time
+        assert!(breakers.allow(url).is_err());
+        assert_eq!(breakers.status()[0].state, BreakerState::HalfOpen);
+        probe.record(Err(&network_error()));
+        assert!(breakers.allow(url).is_err());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        breakers.allow(url).unwrap().unwrap().record(Ok(200));
+        assert_eq!(breakers.status()[0].state, BreakerState::Closed);
+        assert_eq!(breakers.status()[0].trips, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_opens_on_failure_rate() {
+        let breakers = CircuitBreakers::new(BreakerConfig {
+            failure_rate: 0.5,
+            min_requests: 4,
+            ..BreakerConfig::default()
+        });
+        let url = "https://flaky.example/";
+
+        for status in [200, 503, 200] {
+            breakers.allow(url).unwrap().unwrap().record(Ok(status));
+        }
+        // Errors raised before reaching the host do not count
+        breakers
+            .allow(url)
+            .unwrap()
+            .unwrap()
+            .record(Err(&FetchError::InvalidRequest("bad header".to_string())));
+        assert_eq!(breakers.status()[0].state, BreakerState::Closed);
+
+        breakers.allow(url).unwrap().unwrap().record(Ok(502));
+        assert_eq!(breakers.status()[0].state, BreakerState::Open);
+        assert!(breakers.reset("flaky.example"));
+        assert!(breakers.allow(url).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_service_fails_fast_once_open() {
+        use crate::domain::fetch::config::FetchConfig;
+        use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+        use crate::infra::http::HttpClient;
+        use axum::http::StatusCode;
+
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::get(|| async { StatusCode::SERVICE_UNAVAILABLE }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let service = DefaultFetchService::new(HttpClient::new().unwrap()).with_circuit_breaker(
+            BreakerConfig {
+                consecutive_failures: 2,
+                ..BreakerConfig::default()
+            },
+        );
+        let config = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+
+        for _ in 0..2 {
+            assert!(matches!(
+                service.fetch(&url, &config).await,
+                Err(FetchError::ServerError { status: 503, .. })
+            ));
+        }
+        assert!(matches!(
+            service.fetch(&url, &config).await,
+            Err(FetchError::CircuitOpen(_))
+        ));
+        assert_eq!(service.breakers.status()[0].state, BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_targets_trip_their_own_breaker() {
+        use crate::domain::fetch::config::FetchConfig;
+        use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+        use crate::infra::http::HttpClient;
+        use axum::http::StatusCode;
+        use axum::response::Redirect;
+
+        let cdn = axum::Router::new().route(
+            "/",
+            axum::routing::get(|| async { StatusCode::SERVICE_UNAVAILABLE }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = format!(
+            "http://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        tokio::spawn(async move { axum::serve(listener, cdn).await });
+
+        let origin = axum::Router::new().route(
+            "/",
+            axum::routing::get(move || async move { Redirect::temporary(&target) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, origin).await });
+
+        let service = DefaultFetchService::new(HttpClient::new().unwrap()).with_circuit_breaker(
+            BreakerConfig {
+                consecutive_failures: 2,
+                ..BreakerConfig::default()
+            },
+        );
+        let config = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+
+        for _ in 0..2 {
+            assert!(matches!(
+                service.fetch(&url, &config).await,
+                Err(FetchError::ServerError { status: 503, .. })
+            ));
+        }
+        let status = service.breakers.status();
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].host, "127.0.0.1");
+        assert_eq!(status[0].state, BreakerState::Closed);
+        assert_eq!(status[1].host, "localhost");
+        assert_eq!(status[1].state, BreakerState::Open);
+
+        // The origin is still reached; the open target fails the hop
+        let err = service.fetch(&url, &config).await.unwrap_err();
+        assert!(matches!(&err, FetchError::CircuitOpen(message) if message.contains("localhost")));
+    }
+}
@@ -0,0 +1,576 @@
+//! Single-flight coalescing of identical concurrent fetches.
+//!
+//! When many jobs ask for the same page at once, only the first request goes
+//! upstream; identical calls arriving while it is in flight wait for it and
+//! share its response. Buffered fetches share the result. Streamed bodies
+//! are read once and fanned out to every caller, keeping each chunk only
+//! until all callers have read it (at most the size limit).
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::config::{FetchConfig, HttpMethod};
+use super::error::FetchError;
+use super::redirect::RedirectHop;
+use super::service::{FetchResult, StreamingFetchResult};
+use crate::infra::http::cache::CacheStatus;
+use crate::infra::http::resolver::Resolution;
+use crate::infra::http::streaming::ResponseStream;
+
+/// Request coalescing configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoalesceConfig {
+    /// Whether identical concurrent fetches share one upstream request
+    pub enabled: bool,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Identity of a fetch: requests with equal keys get the same response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CoalesceKey {
+    method: HttpMethod,
+    url: String,
+    /// Request headers with lowercase names, user agent and cookies included
+    headers: Vec<(String, String)>,
+    /// Session cookie jar, by identity
+    session: Option<usize>,
+    /// Options changing what callers get back or how long they wait (size
+    /// limit, statuses, proxy, timeouts, retries, ...)
+    options: String,
+}
+
+impl CoalesceKey {
+    /// Key for fetching `url` with `config`, or `None` when the request must
+    /// not be shared.
+    ///
+    /// Only `GET` and `HEAD` without a body are shared. Requests carrying
+    /// their own credentials are not: stored credentials are attached per
+    /// host later, so they are the same for every caller.
+    pub fn new(url: &str, config: &FetchConfig) -> Option<Self> {
+        if !matches!(config.method, HttpMethod::Get | HttpMethod::Head)
+            || config.body.is_some()
+            || config.auth.is_some()
+        {
+            return None;
+        }
+
+        let mut headers: Vec<(String, String)> = config
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+            .chain(
+                config
+                    .cookies
+                    .iter()
+                    .map(|(name, value)| (format!("cookie:{}", name), value.clone())),
+            )
+            .collect();
+        headers.push(("user-agent".to_string(), config.user_agent.clone()));
+        headers.sort();
+
+        let options = serde_json::to_string(&(
+            config.max_content_size,
+            config.transcode_to_utf8,
+            &config.accept_statuses,
+            config.follow_redirects,
+            config.max_redirects,
+            &config.html_redirects,
+            &config.proxy,
+            config.respect_robots,
+            config.verify_tls,
+            config.timeout,
+            config.read_timeout,
+            &config.retry,
+        ))
+        .ok()?;
+
+        Some(Self {
+            method: config.method,
+            url: url.to_string(),
+            headers,
+            session: config
+                .cookie_jar
+                .as_ref()
+                .map(|jar| Arc::as_ptr(jar) as usize),
+            options,
+        })
+    }
+}
+
+/// Coalescing counters.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CoalesceStats {
+    /// Fetches that went upstream
+    pub upstream: u64,
+    /// Fetches that shared the response of an identical fetch in flight
+    pub coalesced: u64,
+    /// Fetches currently in flight and open to joining
+    pub in_flight: usize,
+}
+
+type FetchFlight = BoxFuture<'static, Result<FetchResult, FetchError>>;
+type StreamFlight = BoxFuture<'static, Result<StreamHead, FetchError>>;
+/// A stream in flight and the body its callers subscribe to.
+type StreamEntry = (WeakShared<StreamFlight>, Arc<FanOut>);
+
+/// In-flight fetches, joined by identical fetches until their response arrives.
+#[derive(Default)]
+pub struct Coalescer {
+    fetches: Mutex<HashMap<CoalesceKey, WeakShared<FetchFlight>>>,
+    streams: Mutex<HashMap<CoalesceKey, StreamEntry>>,
+    upstream: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl Coalescer {
+    /// Current counters.
+    pub fn stats(&self) -> CoalesceStats {
+        CoalesceStats {
+            upstream: self.upstream.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            in_flight: self
+                .fetches
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|flight| flight.upgrade().is_some())
+                .count()
+                + self
+                    .streams
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .filter(|(flight, _)| flight.upgrade().is_some())
+                    .count(),
+        }
+    }
+
+    /// Run `upstream`, or wait for the identical fetch already in flight.
+    ///
+    /// `upstream` is dropped without being polled when a flight is joined.
+    pub async fn fetch(
+        self: &Arc<Self>,
+        key: CoalesceKey,
+        upstream: impl Future<Output = Result<FetchResult, FetchError>> + Send + 'static,
+    ) -> Result<FetchResult, FetchError> {
+        let flight: Shared<FetchFlight> = {
+            let mut fetches = self.fetches.lock().unwrap();
+            match fetches.get(&key).and_then(WeakShared::upgrade) {
+                Some(flight) => {
+                    self.joined(&key);
+                    flight
+                }
+                None => {
+                    self.upstream.fetch_add(1, Ordering::Relaxed);
+                    let coalescer = self.clone();
+                    let id = key.clone();
+                    let flight = async move {
+                        let result = upstream.await;
+                        coalescer.fetches.lock().unwrap().remove(&id);
+                        result
+                    }
+                    .boxed()
+                    .shared();
+                    // Flights whose callers all went away are never polled again
+                    fetches.retain(|_, flight| flight.upgrade().is_some());
+                    if let Some(weak) = flight.downgrade() {
+                        fetches.insert(key, weak);
+                    }
+                    flight
+                }
+            }
+        };
+        flight.await
+    }
+
+    /// Stream `upstream`, or share the body of the identical stream in flight.
+    ///
+    /// Every caller gets its own stream of the complete body.
+    pub async fn stream(
+        self: &Arc<Self>,
+        key: CoalesceKey,
+        upstream: impl Future<Output = Result<StreamingFetchResult, FetchError>> + Send + 'static,
+        max_size: usize,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        let (flight, subscriber): (Shared<StreamFlight>, _) = {
+            let mut streams = self.streams.lock().unwrap();
+            let joined = streams.get(&key).and_then(|(flight, fanout)| {
+                flight.upgrade().map(|flight| (flight, fanout.clone()))
+            });
+            match joined {
+                Some((flight, fanout)) => {
+                    self.joined(&key);
+                    (flight, FanOut::subscribe(&fanout))
+                }
+                None => {
+                    self.upstream.fetch_add(1, Ordering::Relaxed);
+                    let fanout = Arc::new(FanOut::default());
+                    let coalescer = self.clone();
+                    let id = key.clone();
+                    let body = fanout.clone();
+                    let flight = async move {
+                        let result = upstream.await;
+                        // Closed to joining before any caller reads the body
+                        coalescer.streams.lock().unwrap().remove(&id);
+                        let (head, stream) = StreamHead::split(result?);
+                        body.start(stream).await;
+                        Ok(head)
+                    }
+                    .boxed()
+                    .shared();
+                    streams.retain(|_, (flight, _)| flight.upgrade().is_some());
+                    let subscriber = FanOut::subscribe(&fanout);
+                    if let Some(weak) = flight.downgrade() {
+                        streams.insert(key, (weak, fanout));
+                    }
+                    (flight, subscriber)
+                }
+            }
+        };
+        let head = flight.await?;
+        Ok(head.with_stream(ResponseStream::from_results(
+            subscriber.into_stream(),
+            max_size,
+        )))
+    }
+
+    fn joined(&self, key: &CoalesceKey) {
+        self.coalesced.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            "Coalescing {} {} with the fetch in flight",
+            key.method,
+            key.url
+        );
+    }
+}
+
+/// Response metadata of a coalesced stream, copied to every caller.
+#[derive(Clone)]
+struct StreamHead {
+    status_code: u16,
+    final_url: String,
+    timestamp: DateTime<Utc>,
+    content_length: Option<u64>,
+    content_type: Option<String>,
+    encoding: Option<String>,
+    attempts: u32,
+    cache_status: Option<CacheStatus>,
+    proxy: Option<String>,
+    redirects: Vec<RedirectHop>,
+    headers: Vec<(String, String)>,
+    dns: Option<Resolution>,
+}
+
+impl StreamHead {
+    fn split(result: StreamingFetchResult) -> (Self, ResponseStream) {
+        let StreamingFetchResult {
+            stream,
+            status_code,
+            final_url,
+            timestamp,
+            content_length,
+            content_type,
+            encoding,
+            attempts,
+            cache_status,
+            proxy,
+            redirects,
+            headers,
+            dns,
+        } = result;
+        let head = Self {
+            status_code,
+            final_url,
+            timestamp,
+            content_length,
+            content_type,
+            encoding,
+            attempts,
+            cache_status,
+            proxy,
+            redirects,
+            headers,
+            dns,
+        };
+        (head, stream)
+    }
+
+    fn with_stream(self, stream: ResponseStream) -> StreamingFetchResult {
+        StreamingFetchResult {
+            stream,
+            status_code: self.status_code,
+            final_url: self.final_url,
+            timestamp: self.timestamp,
+            content_length: self.content_length,
+            content_type: self.content_type,
+            encoding: self.encoding,
+            attempts: self.attempts,
+            cache_status: self.cache_status,
+            proxy: self.proxy,
+            redirects: self.redirects,
+            headers: self.headers,
+            dns: self.dns,
+        }
+    }
+}
+
+/// A body read once from upstream and replayed to each subscriber.
+///
+/// Whichever subscriber is ahead pulls the next chunk; a chunk is dropped
+/// once every live subscriber has read it.
+#[derive(Default)]
+struct FanOut {
+    state: Mutex<FanOutState>,
+    upstream: tokio::sync::Mutex<Option<ResponseStream>>,
+}
+
+#[derive(Default)]
+struct FanOutState {
+    /// Chunks not yet read by every subscriber, starting with chunk `base`
+    chunks: VecDeque<Bytes>,
+    base: usize,
+    /// Index of the next chunk for each subscriber
+    cursors: HashMap<usize, usize>,
+    next_id: usize,
+    /// How the upstream body ended, once it did
+    end: Option<Result<(), FetchError>>,
+}
+
+enum Step {
+    Chunk(Bytes),
+    End(Option<FetchError>),
+    Pull,
+}
+
+impl FanOut {
+    /// Register a subscriber; only valid before the body starts.
+    fn subscribe(fanout: &Arc<Self>) -> Subscriber {
+        let mut state = fanout.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        let base = state.base;
+        state.cursors.insert(id, base);
+        Subscriber {
+            fanout: fanout.clone(),
+            id,
+        }
+    }
+
+    async fn start(&self, stream: ResponseStream) {
+        *self.upstream.lock().await = Some(stream);
+    }
+}
+
+impl FanOutState {
+    fn step(&mut self, id: usize) -> Step {
+        let cursor = self.cursors[&id];
+        if let Some(chunk) = self.chunks.get(cursor - self.base).cloned() {
+            self.cursors.insert(id, cursor + 1);
+            self.trim();
+            return Step::Chunk(chunk);
+        }
+        match &self.end {
+            Some(end) => Step::End(end.clone().err()),
+            None => Step::Pull,
+        }
+    }
+
+    fn has_next(&self, id: usize) -> bool {
+        self.cursors[&id] < self.base + self.chunks.len() || self.end.is_some()
+    }
+
+    fn trim(&mut self) {
+        let read = self
+            .cursors
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(self.base + self.chunks.len());
+        while self.base < read && self.chunks.pop_front().is_some() {
+            self.base += 1;
+        }
+    }
+}
+
+/// One caller's view of a `FanOut`.
+struct Subscriber {
+    fanout: Arc<FanOut>,
+    id: usize,
+}
+
+impl Subscriber {
+    fn into_stream(self) -> impl Stream<Item = Result<Bytes, FetchError>> + Send + 'static {
+        let subscriber = self;
+        async_stream::stream! {
+            loop {
+                let step = subscriber.fanout.state.lock().unwrap().step(subscriber.id);
+                match step {
+                    Step::Chunk(chunk) => yield Ok(chunk),
+                    Step::End(None) => break,
+                    Step::End(Some(e)) => {
+                        yield Err(e);
+                        break;
+                    }
+                    Step::Pull => subscriber.pull().await,
+                }
+            }
+        }
+    }
+
+    /// Read the next chunk from upstream into the shared buffer.
+    async fn pull(&self) {
+        let mut upstream = self.fanout.upstream.lock().await;
+        // Another subscriber may have pulled while we waited
+        if self.fanout.state.lock().unwrap().has_next(self.id) {
+            return;
+        }
+        let item = match upstream.as_mut() {
+            Some(stream) => stream.next().await,
+            None => Some(Err(FetchError::Other(
+                "coalesced body was never started".to_string(),
+            ))),
+        };
+        let mut state = self.fanout.state.lock().unwrap();
+        match item {
+            Some(Ok(chunk)) => state.chunks.push_back(chunk),
+            Some(Err(e)) => state.end = Some(Err(e)),
+            None => state.end = Some(Ok(())),
+        }
+        if state.end.is_some() {
+            // Releases the connection and host slot
+            *upstream = None;
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let mut state = self.fanout.state.lock().unwrap();
+        state.cursors.remove(&self.id);
+        state.trim();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+    use crate::infra::http::HttpClient;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn test_key_covers_method_headers_and_body() {
+        let config = FetchConfig::default();
+        let key = CoalesceKey::new("https://example.com/c", &config).unwrap();
+        assert_eq!(
+            CoalesceKey::new("https://example.com/c", &config.clone()),
+            Some(key.clone())
+        );
+
+        let mut other = config.clone();
+        other
+            .headers
+            .insert("Accept-Language".to_string(), "de".to_string());
+        assert_ne!(
+            CoalesceKey::new("https://example.com/c", &other),
+            Some(key.clone())
+        );
+
+        // A caller must not wait on another caller's timeout or retries
+        let impatient = FetchConfig {
+            timeout: Duration::from_secs(2),
+            ..config.clone()
+        };
+        assert_ne!(
+            CoalesceKey::new("https://example.com/c", &impatient),
+            Some(key.clone())
+        );
+        let mut retrying = config.clone();
+        retrying.retry.max_attempts += 1;
+        assert_ne!(
+            CoalesceKey::new("https://example.com/c", &retrying),
+            Some(key)
+        );
+
+        let post = FetchConfig {
+            method: HttpMethod::Post,
+            ..config
+        };
+        assert_eq!(CoalesceKey::new("https://example.com/c", &post), None);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_share_one_request() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = axum::Router::new().route(
+            "/category",
+            axum::routing::get(move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    "x".repeat(100_000)
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/category", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let service = Arc::new(DefaultFetchService::new(HttpClient::new().unwrap()));
+        let config = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+
+        let fetches = (0..5).map(|_| {
+            let (service, url, config) = (service.clone(), url.clone(), config.clone());
+            tokio::spawn(async move { service.fetch(&url, &config).await })
+        });
+        let streams = (0..5).map(|_| {
+            let (service, url, config) = (service.clone(), url.clone(), config.clone());
+            tokio::spawn(async move {
+                let mut result = service.fetch_stream(&url, &config).await?;
+                let mut body = Vec::new();
+                while let Some(chunk) = result.stream.next().await {
+                    body.extend_from_slice(&chunk?);
+                }
+                Ok::<_, FetchError>(body)
+            })
+        });
+        let (fetches, streams) = futures::future::join(
+            futures::future::join_all(fetches),
+            futures::future::join_all(streams),
+        )
+        .await;
+
+        for result in fetches {
+            assert_eq!(result.unwrap().unwrap().length, 100_000);
+        }
+        for body in streams {
+            assert_eq!(body.unwrap().unwrap().len(), 100_000);
+        }
+        // One request for the buffered fetches, one for the streams
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        let stats = service.coalesce_stats();
+        assert_eq!(
+            (stats.upstream, stats.coalesced, stats.in_flight),
+            (2, 8, 0)
+        );
+    }
+}
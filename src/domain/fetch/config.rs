@@ -1,8 +1,163 @@
 //! Configuration for fetch operations.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
+use super::auth::Credentials;
+use super::cookies::CookieJar;
+use super::proxy::ProxyConfig;
+use super::redirect::HtmlRedirectConfig;
+use super::retry::RetryPolicy;
+
+/// HTTP method used for a fetch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    /// GET request
+    #[default]
+    #[serde(alias = "get")]
+    Get,
+    /// POST request
+    #[serde(alias = "post")]
+    Post,
+    /// PUT request
+    #[serde(alias = "put")]
+    Put,
+    /// HEAD request
+    #[serde(alias = "head")]
+    Head,
+}
+
+impl HttpMethod {
+    /// Method name as sent on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Head => "HEAD",
+        }
+    }
+}
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Request body sent with a fetch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestBody {
+    /// Raw body with an optional content type
+    Raw {
+        /// Body content
+        content: String,
+        /// Content-Type header (defaults to none)
+        #[serde(default)]
+        content_type: Option<String>,
+    },
+    /// `application/x-www-form-urlencoded` fields
+    Form(HashMap<String, String>),
+    /// `application/json` document
+    Json(serde_json::Value),
+}
+
+/// Inclusive range of HTTP status codes.
+///
+/// Deserializes from a single code (`404`, `"404"`), a range (`"400-499"`) or
+/// a class (`"4xx"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "StatusSpec", into = "StatusSpec")]
+pub struct StatusRange {
+    /// First status in the range
+    pub start: u16,
+    /// Last status in the range
+    pub end: u16,
+}
+
+impl StatusRange {
+    /// Range holding only `status`.
+    pub fn single(status: u16) -> Self {
+        Self {
+            start: status,
+            end: status,
+        }
+    }
+
+    /// Whether `status` falls in the range.
+    pub fn contains(&self, status: u16) -> bool {
+        (self.start..=self.end).contains(&status)
+    }
+}
+
+impl std::str::FromStr for StatusRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parse = |code: &str| {
+            code.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|code| (100..=599).contains(code))
+                .ok_or_else(|| format!("invalid status {:?}", s))
+        };
+
+        let range = if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+            let class = parse(&format!("{}00", class))?;
+            Self {
+                start: class,
+                end: class + 99,
+            }
+        } else if let Some((start, end)) = s.split_once('-') {
+            Self {
+                start: parse(start)?,
+                end: parse(end)?,
+            }
+        } else {
+            Self::single(parse(s)?)
+        };
+
+        if range.start > range.end {
+            return Err(format!("empty status range {:?}", s));
+        }
+        Ok(range)
+    }
+}
+
+/// Wire form of a `StatusRange`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StatusSpec {
+    Code(u16),
+    Text(String),
+}
+
+impl TryFrom<StatusSpec> for StatusRange {
+    type Error = String;
+
+    fn try_from(spec: StatusSpec) -> Result<Self, Self::Error> {
+        match spec {
+            StatusSpec::Code(code) => code.to_string().parse(),
+            StatusSpec::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<StatusRange> for StatusSpec {
+    fn from(range: StatusRange) -> Self {
+        if range.start == range.end {
+            StatusSpec::Code(range.start)
+        } else {
+            StatusSpec::Text(format!("{}-{}", range.start, range.end))
+        }
+    }
+}
+
 /// Configuration for fetch operations.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FetchConfig {
@@ -29,6 +184,49 @@ pub struct FetchConfig {
     /// Buffer size for streaming operations
     #[serde(default = "default_stream_buffer_size")]
     pub stream_buffer_size: usize,
+    /// HTTP method
+    #[serde(default)]
+    pub method: HttpMethod,
+    /// Extra request headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookies sent in the `Cookie` header
+    #[serde(default)]
+    pub cookies: HashMap<String, String>,
+    /// Optional request body
+    #[serde(default)]
+    pub body: Option<RequestBody>,
+    /// Transcode streamed bodies to UTF-8 using the detected charset
+    #[serde(default)]
+    pub transcode_to_utf8: bool,
+    /// Retry policy for failed fetches
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Check robots.txt before fetching (disable only with the site's permission)
+    #[serde(default = "default_respect_robots")]
+    pub respect_robots: bool,
+    /// Proxy for this request (overrides per-host and pool proxies)
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Non-2xx statuses whose responses are returned instead of failing the fetch
+    #[serde(default)]
+    pub accept_statuses: Vec<StatusRange>,
+    /// Cookie jar read before and updated after every request (set by sessions)
+    #[serde(skip)]
+    pub cookie_jar: Option<Arc<CookieJar>>,
+    /// Credentials for the target host (resolved from the credential store)
+    #[serde(skip)]
+    pub auth: Option<Credentials>,
+    /// Follow meta refresh and canonical redirects in HTML pages (off when `None`)
+    #[serde(default)]
+    pub html_redirects: Option<HtmlRedirectConfig>,
+}
+
+impl FetchConfig {
+    /// Whether a response with `status` is returned rather than treated as an error.
+    pub fn accepts_status(&self, status: u16) -> bool {
+        (200..300).contains(&status) || self.accept_statuses.iter().any(|r| r.contains(status))
+    }
 }
 
 fn default_max_content_size() -> usize {
@@ -43,6 +241,10 @@ fn default_stream_buffer_size() -> usize {
     64 * 1024 // 64KB
 }
 
+fn default_respect_robots() -> bool {
+    true
+}
+
 impl Default for FetchConfig {
     fn default() -> Self {
         Self {
@@ -56,6 +258,47 @@ impl Default for FetchConfig {
             max_content_size: default_max_content_size(),
             streaming_threshold: default_streaming_threshold(),
             stream_buffer_size: default_stream_buffer_size(),
+            method: HttpMethod::default(),
+            headers: HashMap::new(),
+            cookies: HashMap::new(),
+            body: None,
+            transcode_to_utf8: false,
+            retry: RetryPolicy::default(),
+            respect_robots: default_respect_robots(),
+            proxy: None,
+            accept_statuses: Vec::new(),
+            cookie_jar: None,
+            auth: None,
+            html_redirects: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_statuses_parse_codes_ranges_and_classes() {
+        let statuses: Vec<StatusRange> =
+            serde_json::from_value(serde_json::json!([404, "410", "500-503", "3xx"])).unwrap();
+        let config = FetchConfig {
+            accept_statuses: statuses,
+            ..Default::default()
+        };
+
+        for status in [200, 204, 301, 399, 404, 410, 500, 503] {
+            assert!(config.accepts_status(status), "{}", status);
+        }
+        for status in [403, 429, 504] {
+            assert!(!config.accepts_status(status), "{}", status);
+        }
+
+        assert!("503-500".parse::<StatusRange>().is_err());
+        assert!("7xx".parse::<StatusRange>().is_err());
+        assert_eq!(
+            serde_json::to_value(&config.accept_statuses).unwrap(),
+            serde_json::json!([404, 410, "500-503", "300-399"])
+        );
+    }
+}
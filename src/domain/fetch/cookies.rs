@@ -0,0 +1,301 @@
+//! Cookie jar shared across fetches (RFC 6265 subset).
+//!
+//! A jar attached to `FetchConfig::cookie_jar` is consulted when building
+//! every request and updated from `Set-Cookie` on every response. Domain,
+//! path, `Secure`, `Expires` and `Max-Age` are honored; there is no public
+//! suffix list, so single-label `Domain` attributes are rejected instead.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Maximum number of cookies kept per jar; the oldest are evicted first.
+const MAX_COOKIES: usize = 1000;
+
+/// A stored cookie.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    /// Cookie name
+    pub name: String,
+    /// Cookie value
+    pub value: String,
+    /// Domain the cookie belongs to (lowercase, no leading dot)
+    pub domain: String,
+    /// Only sent to `domain` itself, not its subdomains
+    #[serde(default)]
+    pub host_only: bool,
+    /// Path prefix the cookie applies to
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Only sent over HTTPS
+    #[serde(default)]
+    pub secure: bool,
+    /// Expiry time (`None` for session cookies)
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header received from `url`.
+    ///
+    /// Returns `None` for malformed cookies and cookies the origin may not set.
+    pub fn parse(set_cookie: &str, url: &url::Url) -> Option<Self> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_cookie_path(url.path()),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_matches(&host, &domain) || !domain.contains('.') {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "expires" if cookie.expires.is_none() => {
+                    cookie.expires = parse_cookie_date(value);
+                }
+                _ => {}
+            }
+        }
+
+        // Max-Age wins over Expires; lifetimes past the latest representable
+        // date end there
+        if let Some(seconds) = max_age {
+            cookie.expires = Some(
+                chrono::TimeDelta::try_seconds(seconds.max(0))
+                    .and_then(|lifetime| Utc::now().checked_add_signed(lifetime))
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            );
+        }
+        Some(cookie)
+    }
+
+    /// Whether the cookie has expired at `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether the cookie should be sent to `url`.
+    fn matches(&self, url: &url::Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        domain_ok
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+    }
+
+    fn same_identity(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+}
+
+/// Thread-safe cookie jar.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Create an empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a jar holding `cookies` (expired ones are dropped).
+    pub fn from_cookies(cookies: Vec<Cookie>) -> Self {
+        let jar = Self::new();
+        for cookie in cookies {
+            jar.insert(cookie);
+        }
+        jar
+    }
+
+    /// Add or replace a cookie; an expired cookie deletes its counterpart.
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|existing| !existing.same_identity(&cookie));
+        if cookie.is_expired(Utc::now()) {
+            return;
+        }
+        if cookies.len() >= MAX_COOKIES {
+            cookies.remove(0);
+        }
+        cookies.push(cookie);
+    }
+
+    /// Store the `Set-Cookie` headers of a response from `url`.
+    pub fn store_response<'a>(
+        &self,
+        url: &url::Url,
+        set_cookies: impl IntoIterator<Item = &'a str>,
+    ) {
+        for set_cookie in set_cookies {
+            match Cookie::parse(set_cookie, url) {
+                Some(cookie) => self.insert(cookie),
+                None => tracing::debug!("Ignoring cookie from {}: {}", url, set_cookie),
+            }
+        }
+    }
+
+    /// Name/value pairs to send to `url`, most specific path first.
+    pub fn cookies_for(&self, url: &url::Url) -> Vec<(String, String)> {
+        let now = Utc::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<&Cookie> = cookies.iter().filter(|c| c.matches(url)).collect();
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        matching
+            .into_iter()
+            .map(|c| (c.name.clone(), c.value.clone()))
+            .collect()
+    }
+
+    /// Copy of all unexpired cookies (for persistence).
+    pub fn snapshot(&self) -> Vec<Cookie> {
+        let now = Utc::now();
+        self.cookies
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of stored cookies.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    /// Whether the jar is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Default cookie path: the request path up to its last `/` (RFC 6265 5.1.4).
+fn default_cookie_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || request_path.as_bytes().get(cookie_path.len()) == Some(&b'/')))
+}
+
+/// Parse the date formats seen in `Expires` attributes.
+fn parse_cookie_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    // Netscape style: "Wed, 21-Oct-2015 07:28:00 GMT"
+    chrono::NaiveDateTime::parse_from_str(value, "%a, %d-%b-%Y %H:%M:%S GMT")
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> url::Url {
+        url::Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_domain_path_and_secure_matching() {
+        let jar = CookieJar::new();
+        jar.store_response(
+            &url("https://www.example.com/account/login"),
+            [
+                "sid=abc; Path=/; Secure; HttpOnly",
+                "pref=dark; Domain=.example.com; Path=/",
+                "step=2",
+                "evil=1; Domain=other.com",
+                "tld=1; Domain=com",
+            ],
+        );
+        assert_eq!(jar.len(), 3);
+
+        let sent = jar.cookies_for(&url("https://www.example.com/account/settings"));
+        assert!(sent.contains(&("sid".into(), "abc".into())));
+        assert!(sent.contains(&("pref".into(), "dark".into())));
+        // Default path is /account, so `step` is sent first
+        assert_eq!(sent[0], ("step".into(), "2".into()));
+
+        let sent = jar.cookies_for(&url("http://api.example.com/other"));
+        assert_eq!(sent, vec![("pref".to_string(), "dark".to_string())]);
+    }
+
+    #[test]
+    fn test_expiry_and_replacement() {
+        let jar = CookieJar::new();
+        let origin = url("https://example.com/");
+        jar.store_response(&origin, ["a=1", "b=1; Max-Age=3600"]);
+        jar.store_response(&origin, ["a=2"]);
+        jar.store_response(&origin, ["b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT"]);
+
+        assert_eq!(
+            jar.cookies_for(&origin),
+            vec![("a".to_string(), "2".to_string())]
+        );
+
+        let restored = CookieJar::from_cookies(jar.snapshot());
+        assert_eq!(restored.cookies_for(&origin), jar.cookies_for(&origin));
+
+        // Huge lifetimes neither overflow nor expire
+        for max_age in ["99999999999999", &i64::MAX.to_string()] {
+            let cookie = Cookie::parse(&format!("c=1; Max-Age={}", max_age), &origin).unwrap();
+            assert_eq!(cookie.expires, Some(DateTime::<Utc>::MAX_UTC));
+        }
+    }
+}
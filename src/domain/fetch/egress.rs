@@ -0,0 +1,371 @@
+//! Egress policy for server-side fetches (SSRF protection).
+//!
+//! Every URL a fetch touches (including each redirect target) is checked
+//! against domain and port allow/deny lists, and every address a host
+//! resolves to must be public unless `allow_private` is set. The resolved
+//! addresses are checked again at connect time by the HTTP client's resolver,
+//! so a host cannot pass the check and then rebind to an internal address.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+
+use super::error::FetchError;
+use super::proxy::ProxyConfig;
+
+/// Configuration for the egress policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EgressConfig {
+    /// Enforce the policy
+    pub enabled: bool,
+    /// Allow loopback, private, link-local and other non-public addresses
+    pub allow_private: bool,
+    /// Only these domains (and their subdomains) may be fetched; empty allows all
+    pub allowed_domains: Vec<String>,
+    /// Domains (and their subdomains) that may never be fetched
+    pub denied_domains: Vec<String>,
+    /// Only these ports may be fetched; empty allows all
+    pub allowed_ports: Vec<u16>,
+    /// Ports that may never be fetched
+    pub denied_ports: Vec<u16>,
+}
+
+impl Default for EgressConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allow_private: false,
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            allowed_ports: Vec::new(),
+            denied_ports: Vec::new(),
+        }
+    }
+}
+
+/// Egress policy applied to every outgoing connection.
+#[derive(Debug, Default)]
+pub struct EgressPolicy {
+    config: EgressConfig,
+    /// Hosts exempt from address checks (operator-configured proxies)
+    trusted_hosts: HashSet<String>,
+}
+
+impl EgressPolicy {
+    /// Create a policy from its configuration.
+    pub fn new(config: EgressConfig) -> Self {
+        Self {
+            config,
+            trusted_hosts: HashSet::new(),
+        }
+    }
+
+    /// Exempt the hosts of `proxies` from address checks.
+    ///
+    /// Operators commonly run proxies on private addresses; proxies supplied
+    /// per request are not trusted and are checked like any other host.
+    pub fn with_trusted_proxies<'a>(
+        mut self,
+        proxies: impl IntoIterator<Item = &'a ProxyConfig>,
+    ) -> Self {
+        self.trusted_hosts.extend(
+            proxies
+                .into_iter()
+                .filter_map(|proxy| url::Url::parse(&proxy.url).ok())
+                .filter_map(|url| url.host_str().map(normalize_host)),
+        );
+        self
+    }
+
+    /// Exempt `hosts` from address checks.
+    ///
+    /// Used for hosts the operator pinned to fixed addresses (DNS overrides),
+    /// which are typically staging or internal services.
+    pub fn with_trusted_hosts<'a>(mut self, hosts: impl IntoIterator<Item = &'a String>) -> Self {
+        self.trusted_hosts
+            .extend(hosts.into_iter().map(|host| normalize_host(host)));
+        self
+    }
+
+    /// Policy configuration.
+    pub fn config(&self) -> &EgressConfig {
+        &self.config
+    }
+
+    /// Whether `host` is exempt from address checks.
+    pub fn is_trusted(&self, host: &str) -> bool {
+        self.trusted_hosts.contains(&normalize_host(host))
+    }
+
+    /// Check scheme, domain lists, port lists and literal IP hosts of `url`.
+    pub fn check_url(&self, url: &url::Url) -> Result<(), FetchError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(FetchError::EgressDenied(format!(
+                "scheme {} is not allowed",
+                url.scheme()
+            )));
+        }
+
+        let host = url
+            .host_str()
+            .map(normalize_host)
+            .ok_or_else(|| FetchError::InvalidUrl(format!("{} has no host", url)))?;
+        if self
+            .config
+            .denied_domains
+            .iter()
+            .any(|domain| domain_matches(&host, domain))
+        {
+            return Err(FetchError::EgressDenied(format!(
+                "domain {} is denied",
+                host
+            )));
+        }
+        if !self.config.allowed_domains.is_empty()
+            && !self
+                .config
+                .allowed_domains
+                .iter()
+                .any(|domain| domain_matches(&host, domain))
+        {
+            return Err(FetchError::EgressDenied(format!(
+                "domain {} is not allowed",
+                host
+            )));
+        }
+
+        let port = url.port_or_known_default().unwrap_or(0);
+        if self.config.denied_ports.contains(&port)
+            || (!self.config.allowed_ports.is_empty() && !self.config.allowed_ports.contains(&port))
+        {
+            return Err(FetchError::EgressDenied(format!(
+                "port {} is not allowed",
+                port
+            )));
+        }
+
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            self.check_ip(&host, ip)?;
+        }
+        Ok(())
+    }
+
+    /// Check an address `host` resolved (or was written as) to.
+    pub fn check_ip(&self, host: &str, ip: IpAddr) -> Result<(), FetchError> {
+        if !self.config.enabled || self.config.allow_private || self.is_trusted(host) {
+            return Ok(());
+        }
+        if !is_public(ip) {
+            return Err(FetchError::EgressDenied(format!(
+                "{} resolves to non-public address {}",
+                host, ip
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a fetch target before connecting.
+    ///
+    /// Direct connections are checked again by the client's resolver. When a
+    /// proxy resolves the target instead, the target is resolved here as a
+    /// best effort, and an untrusted proxy written as an IP is checked too.
+    pub async fn check_target(
+        &self,
+        url: &str,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<(), FetchError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let parsed = url::Url::parse(url).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+        self.check_url(&parsed)?;
+
+        let Some(proxy) = proxy else {
+            return Ok(());
+        };
+        if let Some(proxy_host) = url::Url::parse(&proxy.url)
+            .ok()
+            .and_then(|proxy_url| proxy_url.host_str().map(normalize_host))
+            && let Ok(ip) = proxy_host.parse::<IpAddr>()
+        {
+            self.check_ip(&proxy_host, ip)?;
+        }
+
+        if let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default())
+            && host.parse::<IpAddr>().is_err()
+            && let Ok(addrs) = tokio::net::lookup_host((normalize_host(host), port)).await
+        {
+            for addr in addrs {
+                self.check_ip(host, addr.ip())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `ip` is a publicly routable unicast address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(v4);
+            }
+            let segments = ip.segments();
+            let octets = ip.octets();
+            let v4 = |at: usize| {
+                Ipv4Addr::new(octets[at], octets[at + 1], octets[at + 2], octets[at + 3])
+            };
+            match segments {
+                // NAT64 (64:ff9b::/96) and IPv4-compatible (::/96) addresses
+                // end in an IPv4 address
+                [0x64, 0xff9b, 0, 0, 0, 0, _, _] | [0, 0, 0, 0, 0, 0, _, _] => {
+                    return is_public_v4(v4(12));
+                }
+                // 6to4 (2002::/16) carries the IPv4 address in bits 16..48
+                [0x2002, ..] => return is_public_v4(v4(2)),
+                // Teredo (2001::/32) carries the server's IPv4 address in bits
+                // 32..64 and the client's, inverted, in the last 32 bits
+                [0x2001, 0, ..] => return is_public_v4(v4(4)) && is_public_v4(!v4(12)),
+                _ => {}
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                // Deprecated site-local fec0::/10
+                || (segments[0] & 0xffc0) == 0xfec0
+                // Documentation 2001:db8::/32
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network" 0.0.0.0/8
+        || a == 0
+        // Shared address space (carrier-grade NAT) 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments 192.0.0.0/24
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved 240.0.0.0/4
+        || a >= 240)
+}
+
+/// Lowercase host without IPv6 brackets or a trailing dot.
+pub(crate) fn normalize_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// Whether `host` is `domain` or one of its subdomains (`*.` prefixes allowed).
+fn domain_matches(host: &str, domain: &str) -> bool {
+    let domain = normalize_host(domain.trim_start_matches("*.").trim_start_matches('.'));
+    host == domain
+        || host
+            .strip_suffix(&domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &EgressPolicy, url: &str) -> Result<(), FetchError> {
+        policy.check_url(&url::Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_non_public_addresses_are_denied() {
+        let policy = EgressPolicy::default();
+        for url in [
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[64:ff9b::a9fe:a9fe]/",
+            "http://[::a9fe:a9fe]/",
+            "http://[2002:7f00:1::]/",
+            "http://[2002:a9fe:a9fe::1]/",
+            // Teredo client 10.0.0.1 behind server 65.54.227.120
+            "http://[2001:0:4136:e378:8000:63bf:f5ff:fffe]/",
+        ] {
+            assert!(
+                matches!(check(&policy, url), Err(FetchError::EgressDenied(_))),
+                "{}",
+                url
+            );
+        }
+        assert!(check(&policy, "https://93.184.216.34/").is_ok());
+        assert!(check(&policy, "https://[2606:4700::1111]/").is_ok());
+        assert!(check(&policy, "https://[2002:5db8:d822::1]/").is_ok());
+        assert!(check(&policy, "file:///etc/passwd").is_err());
+
+        let permissive = EgressPolicy::new(EgressConfig {
+            allow_private: true,
+            ..Default::default()
+        });
+        assert!(check(&permissive, "http://127.0.0.1/").is_ok());
+
+        let trusted = EgressPolicy::default()
+            .with_trusted_proxies([&ProxyConfig::new("http://10.0.0.5:3128")]);
+        assert!(
+            trusted
+                .check_ip("10.0.0.5", "10.0.0.5".parse().unwrap())
+                .is_ok()
+        );
+        assert!(
+            trusted
+                .check_ip("10.0.0.6", "10.0.0.6".parse().unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_domain_and_port_lists() {
+        let policy = EgressPolicy::new(EgressConfig {
+            allowed_domains: vec!["example.com".to_string(), "*.example.org".to_string()],
+            denied_domains: vec!["admin.example.com".to_string()],
+            denied_ports: vec![22],
+            ..Default::default()
+        });
+
+        assert!(check(&policy, "https://example.com/").is_ok());
+        assert!(check(&policy, "https://www.example.com/").is_ok());
+        assert!(check(&policy, "https://api.example.org/").is_ok());
+        assert!(check(&policy, "https://notexample.com/").is_err());
+        assert!(check(&policy, "https://x.admin.example.com/").is_err());
+        assert!(check(&policy, "https://example.com:22/").is_err());
+
+        let ports = EgressPolicy::new(EgressConfig {
+            allowed_ports: vec![443],
+            ..Default::default()
+        });
+        assert!(check(&ports, "https://example.com/").is_ok());
+        assert!(check(&ports, "http://example.com/").is_err());
+    }
+}
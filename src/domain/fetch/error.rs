@@ -1,14 +1,20 @@
 //! Error types for fetch operations.
 
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
 /// Errors that can occur during fetch operations.
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum FetchError {
     /// Invalid URL
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
 
+    /// Invalid request (bad header, body, etc.)
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
     /// Network error (connection failed, DNS resolution, etc.)
     #[error("Network error: {0}")]
     NetworkError(String),
@@ -22,8 +28,15 @@ pub enum FetchError {
     TooManyRedirects(String),
 
     /// Server error (4xx or 5xx response)
-    #[error("Server error: {0}")]
-    ServerError(String),
+    #[error("Server error: {message}")]
+    ServerError {
+        /// HTTP status code
+        status: u16,
+        /// Status line or description
+        message: String,
+        /// Delay requested by a `Retry-After` header
+        retry_after: Option<Duration>,
+    },
 
     /// Content too large
     #[error("Content too large: {0}")]
@@ -37,6 +50,26 @@ pub enum FetchError {
     #[error("SSL/TLS error: {0}")]
     TlsError(String),
 
+    /// Disallowed by the site's robots.txt
+    #[error("Disallowed by robots.txt: {0}")]
+    RobotsDisallowed(String),
+
+    /// Blocked by the egress policy (private address, denied domain or port)
+    #[error("Egress denied: {0}")]
+    EgressDenied(String),
+
+    /// An interrupted download cannot be resumed (resource changed, range refused)
+    #[error("Cannot resume download: {0}")]
+    ResumeFailed(String),
+
+    /// No recorded response for the request in replay mode
+    #[error("No recorded response: {0}")]
+    ReplayMiss(String),
+
+    /// The host's circuit breaker is open after repeated failures
+    #[error("Circuit open: {0}")]
+    CircuitOpen(String),
+
     /// Not implemented (temporary for development)
     #[error("Not implemented: {0}")]
     NotImplemented(String),
@@ -44,4 +77,74 @@ pub enum FetchError {
     /// Other error
     #[error("Other error: {0}")]
     Other(String),
-}
\ No newline at end of file
+}
+
+/// Kind of a `FetchError`, used to configure which failures are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchErrorKind {
+    /// `FetchError::InvalidUrl`
+    InvalidUrl,
+    /// `FetchError::InvalidRequest`
+    InvalidRequest,
+    /// `FetchError::NetworkError`
+    NetworkError,
+    /// `FetchError::Timeout`
+    Timeout,
+    /// `FetchError::TooManyRedirects`
+    TooManyRedirects,
+    /// `FetchError::ServerError`
+    ServerError,
+    /// `FetchError::ContentTooLarge`
+    ContentTooLarge,
+    /// `FetchError::UnsupportedProtocol`
+    UnsupportedProtocol,
+    /// `FetchError::TlsError`
+    TlsError,
+    /// `FetchError::RobotsDisallowed`
+    RobotsDisallowed,
+    /// `FetchError::EgressDenied`
+    EgressDenied,
+    /// `FetchError::ResumeFailed`
+    ResumeFailed,
+    /// `FetchError::ReplayMiss`
+    ReplayMiss,
+    /// `FetchError::CircuitOpen`
+    CircuitOpen,
+    /// `FetchError::NotImplemented`
+    NotImplemented,
+    /// `FetchError::Other`
+    Other,
+}
+
+impl FetchError {
+    /// Get the kind of this error.
+    pub fn kind(&self) -> FetchErrorKind {
+        match self {
+            FetchError::InvalidUrl(_) => FetchErrorKind::InvalidUrl,
+            FetchError::InvalidRequest(_) => FetchErrorKind::InvalidRequest,
+            FetchError::NetworkError(_) => FetchErrorKind::NetworkError,
+            FetchError::Timeout(_) => FetchErrorKind::Timeout,
+            FetchError::TooManyRedirects(_) => FetchErrorKind::TooManyRedirects,
+            FetchError::ServerError { .. } => FetchErrorKind::ServerError,
+            FetchError::ContentTooLarge(_) => FetchErrorKind::ContentTooLarge,
+            FetchError::UnsupportedProtocol(_) => FetchErrorKind::UnsupportedProtocol,
+            FetchError::TlsError(_) => FetchErrorKind::TlsError,
+            FetchError::RobotsDisallowed(_) => FetchErrorKind::RobotsDisallowed,
+            FetchError::EgressDenied(_) => FetchErrorKind::EgressDenied,
+            FetchError::ResumeFailed(_) => FetchErrorKind::ResumeFailed,
+            FetchError::ReplayMiss(_) => FetchErrorKind::ReplayMiss,
+            FetchError::CircuitOpen(_) => FetchErrorKind::CircuitOpen,
+            FetchError::NotImplemented(_) => FetchErrorKind::NotImplemented,
+            FetchError::Other(_) => FetchErrorKind::Other,
+        }
+    }
+
+    /// HTTP status code, for errors caused by an HTTP response.
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            FetchError::ServerError { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
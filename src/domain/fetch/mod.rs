@@ -1,10 +1,34 @@
 //! Fetch operation domain logic.
 
+pub mod auth;
+pub mod breaker;
+pub mod coalesce;
 pub mod config;
+pub mod cookies;
+pub mod egress;
 pub mod service;
 pub mod error;
+pub mod retry;
+pub mod politeness;
+pub mod probe;
+pub mod proxy;
+pub mod range;
+pub mod redirect;
+pub mod robots;
 
 // Re-exports
-pub use config::FetchConfig;
+pub use auth::{CredentialStore, Credentials};
+pub use breaker::{BreakerConfig, BreakerState, BreakerStatus, CircuitBreakers};
+pub use coalesce::{CoalesceConfig, CoalesceStats};
+pub use config::{FetchConfig, HttpMethod, RequestBody, StatusRange};
+pub use cookies::{Cookie, CookieJar};
+pub use egress::{EgressConfig, EgressPolicy};
 pub use service::{FetchService, DefaultFetchService};
-pub use error::FetchError;
\ No newline at end of file
+pub use error::{FetchError, FetchErrorKind};
+pub use retry::RetryPolicy;
+pub use politeness::{HostLimits, HostScheduler, PolitenessConfig};
+pub use probe::{ProbeMethod, ProbeResult};
+pub use proxy::{ProxyConfig, ProxyPool, ProxySettings, RotationStrategy};
+pub use range::{ByteRange, ContentRange, ResumeState};
+pub use redirect::RedirectHop;
+pub use robots::{RobotsCache, RobotsConfig, RobotsTxt};
\ No newline at end of file
@@ -0,0 +1,367 @@
+//! Per-host politeness: concurrency caps, request spacing and adaptive slowdown.
+//!
+//! Every fetch acquires a `HostPermit` from the `HostScheduler` before it
+//! touches the network. The scheduler caps in-flight requests per host, keeps
+//! a minimum delay between request starts, and widens that delay
+//! multiplicatively when a host answers 429/503 or its latency spikes. Healthy
+//! responses shrink the delay again additively (AIMD).
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Maximum number of hosts tracked at once.
+const MAX_TRACKED_HOSTS: usize = 10_000;
+
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+/// Limits for a single host.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostLimits {
+    /// Maximum in-flight requests (falls back to the global limit)
+    pub max_concurrent: Option<usize>,
+    /// Minimum delay between request starts (falls back to the global delay)
+    pub min_delay: Option<Duration>,
+}
+
+/// Politeness configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolitenessConfig {
+    /// Whether politeness limits are enforced at all
+    pub enabled: bool,
+    /// Maximum in-flight requests per host
+    pub max_concurrent_per_host: usize,
+    /// Minimum delay between request starts to the same host
+    pub min_delay: Duration,
+    /// Slow down automatically on 429/503 and latency spikes
+    pub adaptive: bool,
+    /// Multiplier applied to the delay when a host pushes back
+    pub backoff_factor: f64,
+    /// Smallest delay used once a host has pushed back
+    pub backoff_floor: Duration,
+    /// Amount the delay shrinks after each healthy response
+    pub recovery_step: Duration,
+    /// Upper bound for the adaptive delay
+    pub max_delay: Duration,
+    /// Latency above this multiple of the moving average counts as a spike
+    pub latency_spike_factor: f64,
+    /// Per-host overrides, keyed by host name (also matches subdomains)
+    pub hosts: HashMap<String, HostLimits>,
+}
+
+impl Default for PolitenessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_concurrent_per_host: 8,
+            min_delay: Duration::ZERO,
+            adaptive: true,
+            backoff_factor: 2.0,
+            backoff_floor: Duration::from_millis(250),
+            recovery_step: Duration::from_millis(50),
+            max_delay: Duration::from_secs(30),
+            latency_spike_factor: 3.0,
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+impl PolitenessConfig {
+    /// Resolve the effective limits for `host`.
+    ///
+    /// An exact override wins; otherwise the most specific parent domain
+    /// override applies (`example.com` covers `www.example.com`).
+    pub fn limits_for(&self, host: &str) -> (usize, Duration) {
+        let mut domain = host;
+        let limits = loop {
+            if let Some(limits) = self.hosts.get(domain) {
+                break Some(limits);
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => domain = parent,
+                _ => break None,
+            }
+        };
+
+        let max_concurrent = limits
+            .and_then(|l| l.max_concurrent)
+            .unwrap_or(self.max_concurrent_per_host)
+            .max(1);
+        let min_delay = limits.and_then(|l| l.min_delay).unwrap_or(self.min_delay);
+        (max_concurrent, min_delay)
+    }
+}
+
+/// Mutable pacing state for a host.
+#[derive(Debug)]
+struct Pacing {
+    /// Baseline delay from configuration
+    min_delay: Duration,
+    /// Current (possibly widened) delay between request starts
+    delay: Duration,
+    /// Earliest start time for the next request
+    next_start: Instant,
+    /// Moving average of time-to-response
+    avg_latency: Option<Duration>,
+}
+
+/// Scheduling state for a single host.
+#[derive(Debug)]
+struct HostState {
+    semaphore: Arc<Semaphore>,
+    pacing: Mutex<Pacing>,
+}
+
+/// Per-host request scheduler.
+#[derive(Debug)]
+pub struct HostScheduler {
+    config: Arc<PolitenessConfig>,
+    hosts: Mutex<LruCache<String, Arc<HostState>>>,
+}
+
+impl HostScheduler {
+    /// Create a scheduler with the given configuration.
+    pub fn new(config: PolitenessConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            hosts: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_TRACKED_HOSTS).unwrap())),
+        }
+    }
+
+    /// Get the scheduler configuration.
+    pub fn config(&self) -> &PolitenessConfig {
+        &self.config
+    }
+
+    /// Wait until a request to `url` may start.
+    ///
+    /// Returns `None` when politeness is disabled or the URL has no host.
+    pub async fn acquire(&self, url: &str) -> Option<HostPermit> {
+        if !self.config.enabled {
+            return None;
+        }
+        let host = url::Url::parse(url).ok()?.host_str()?.to_ascii_lowercase();
+        let state = self.state_for(&host);
+
+        let permit = state
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("host semaphore is never closed");
+
+        // Reserve a start slot, then sleep outside the lock
+        let start_at = {
+            let mut pacing = state.pacing.lock().unwrap();
+            let now = Instant::now();
+            let start_at = pacing.next_start.max(now);
+            pacing.next_start = start_at + pacing.delay;
+            start_at
+        };
+        if start_at > Instant::now() {
+            tracing::debug!(
+                "Delaying request to {} by {:?}",
+                host,
+                start_at - Instant::now()
+            );
+            tokio::time::sleep_until(start_at).await;
+        }
+
+        Some(HostPermit {
+            host,
+            state,
+            config: self.config.clone(),
+            started: Instant::now(),
+            _permit: permit,
+        })
+    }
+
+    /// Raise the baseline delay for the host of `url` to honor a crawl delay.
+    ///
+    /// The delay is capped at `max_delay` so a hostile robots.txt cannot stall
+    /// a worker indefinitely.
+    pub fn apply_crawl_delay(&self, url: &str, crawl_delay: Duration) {
+        if !self.config.enabled {
+            return;
+        }
+        let Some(host) = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return;
+        };
+
+        let crawl_delay = crawl_delay.min(self.config.max_delay);
+        let state = self.state_for(&host);
+        let mut pacing = state.pacing.lock().unwrap();
+        if crawl_delay > pacing.min_delay {
+            pacing.min_delay = crawl_delay;
+            pacing.delay = pacing.delay.max(crawl_delay);
+        }
+    }
+
+    /// Current delay between requests to `host`, if it is tracked.
+    pub fn current_delay(&self, host: &str) -> Option<Duration> {
+        let hosts = self.hosts.lock().unwrap();
+        hosts
+            .peek(host)
+            .map(|state| state.pacing.lock().unwrap().delay)
+    }
+
+    fn state_for(&self, host: &str) -> Arc<HostState> {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(state) = hosts.get(host) {
+            return state.clone();
+        }
+
+        let (max_concurrent, min_delay) = self.config.limits_for(host);
+        let state = Arc::new(HostState {
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            pacing: Mutex::new(Pacing {
+                min_delay,
+                delay: min_delay,
+                next_start: Instant::now(),
+                avg_latency: None,
+            }),
+        });
+        hosts.put(host.to_string(), state.clone());
+        state
+    }
+}
+
+impl Default for HostScheduler {
+    fn default() -> Self {
+        Self::new(PolitenessConfig::default())
+    }
+}
+
+/// Permission to run one request against a host.
+///
+/// Holds a concurrency slot until dropped; report the outcome with
+/// [`HostPermit::record`] so the scheduler can adapt.
+#[derive(Debug)]
+pub struct HostPermit {
+    host: String,
+    state: Arc<HostState>,
+    config: Arc<PolitenessConfig>,
+    started: Instant,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl HostPermit {
+    /// Host this permit was issued for.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Feed the response status (if any) back into the pacing state.
+    pub fn record(&self, status: Option<u16>) {
+        let config = &self.config;
+        if !config.adaptive {
+            return;
+        }
+
+        let latency = self.started.elapsed();
+        let mut pacing = self.state.pacing.lock().unwrap();
+
+        let spiked = pacing.avg_latency.is_some_and(|avg| {
+            latency.as_secs_f64() > avg.as_secs_f64() * config.latency_spike_factor
+        });
+        pacing.avg_latency = Some(match pacing.avg_latency {
+            Some(avg) => {
+                avg.mul_f64(1.0 - LATENCY_EWMA_ALPHA) + latency.mul_f64(LATENCY_EWMA_ALPHA)
+            }
+            None => latency,
+        });
+
+        if matches!(status, Some(429 | 503)) || spiked {
+            // Multiplicative increase
+            let widened = pacing
+                .delay
+                .mul_f64(config.backoff_factor.max(1.0))
+                .max(config.backoff_floor)
+                .min(config.max_delay);
+            if widened != pacing.delay {
+                tracing::warn!(
+                    "Slowing down requests to {}: delay {:?} -> {:?} (status {:?}, latency {:?})",
+                    self.host,
+                    pacing.delay,
+                    widened,
+                    status,
+                    latency
+                );
+            }
+            pacing.delay = widened;
+        } else if pacing.delay > pacing.min_delay {
+            // Additive decrease
+            pacing.delay = pacing
+                .delay
+                .saturating_sub(config.recovery_step)
+                .max(pacing.min_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_for_matches_parent_domains() {
+        let mut config = PolitenessConfig::default();
+        config.hosts.insert(
+            "example.com".to_string(),
+            HostLimits {
+                max_concurrent: Some(1),
+                min_delay: Some(Duration::from_secs(1)),
+            },
+        );
+
+        assert_eq!(
+            config.limits_for("example.com"),
+            (1, Duration::from_secs(1))
+        );
+        assert_eq!(
+            config.limits_for("www.example.com"),
+            (1, Duration::from_secs(1))
+        );
+        assert_eq!(config.limits_for("example.org"), (8, Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_spacing_and_aimd_backoff() {
+        let scheduler = HostScheduler::new(PolitenessConfig {
+            min_delay: Duration::from_millis(20),
+            backoff_floor: Duration::from_millis(60),
+            recovery_step: Duration::from_millis(10),
+            ..PolitenessConfig::default()
+        });
+        let url = "https://example.com/page";
+
+        let start = Instant::now();
+        let first = scheduler.acquire(url).await.unwrap();
+        first.record(Some(200));
+        let second = scheduler.acquire(url).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        second.record(Some(429));
+        assert_eq!(
+            scheduler.current_delay("example.com"),
+            Some(Duration::from_millis(60))
+        );
+
+        drop(second);
+        scheduler.acquire(url).await.unwrap().record(Some(200));
+        assert_eq!(
+            scheduler.current_delay("example.com"),
+            Some(Duration::from_millis(50))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Cheap URL inspection.
+//!
+//! A probe sends `HEAD` and reads nothing but the headers. Servers that
+//! reject `HEAD` are asked for the first few KB with a ranged `GET` instead,
+//! which is also enough to sniff the charset of textual responses.
+
+use serde::Serialize;
+
+use super::config::{FetchConfig, HttpMethod, StatusRange};
+use super::range::ContentRange;
+use super::redirect::RedirectHop;
+use crate::common::types::header;
+use crate::infra::http::resolver::Resolution;
+
+/// Number of body bytes requested when falling back to a ranged `GET`.
+pub const PROBE_BYTES: u64 = 4 * 1024;
+
+/// Request the probe answer came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeMethod {
+    /// `HEAD` request
+    Head,
+    /// `GET` for the first `PROBE_BYTES` bytes after `HEAD` was rejected
+    RangedGet,
+}
+
+/// One metric of a `Server-Timing` header.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ServerTiming {
+    /// Metric name
+    pub name: String,
+    /// Duration in milliseconds
+    pub duration_ms: Option<f64>,
+    /// Description
+    pub description: Option<String>,
+}
+
+/// Result of probing a URL.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    /// HTTP status code of the final response
+    pub status_code: u16,
+    /// Final URL after redirects
+    pub final_url: String,
+    /// Request the result came from
+    pub method: ProbeMethod,
+    /// Status of the rejected `HEAD` when falling back to `GET`
+    pub head_status: Option<u16>,
+    /// Content-Type header
+    pub content_type: Option<String>,
+    /// Length of the whole resource, if known
+    pub content_length: Option<u64>,
+    /// Declared charset, or the one detected in the first bytes after a fallback
+    pub charset: Option<String>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Headers of the final response (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// Metrics of the `Server-Timing` header
+    pub server_timing: Vec<ServerTiming>,
+    /// How the final host was resolved
+    pub dns: Option<Resolution>,
+    /// Number of requests made, fallback and retries included
+    pub attempts: u32,
+    /// Time until the final response headers were received, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+/// Config for the `HEAD` request of a probe.
+///
+/// Every status is returned rather than failing, so dead links are reported
+/// like any other response.
+pub fn probe_config(config: &FetchConfig) -> FetchConfig {
+    FetchConfig {
+        method: HttpMethod::Head,
+        body: None,
+        transcode_to_utf8: false,
+        html_redirects: None,
+        accept_statuses: vec![StatusRange {
+            start: 100,
+            end: 599,
+        }],
+        ..config.clone()
+    }
+}
+
+/// Whether a `HEAD` response means the server does not support `HEAD`.
+pub fn head_rejected(status: u16) -> bool {
+    matches!(status, 405 | 501)
+}
+
+/// Length of the whole resource from the response headers.
+///
+/// Partial responses carry it in `Content-Range`; the `Content-Length` of a
+/// `HEAD` response is that of the body a `GET` would return.
+pub fn content_length(status: u16, headers: &[(String, String)]) -> Option<u64> {
+    if status == 206 {
+        return ContentRange::from_headers(headers).and_then(|range| range.total);
+    }
+    header(headers, "content-length").and_then(|value| value.trim().parse().ok())
+}
+
+/// Parse the `Server-Timing` headers, e.g. `db;dur=53, cache;desc="Cache Read";dur=23.2`.
+pub fn server_timing(headers: &[(String, String)]) -> Vec<ServerTiming> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("server-timing"))
+        .flat_map(|(_, value)| value.split(','))
+        .filter_map(|metric| {
+            let mut params = metric.split(';');
+            let name = params.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let mut timing = ServerTiming {
+                name: name.to_string(),
+                duration_ms: None,
+                description: None,
+            };
+            for param in params {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "dur" => timing.duration_ms = value.parse().ok(),
+                    "desc" => timing.description = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            Some(timing)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_content_length() {
+        let head = headers(&[("content-length", "1234")]);
+        assert_eq!(content_length(200, &head), Some(1234));
+        let partial = headers(&[
+            ("content-length", "4096"),
+            ("content-range", "bytes 0-4095/98765"),
+        ]);
+        assert_eq!(content_length(206, &partial), Some(98765));
+        let unknown = headers(&[("content-range", "bytes 0-4095/*")]);
+        assert_eq!(content_length(206, &unknown), None);
+    }
+
+    #[test]
+    fn test_server_timing() {
+        let timings = server_timing(&headers(&[
+            (
+                "server-timing",
+                "db;dur=53, cache;desc=\"Cache Read\";dur=23.2",
+            ),
+            ("server-timing", "miss"),
+        ]));
+        assert_eq!(
+            timings,
+            vec![
+                ServerTiming {
+                    name: "db".to_string(),
+                    duration_ms: Some(53.0),
+                    description: None,
+                },
+                ServerTiming {
+                    name: "cache".to_string(),
+                    duration_ms: Some(23.2),
+                    description: Some("Cache Read".to_string()),
+                },
+                ServerTiming {
+                    name: "miss".to_string(),
+                    duration_ms: None,
+                    description: None,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_falls_back_to_ranged_get() {
+        use crate::domain::fetch::service::DefaultFetchService;
+        use crate::infra::http::HttpClient;
+        use axum::http::{HeaderMap, StatusCode, header};
+
+        let app = axum::Router::new()
+            .route(
+                "/doc",
+                axum::routing::get(|headers: HeaderMap| async move {
+                    assert_eq!(headers.get(header::RANGE).unwrap(), "bytes=0-4095");
+                    let mut body = b"<meta charset=\"shift_jis\">".to_vec();
+                    body.resize(4096, b' ');
+                    (
+                        StatusCode::PARTIAL_CONTENT,
+                        [
+                            (header::CONTENT_TYPE, "text/html"),
+                            (header::CONTENT_RANGE, "bytes 0-4095/10000"),
+                        ],
+                        body,
+                    )
+                })
+                .head(|| async { StatusCode::METHOD_NOT_ALLOWED }),
+            )
+            .route(
+                "/gone",
+                axum::routing::get(|| async {
+                    (
+                        StatusCode::GONE,
+                        [
+                            (header::CONTENT_TYPE, "text/html; charset=iso-8859-1"),
+                            (
+                                header::HeaderName::from_static("server-timing"),
+                                "app;dur=12.5",
+                            ),
+                        ],
+                        "gone",
+                    )
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let service = DefaultFetchService::new(HttpClient::new().unwrap());
+        let config = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+
+        let doc = service
+            .probe(&format!("{}/doc", base), &config)
+            .await
+            .unwrap();
+        assert_eq!(doc.method, ProbeMethod::RangedGet);
+        assert_eq!(doc.head_status, Some(405));
+        assert_eq!(doc.status_code, 206);
+        assert_eq!(doc.content_length, Some(10000));
+        assert_eq!(doc.charset.as_deref(), Some("Shift_JIS"));
+        assert_eq!(doc.attempts, 2);
+
+        let gone = service
+            .probe(&format!("{}/gone", base), &config)
+            .await
+            .unwrap();
+        assert_eq!(gone.method, ProbeMethod::Head);
+        assert_eq!(gone.status_code, 410);
+        assert_eq!(gone.content_length, Some(4));
+        assert_eq!(gone.charset.as_deref(), Some("windows-1252"));
+        assert_eq!(gone.server_timing[0].duration_ms, Some(12.5));
+    }
+}
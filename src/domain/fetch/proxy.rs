@@ -0,0 +1,419 @@
+//! Proxy selection: global, per-host and per-request proxies plus a rotating pool.
+//!
+//! Precedence for a request is: the proxy set on the request itself, then a
+//! per-host override, then the rotating pool, then the global default.
+//! Pool members that fail `max_failures` times in a row are ejected for
+//! `ejection_cooldown` and rejoin afterwards.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use super::config::FetchConfig;
+use super::error::{FetchError, FetchErrorKind};
+
+/// A single proxy server.
+///
+/// `url` may use the `http`, `https`, `socks5` or `socks5h` scheme. Credentials
+/// can be given separately or embedded in the URL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy URL, e.g. `http://proxy:8080` or `socks5://proxy:1080`
+    pub url: String,
+    /// Username for proxy authentication
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password for proxy authentication
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    /// Create a proxy from a URL (credentials may be embedded).
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            username: None,
+            password: None,
+        }
+    }
+
+    /// Set explicit credentials.
+    pub fn with_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Check the URL and scheme.
+    pub fn validate(&self) -> Result<(), FetchError> {
+        let url = url::Url::parse(&self.url).map_err(|e| {
+            FetchError::InvalidRequest(format!("proxy URL {}: {}", self.display_url(), e))
+        })?;
+        match url.scheme() {
+            "http" | "https" | "socks5" | "socks5h" => Ok(()),
+            scheme => Err(FetchError::InvalidRequest(format!(
+                "unsupported proxy scheme: {}",
+                scheme
+            ))),
+        }
+    }
+
+    /// Proxy URL with any embedded password masked, safe for logs and metadata.
+    pub fn display_url(&self) -> String {
+        match url::Url::parse(&self.url) {
+            Ok(mut url) => {
+                if url.password().is_some() {
+                    let _ = url.set_password(Some("***"));
+                }
+                url.to_string()
+            }
+            Err(_) => "<invalid proxy url>".to_string(),
+        }
+    }
+}
+
+/// How the pool picks a proxy for each request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationStrategy {
+    /// Cycle through healthy proxies in order
+    #[default]
+    RoundRobin,
+    /// Pick a healthy proxy at random
+    Random,
+    /// Keep using the same proxy for a host while it stays healthy
+    StickyPerHost,
+}
+
+/// Proxy settings applied by the fetch service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// Proxy used when nothing more specific applies
+    pub default: Option<ProxyConfig>,
+    /// Per-host proxies, keyed by host name (also matches subdomains)
+    pub hosts: HashMap<String, ProxyConfig>,
+    /// Rotating pool (takes precedence over `default` when non-empty)
+    pub pool: Vec<ProxyConfig>,
+    /// Pool rotation strategy
+    pub strategy: RotationStrategy,
+    /// Consecutive failures before a pool member is ejected
+    pub max_failures: u32,
+    /// How long an ejected proxy stays out of rotation
+    pub ejection_cooldown: Duration,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            default: None,
+            hosts: HashMap::new(),
+            pool: Vec::new(),
+            strategy: RotationStrategy::RoundRobin,
+            max_failures: 3,
+            ejection_cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ProxySettings {
+    /// Per-host override for `host`, matching parent domains.
+    fn host_proxy(&self, host: &str) -> Option<&ProxyConfig> {
+        let mut domain = host;
+        loop {
+            if let Some(proxy) = self.hosts.get(domain) {
+                return Some(proxy);
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => domain = parent,
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Health of a pool member.
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    successes: u64,
+    failures: u64,
+    ejected_until: Option<Instant>,
+}
+
+impl Health {
+    fn is_available(&self, now: Instant) -> bool {
+        self.ejected_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// Snapshot of a pool member's health.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyStatus {
+    /// Proxy URL (credentials masked)
+    pub url: String,
+    /// Whether the proxy is currently in rotation
+    pub healthy: bool,
+    /// Successful requests through this proxy
+    pub successes: u64,
+    /// Failed requests through this proxy
+    pub failures: u64,
+}
+
+/// The proxy picked for one request.
+#[derive(Debug, Clone)]
+pub struct ProxyChoice {
+    /// Proxy to use
+    pub proxy: ProxyConfig,
+    /// Index in the pool, when the proxy came from it
+    pool_index: Option<usize>,
+}
+
+/// Selects proxies and tracks the health of pool members.
+#[derive(Debug)]
+pub struct ProxyPool {
+    settings: ProxySettings,
+    health: Vec<Mutex<Health>>,
+    next: AtomicUsize,
+    sticky: Mutex<HashMap<String, usize>>,
+}
+
+impl ProxyPool {
+    /// Create a pool from settings.
+    pub fn new(settings: ProxySettings) -> Self {
+        let health = settings.pool.iter().map(|_| Mutex::default()).collect();
+        Self {
+            settings,
+            health,
+            next: AtomicUsize::new(0),
+            sticky: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the proxy settings.
+    pub fn settings(&self) -> &ProxySettings {
+        &self.settings
+    }
+
+    /// Pick the proxy for a request to `url`, if any.
+    pub fn select(&self, url: &str, request: &FetchConfig) -> Option<ProxyChoice> {
+        if let Some(proxy) = &request.proxy {
+            return Some(ProxyChoice {
+                proxy: proxy.clone(),
+                pool_index: None,
+            });
+        }
+
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .unwrap_or_default();
+        if let Some(proxy) = self.settings.host_proxy(&host) {
+            return Some(ProxyChoice {
+                proxy: proxy.clone(),
+                pool_index: None,
+            });
+        }
+
+        if let Some(index) = self.pick(&host) {
+            return Some(ProxyChoice {
+                proxy: self.settings.pool[index].clone(),
+                pool_index: Some(index),
+            });
+        }
+
+        self.settings.default.clone().map(|proxy| ProxyChoice {
+            proxy,
+            pool_index: None,
+        })
+    }
+
+    /// Record the outcome of a request made through `choice`.
+    ///
+    /// Only connection-level failures (and 407 from the proxy) count against
+    /// the proxy; errors returned by the target site do not.
+    pub fn report(&self, choice: &ProxyChoice, error: Option<&FetchError>) {
+        let Some(index) = choice.pool_index else {
+            return;
+        };
+        let proxy_failed = error.is_some_and(|e| {
+            matches!(
+                e.kind(),
+                FetchErrorKind::NetworkError | FetchErrorKind::Timeout | FetchErrorKind::TlsError
+            ) || e.status_code() == Some(407)
+        });
+
+        let mut health = self.health[index].lock().unwrap();
+        if !proxy_failed {
+            health.successes += 1;
+            health.consecutive_failures = 0;
+            return;
+        }
+
+        health.failures += 1;
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.settings.max_failures.max(1) {
+            health.consecutive_failures = 0;
+            health.ejected_until = Some(Instant::now() + self.settings.ejection_cooldown);
+            tracing::warn!(
+                "Ejecting proxy {} for {:?} after repeated failures",
+                choice.proxy.display_url(),
+                self.settings.ejection_cooldown
+            );
+        }
+    }
+
+    /// Health of every pool member.
+    pub fn status(&self) -> Vec<ProxyStatus> {
+        let now = Instant::now();
+        self.settings
+            .pool
+            .iter()
+            .zip(&self.health)
+            .map(|(proxy, health)| {
+                let health = health.lock().unwrap();
+                ProxyStatus {
+                    url: proxy.display_url(),
+                    healthy: health.is_available(now),
+                    successes: health.successes,
+                    failures: health.failures,
+                }
+            })
+            .collect()
+    }
+
+    /// Pick a pool member for `host` according to the rotation strategy.
+    fn pick(&self, host: &str) -> Option<usize> {
+        if self.settings.pool.is_empty() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let available: Vec<usize> = (0..self.health.len())
+            .filter(|&i| self.health[i].lock().unwrap().is_available(now))
+            .collect();
+        if available.is_empty() {
+            // Everything is ejected: use the proxy that comes back first
+            tracing::warn!("All proxies are ejected, using the one closest to recovery");
+            return (0..self.health.len())
+                .min_by_key(|&i| self.health[i].lock().unwrap().ejected_until);
+        }
+
+        let index = match self.settings.strategy {
+            RotationStrategy::RoundRobin => {
+                available[self.next.fetch_add(1, Ordering::Relaxed) % available.len()]
+            }
+            RotationStrategy::Random => available[rand::thread_rng().gen_range(0..available.len())],
+            RotationStrategy::StickyPerHost => {
+                let mut sticky = self.sticky.lock().unwrap();
+                match sticky.get(host) {
+                    Some(index) if available.contains(index) => *index,
+                    _ => {
+                        let index =
+                            available[self.next.fetch_add(1, Ordering::Relaxed) % available.len()];
+                        sticky.insert(host.to_string(), index);
+                        index
+                    }
+                }
+            }
+        };
+        Some(index)
+    }
+}
+
+impl Default for ProxyPool {
+    fn default() -> Self {
+        Self::new(ProxySettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(strategy: RotationStrategy) -> ProxyPool {
+        ProxyPool::new(ProxySettings {
+            pool: vec![
+                ProxyConfig::new("http://p1:8080"),
+                ProxyConfig::new("socks5://user:secret@p2:1080"),
+            ],
+            hosts: HashMap::from([(
+                "example.org".to_string(),
+                ProxyConfig::new("http://host-proxy:3128"),
+            )]),
+            strategy,
+            max_failures: 2,
+            ..ProxySettings::default()
+        })
+    }
+
+    fn url_of(choice: Option<ProxyChoice>) -> String {
+        choice.unwrap().proxy.url
+    }
+
+    #[test]
+    fn test_selection_precedence_and_rotation() {
+        let pool = pool(RotationStrategy::RoundRobin);
+        let config = FetchConfig::default();
+
+        assert_eq!(
+            url_of(pool.select("https://a.com/", &config)),
+            "http://p1:8080"
+        );
+        assert_eq!(
+            url_of(pool.select("https://a.com/", &config)),
+            "socks5://user:secret@p2:1080"
+        );
+        assert_eq!(
+            url_of(pool.select("https://www.example.org/", &config)),
+            "http://host-proxy:3128"
+        );
+
+        let per_request = FetchConfig {
+            proxy: Some(ProxyConfig::new("http://mine:1")),
+            ..FetchConfig::default()
+        };
+        assert_eq!(
+            url_of(pool.select("https://www.example.org/", &per_request)),
+            "http://mine:1"
+        );
+
+        assert_eq!(
+            ProxyConfig::new("socks5://user:secret@p2:1080").display_url(),
+            "socks5://user:***@p2:1080"
+        );
+        assert!(ProxyConfig::new("ftp://p:21").validate().is_err());
+    }
+
+    #[test]
+    fn test_sticky_and_ejection() {
+        let pool = pool(RotationStrategy::StickyPerHost);
+        let config = FetchConfig::default();
+
+        let first = pool.select("https://a.com/x", &config).unwrap();
+        let again = pool.select("https://a.com/y", &config).unwrap();
+        assert_eq!(first.proxy, again.proxy);
+
+        // Errors from the target site do not count against the proxy
+        let not_found = FetchError::ServerError {
+            status: 404,
+            message: "HTTP 404".into(),
+            retry_after: None,
+        };
+        pool.report(&first, Some(&not_found));
+        pool.report(&first, Some(&not_found));
+        assert!(pool.status().iter().all(|s| s.healthy));
+
+        let refused = FetchError::NetworkError("connection refused".into());
+        pool.report(&first, Some(&refused));
+        pool.report(&first, Some(&refused));
+        assert_eq!(pool.status().iter().filter(|s| !s.healthy).count(), 1);
+
+        let moved = pool.select("https://a.com/z", &config).unwrap();
+        assert_ne!(moved.proxy, first.proxy);
+    }
+}
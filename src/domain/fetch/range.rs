@@ -0,0 +1,172 @@
+//! Byte ranges and resumable downloads.
+//!
+//! Interrupted downloads resume with `Range: bytes=N-`, guarded by an
+//! `If-Range` validator so a resource that changed in the meantime is not
+//! stitched together from two versions.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::error::FetchError;
+use crate::common::types::header;
+
+/// An inclusive byte range (`bytes=start-end`); `end` is open when `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteRange {
+    /// First byte
+    pub start: u64,
+    /// Last byte, inclusive
+    pub end: Option<u64>,
+}
+
+impl ByteRange {
+    /// Bytes `start..=end`.
+    pub fn new(start: u64, end: u64) -> Result<Self, FetchError> {
+        if end < start {
+            return Err(FetchError::InvalidRequest(format!(
+                "byte range {}-{} ends before it starts",
+                start, end
+            )));
+        }
+        Ok(Self {
+            start,
+            end: Some(end),
+        })
+    }
+
+    /// Everything from `start` to the end of the resource.
+    pub fn starting_at(start: u64) -> Self {
+        Self { start, end: None }
+    }
+
+    /// Number of bytes in the range, if bounded.
+    pub fn len(&self) -> Option<u64> {
+        self.end.map(|end| end - self.start + 1)
+    }
+
+    /// Whether the range is empty (never true for a valid range).
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+}
+
+/// `Range` header value.
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "bytes={}-{}", self.start, end),
+            None => write!(f, "bytes={}-", self.start),
+        }
+    }
+}
+
+/// A parsed `Content-Range: bytes start-end/total` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentRange {
+    /// First byte sent
+    pub start: u64,
+    /// Last byte sent, inclusive
+    pub end: u64,
+    /// Full length of the resource, if the server knows it
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse a `Content-Range` value; unsatisfied ranges (`bytes */total`) yield `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let rest = value.trim().strip_prefix("bytes")?.trim_start();
+        let (range, total) = rest.split_once('/')?;
+        let (start, end) = range.trim().split_once('-')?;
+        let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse().ok()?),
+        };
+        (start <= end).then_some(Self { start, end, total })
+    }
+
+    /// Find and parse the `Content-Range` header among response headers.
+    pub fn from_headers(headers: &[(String, String)]) -> Option<Self> {
+        header(headers, "content-range").and_then(Self::parse)
+    }
+}
+
+/// Validator to send as `If-Range` when resuming a response with `headers`.
+///
+/// A strong `ETag` is preferred; weak ETags cannot be used with `If-Range`,
+/// so `Last-Modified` is the fallback.
+pub fn validator(headers: &[(String, String)]) -> Option<String> {
+    header(headers, "etag")
+        .map(str::trim)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(headers, "last-modified").map(str::trim))
+        .map(str::to_string)
+}
+
+/// Progress of a download that can be resumed later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeState {
+    /// Bytes already written
+    pub offset: u64,
+    /// `ETag` or `Last-Modified` of the response the bytes came from
+    pub validator: Option<String>,
+    /// Status of the response the bytes came from (200 when unknown)
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
+impl ResumeState {
+    /// Resume after `offset` bytes of the response identified by `validator`.
+    pub fn new(offset: u64, validator: Option<String>) -> Self {
+        Self {
+            offset,
+            validator,
+            status: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_headers() {
+        assert_eq!(ByteRange::starting_at(500).to_string(), "bytes=500-");
+        assert_eq!(ByteRange::new(0, 99).unwrap().to_string(), "bytes=0-99");
+        assert_eq!(ByteRange::new(10, 19).unwrap().len(), Some(10));
+        assert!(ByteRange::new(5, 4).is_err());
+
+        assert_eq!(
+            ContentRange::parse("bytes 500-999/1000"),
+            Some(ContentRange {
+                start: 500,
+                end: 999,
+                total: Some(1000)
+            })
+        );
+        assert_eq!(ContentRange::parse("bytes 0-9/*").unwrap().total, None);
+        assert_eq!(ContentRange::parse("bytes */1000"), None);
+
+        let headers = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(
+            validator(&headers(&[("etag", "\"v1\""), ("last-modified", modified)])).as_deref(),
+            Some("\"v1\"")
+        );
+        assert_eq!(
+            validator(&headers(&[
+                ("etag", "W/\"v1\""),
+                ("last-modified", modified)
+            ]))
+            .as_deref(),
+            Some(modified)
+        );
+        assert_eq!(validator(&headers(&[("etag", "W/\"v1\"")])), None);
+    }
+}
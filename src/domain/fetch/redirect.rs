@@ -0,0 +1,382 @@
+//! Redirect handling for fetches.
+//!
+//! Redirects are followed hop by hop by the streaming client so every hop can
+//! be recorded and gets its own cookies; this module holds the per-hop rules.
+//! HTML-level redirects (meta refresh, canonical links) are detected here and
+//! followed by the fetch service.
+
+use lol_html::{HtmlRewriter, Settings, element};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use super::config::{FetchConfig, HttpMethod};
+use super::error::FetchError;
+
+/// Request headers dropped when a redirect leaves the original origin.
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// Bytes of an HTML page scanned for HTML-level redirects.
+pub const HTML_REDIRECT_SCAN_BYTES: usize = 64 * 1024;
+
+/// How a redirect was signalled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectKind {
+    /// 3xx status with a `Location` header
+    #[default]
+    Http,
+    /// `<meta http-equiv="refresh" content="0; url=...">`
+    MetaRefresh,
+    /// `<link rel="canonical" href="...">`
+    Canonical,
+    /// `<meta property="og:url" content="...">`
+    OgUrl,
+}
+
+/// One redirect response on the way to the final URL.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedirectHop {
+    /// URL that was requested
+    pub url: String,
+    /// Status code (301, 302, 303, 307 or 308; the page status for HTML redirects)
+    pub status_code: u16,
+    /// Raw `Location` header, or the target written in the page
+    pub location: String,
+    /// Time until the hop's response headers arrived, in milliseconds
+    pub elapsed_ms: u64,
+    /// How the redirect was signalled
+    #[serde(default)]
+    pub kind: RedirectKind,
+}
+
+/// Which HTML-level redirects the fetch service follows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HtmlRedirectConfig {
+    /// Follow `<meta http-equiv="refresh">`
+    pub meta_refresh: bool,
+    /// Refreshes with a longer delay are page behaviour (e.g. auto-reload),
+    /// not redirects, and are not followed
+    pub max_refresh_delay_secs: u64,
+    /// Follow `<link rel="canonical">` to another URL
+    pub canonical: bool,
+    /// Follow `<meta property="og:url">` to another URL
+    pub og_url: bool,
+    /// Maximum number of HTML-level hops
+    pub max_hops: usize,
+}
+
+impl Default for HtmlRedirectConfig {
+    fn default() -> Self {
+        Self {
+            meta_refresh: true,
+            max_refresh_delay_secs: 5,
+            canonical: false,
+            og_url: false,
+            max_hops: 3,
+        }
+    }
+}
+
+/// An HTML-level redirect found in a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlRedirect {
+    /// How the redirect was signalled
+    pub kind: RedirectKind,
+    /// Target as written in the page
+    pub location: String,
+    /// Target resolved against the page URL
+    pub target: url::Url,
+}
+
+/// Whether `status` is a redirect that is followed when a `Location` is present.
+pub fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+/// Resolve a `Location` header against the URL it was received from.
+pub fn resolve_location(base: &str, location: &str) -> Result<url::Url, FetchError> {
+    let base = url::Url::parse(base).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+    let target = base
+        .join(location.trim())
+        .map_err(|e| FetchError::InvalidUrl(format!("redirect to {:?}: {}", location, e)))?;
+    match target.scheme() {
+        "http" | "https" => Ok(target),
+        scheme => Err(FetchError::UnsupportedProtocol(format!(
+            "redirect to {} URL {}",
+            scheme, target
+        ))),
+    }
+}
+
+/// Config for following a `status` redirect from `from` to `to`.
+///
+/// 303 (and 301/302 after a POST) switch to a body-less GET as browsers do;
+/// 307/308 repeat the request unchanged. Credentials, stored or explicit,
+/// and explicit cookies are not forwarded to another origin.
+pub fn redirect_config(
+    config: &FetchConfig,
+    status: u16,
+    from: &url::Url,
+    to: &url::Url,
+) -> FetchConfig {
+    let mut next = config.clone();
+
+    let becomes_get = match status {
+        303 => config.method != HttpMethod::Head,
+        301 | 302 => config.method == HttpMethod::Post,
+        _ => false,
+    };
+    if becomes_get {
+        next.method = HttpMethod::Get;
+        next.body = None;
+        next.headers
+            .retain(|name, _| !name.eq_ignore_ascii_case("content-type"));
+    }
+
+    if from.origin() != to.origin() {
+        next.headers.retain(|name, _| {
+            !SENSITIVE_HEADERS
+                .iter()
+                .any(|sensitive| name.eq_ignore_ascii_case(sensitive))
+        });
+        next.cookies.clear();
+        next.auth = None;
+    }
+
+    next
+}
+
+/// Config for following an HTML-level redirect from `from` to `to`.
+///
+/// Browsers navigate with a body-less GET, as after a 303.
+pub fn html_redirect_config(config: &FetchConfig, from: &url::Url, to: &url::Url) -> FetchConfig {
+    let mut next = redirect_config(config, 303, from, to);
+    next.method = HttpMethod::Get;
+    next
+}
+
+/// Whether a response with `content_type` may be an HTML page.
+///
+/// A missing type counts, since such bodies are sniffed as HTML.
+pub fn is_html(content_type: Option<&str>) -> bool {
+    content_type.is_none_or(|content_type| {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        mime.is_empty()
+            || mime.eq_ignore_ascii_case("text/html")
+            || mime.eq_ignore_ascii_case("application/xhtml+xml")
+    })
+}
+
+/// Find the HTML-level redirect of the page at `url`, if `config` follows it.
+///
+/// Only the first `HTML_REDIRECT_SCAN_BYTES` of `html` are scanned. A meta
+/// refresh wins over a canonical link, which wins over `og:url`; targets
+/// equal to the page URL (ignoring the fragment) are not redirects.
+pub fn find_html_redirect(
+    html: &[u8],
+    url: &str,
+    config: &HtmlRedirectConfig,
+) -> Option<HtmlRedirect> {
+    let base = url::Url::parse(url).ok()?;
+    let html = &html[..html.len().min(HTML_REDIRECT_SCAN_BYTES)];
+
+    let refresh = RefCell::new(None);
+    let canonical = RefCell::new(None);
+    let og_url = RefCell::new(None);
+    let attribute = |el: &lol_html::html_content::Element, name: &str| {
+        el.get_attribute(name)
+            .map(|value| html_escape::decode_html_entities(&value).trim().to_string())
+    };
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("meta", |el| {
+                    let content = attribute(el, "content");
+                    if attribute(el, "http-equiv")
+                        .is_some_and(|value| value.eq_ignore_ascii_case("refresh"))
+                    {
+                        let mut refresh = refresh.borrow_mut();
+                        if refresh.is_none() {
+                            *refresh = content.as_deref().and_then(parse_refresh);
+                        }
+                    } else if attribute(el, "property")
+                        .is_some_and(|value| value.eq_ignore_ascii_case("og:url"))
+                    {
+                        og_url
+                            .borrow_mut()
+                            .get_or_insert(content.unwrap_or_default());
+                    }
+                    Ok(())
+                }),
+                element!("link[rel][href]", |el| {
+                    let is_canonical = attribute(el, "rel").is_some_and(|rel| {
+                        rel.split_ascii_whitespace()
+                            .any(|token| token.eq_ignore_ascii_case("canonical"))
+                    });
+                    if is_canonical {
+                        canonical
+                            .borrow_mut()
+                            .get_or_insert(attribute(el, "href").unwrap_or_default());
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        },
+        |_: &[u8]| {},
+    );
+    // A truncated document is fine: elements seen so far were handled
+    let _ = rewriter.write(html);
+    drop(rewriter);
+
+    let refresh = refresh
+        .into_inner()
+        .filter(|(delay, _)| config.meta_refresh && *delay <= config.max_refresh_delay_secs)
+        .and_then(|(_, location)| location)
+        .map(|location| (RedirectKind::MetaRefresh, location));
+    let canonical = canonical
+        .into_inner()
+        .filter(|_| config.canonical)
+        .map(|location| (RedirectKind::Canonical, location));
+    let og_url = og_url
+        .into_inner()
+        .filter(|_| config.og_url)
+        .map(|location| (RedirectKind::OgUrl, location));
+
+    [refresh, canonical, og_url]
+        .into_iter()
+        .flatten()
+        .find_map(|(kind, location)| {
+            let target = resolve_location(base.as_str(), &location).ok()?;
+            let same_page = {
+                let (mut a, mut b) = (target.clone(), base.clone());
+                a.set_fragment(None);
+                b.set_fragment(None);
+                a == b
+            };
+            (!location.is_empty() && !same_page).then_some(HtmlRedirect {
+                kind,
+                location,
+                target,
+            })
+        })
+}
+
+/// Parse a refresh `content` value (`5`, `0; url=/next`, `0;URL='/next'`)
+/// into the delay in seconds and the target, if any.
+fn parse_refresh(content: &str) -> Option<(u64, Option<String>)> {
+    let content = content.trim_start();
+    let digits = content
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(content.len());
+    let (delay, rest) = content.split_at(digits);
+    // Fractions are ignored, as browsers do
+    let delay = delay.split('.').next()?.parse().ok()?;
+
+    let rest = rest
+        .trim_start()
+        .trim_start_matches([';', ','])
+        .trim_start();
+    let rest = match rest.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+            match rest[3..].trim_start().strip_prefix('=') {
+                Some(value) => value.trim_start(),
+                None => rest,
+            }
+        }
+        _ => rest,
+    };
+    let location = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or_default(),
+        _ => rest,
+    }
+    .trim();
+    Some((delay, (!location.is_empty()).then(|| location.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::auth::Credentials;
+    use super::super::config::RequestBody;
+    use super::*;
+
+    #[test]
+    fn test_redirect_config_rewrites_method_and_strips_credentials() {
+        let config = FetchConfig {
+            method: HttpMethod::Post,
+            body: Some(RequestBody::Form(
+                [("a".to_string(), "1".to_string())].into(),
+            )),
+            headers: [
+                ("Authorization".to_string(), "Bearer x".to_string()),
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("X-Trace".to_string(), "1".to_string()),
+            ]
+            .into(),
+            cookies: [("sid".to_string(), "1".to_string())].into(),
+            auth: Some(Credentials::Bearer {
+                token: "t".to_string(),
+            }),
+            ..Default::default()
+        };
+        let from = url::Url::parse("https://example.com/login").unwrap();
+
+        let same_origin = resolve_location(from.as_str(), "/home").unwrap();
+        let next = redirect_config(&config, 302, &from, &same_origin);
+        assert_eq!(next.method, HttpMethod::Get);
+        assert!(next.body.is_none());
+        assert!(next.headers.contains_key("Authorization"));
+        assert!(next.auth.is_some());
+        assert!(!next.headers.contains_key("Content-Type"));
+
+        let other = resolve_location(from.as_str(), "https://cdn.example.net/x").unwrap();
+        let next = redirect_config(&config, 307, &from, &other);
+        assert_eq!(next.method, HttpMethod::Post);
+        assert!(next.body.is_some());
+        assert!(!next.headers.contains_key("Authorization"));
+        assert!(next.headers.contains_key("X-Trace"));
+        assert!(next.cookies.is_empty());
+        assert!(next.auth.is_none());
+
+        assert!(resolve_location(from.as_str(), "ftp://example.com/").is_err());
+    }
+
+    #[test]
+    fn test_find_html_redirects() {
+        let config = HtmlRedirectConfig {
+            canonical: true,
+            ..HtmlRedirectConfig::default()
+        };
+        let url = "https://example.com/old?a=1";
+        let find = |html: &str| find_html_redirect(html.as_bytes(), url, &config);
+
+        let page = r#"<html><head>
+            <link rel="Canonical" href="https://example.com/canonical">
+            <META HTTP-EQUIV="Refresh" CONTENT="0;URL='/new?a=1&amp;b=2'">
+            </head>"#;
+        let redirect = find(page).unwrap();
+        assert_eq!(redirect.kind, RedirectKind::MetaRefresh);
+        assert_eq!(redirect.location, "/new?a=1&b=2");
+        assert_eq!(redirect.target.as_str(), "https://example.com/new?a=1&b=2");
+
+        // Slow refreshes are page behaviour; the canonical link applies instead
+        let slow = page.replace("CONTENT=\"0;", "CONTENT=\"300;");
+        assert_eq!(find(&slow).unwrap().kind, RedirectKind::Canonical);
+
+        // Self-references are not redirects, and og:url is off by default
+        assert!(find(r#"<link rel="canonical" href="/old?a=1#top">"#).is_none());
+        assert!(find(r#"<meta property="og:url" content="https://example.com/x">"#).is_none());
+        assert!(find(r#"<meta http-equiv="refresh" content="5">"#).is_none());
+
+        assert_eq!(
+            parse_refresh("3, url = next.html"),
+            Some((3, Some("next.html".to_string())))
+        );
+        assert_eq!(
+            parse_refresh("1.5;https://example.com/"),
+            Some((1, Some("https://example.com/".to_string())))
+        );
+        assert_eq!(parse_refresh("soon"), None);
+    }
+}
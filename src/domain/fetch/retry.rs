@@ -0,0 +1,229 @@
+//! Retry policy with exponential backoff for fetch operations.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+use super::config::HttpMethod;
+use super::error::{FetchError, FetchErrorKind};
+
+/// Retry policy applied to fetch operations.
+///
+/// Delays grow as `base_delay * 2^(attempt - 1)`, capped at `max_delay`, with
+/// a random `jitter` fraction subtracted to spread out retries from many
+/// workers. A `Retry-After` header on 429/503 responses overrides the
+/// computed delay; one asking for longer than `max_delay` ends the retries
+/// rather than retrying before the server is ready.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first (1 disables retries)
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Upper bound for any single delay (a longer `Retry-After` gives up)
+    pub max_delay: Duration,
+    /// Fraction of each delay (0.0 - 1.0) that is randomized
+    pub jitter: f64,
+    /// Error kinds that are retried
+    pub retry_on: Vec<FetchErrorKind>,
+    /// HTTP status codes that are retried (for `ServerError`)
+    pub retry_statuses: Vec<u16>,
+    /// Honor `Retry-After` on 429 and 503 responses
+    pub respect_retry_after: bool,
+    /// Also retry non-idempotent methods (POST)
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            retry_on: vec![
+                FetchErrorKind::NetworkError,
+                FetchErrorKind::Timeout,
+                FetchErrorKind::ServerError,
+            ],
+            retry_statuses: vec![429, 500, 502, 503, 504],
+            respect_retry_after: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether `error` is retryable under this policy, ignoring the attempt budget.
+    pub fn is_retryable(&self, error: &FetchError, method: HttpMethod) -> bool {
+        if method == HttpMethod::Post && !self.retry_non_idempotent {
+            return false;
+        }
+
+        if !self.retry_on.contains(&error.kind()) {
+            return false;
+        }
+
+        match error.status_code() {
+            Some(status) => self.retry_statuses.contains(&status),
+            None => true,
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let factor = 1.0 - jitter * rand::thread_rng().gen_range(0.0..=1.0);
+        delay.mul_f64(factor)
+    }
+
+    /// Decide whether to retry after `attempt` failed with `error`.
+    ///
+    /// Returns the delay to wait, or `None` to give up, which includes a
+    /// `Retry-After` longer than `max_delay`.
+    pub fn next_delay(
+        &self,
+        error: &FetchError,
+        attempt: u32,
+        method: HttpMethod,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.is_retryable(error, method) {
+            return None;
+        }
+
+        if let FetchError::ServerError {
+            status: 429 | 503,
+            retry_after: Some(retry_after),
+            ..
+        } = error
+            && self.respect_retry_after
+        {
+            // Waiting longer than we would ever back off is not worth it
+            return (*retry_after <= self.max_delay).then_some(*retry_after);
+        }
+
+        Some(self.backoff(attempt))
+    }
+}
+
+/// Run `operation` until it succeeds or the policy gives up.
+///
+/// On success returns the value together with the number of attempts made.
+pub async fn retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    method: HttpMethod,
+    mut operation: F,
+) -> Result<(T, u32), FetchError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation(attempt).await {
+            Ok(value) => return Ok((value, attempt)),
+            Err(error) => match policy.next_delay(&error, attempt, method) {
+                Some(delay) => {
+                    tracing::warn!(
+                        "Fetch attempt {}/{} failed ({}), retrying in {:?}",
+                        attempt,
+                        policy.max_attempts,
+                        error,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Err(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(status: u16, retry_after: Option<Duration>) -> FetchError {
+        FetchError::ServerError {
+            status,
+            message: format!("HTTP {}", status),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_next_delay_respects_kinds_statuses_and_retry_after() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let get = HttpMethod::Get;
+
+        assert!(
+            policy
+                .next_delay(&FetchError::Timeout("t".into()), 1, get)
+                .is_some()
+        );
+        assert!(
+            policy
+                .next_delay(&FetchError::InvalidUrl("u".into()), 1, get)
+                .is_none()
+        );
+        assert!(
+            policy
+                .next_delay(&server_error(404, None), 1, get)
+                .is_none()
+        );
+        assert!(
+            policy
+                .next_delay(&server_error(502, None), 3, get)
+                .is_none()
+        );
+        assert!(
+            policy
+                .next_delay(&server_error(502, None), 1, HttpMethod::Post)
+                .is_none()
+        );
+
+        let retry_after = Some(Duration::from_secs(2));
+        assert_eq!(
+            policy.next_delay(&server_error(429, retry_after), 1, get),
+            retry_after
+        );
+        assert!(
+            policy
+                .next_delay(&server_error(503, Some(Duration::from_secs(60))), 1, get)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,656 @@
+//! robots.txt compliance.
+//!
+//! robots.txt is fetched once per origin and cached. Every fetch is checked
+//! against the group matching the configured user agent; `Crawl-delay` is
+//! handed to the politeness scheduler. Parsing follows RFC 9309: the most
+//! specific (longest) matching rule wins, and `Allow` wins ties.
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::infra::http::StreamingClient;
+
+use super::config::FetchConfig;
+use super::error::FetchError;
+
+/// robots.txt configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobotsConfig {
+    /// Whether robots.txt is checked at all
+    pub enabled: bool,
+    /// How long a fetched robots.txt is cached
+    pub cache_ttl: Duration,
+    /// How long an unreachable robots.txt (treated as disallow-all) is cached
+    pub error_ttl: Duration,
+    /// Maximum robots.txt size in bytes
+    pub max_size: usize,
+    /// Maximum number of origins kept in the cache
+    pub cache_capacity: usize,
+    /// Hosts we have permission to crawl (subdomains included)
+    pub exempt_hosts: Vec<String>,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cache_ttl: Duration::from_secs(24 * 60 * 60),
+            error_ttl: Duration::from_secs(10 * 60),
+            max_size: 500 * 1024,
+            cache_capacity: 1024,
+            exempt_hosts: Vec::new(),
+        }
+    }
+}
+
+impl RobotsConfig {
+    /// Whether `host` is exempt from robots.txt checks.
+    pub fn is_exempt(&self, host: &str) -> bool {
+        self.exempt_hosts.iter().any(|exempt| {
+            host.eq_ignore_ascii_case(exempt)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", exempt.to_ascii_lowercase()))
+        })
+    }
+}
+
+/// A single Allow/Disallow rule.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// Rules for a set of user agents.
+#[derive(Debug, Clone, Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+/// Parsed robots.txt file.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+    sitemaps: Vec<String>,
+}
+
+impl RobotsTxt {
+    /// Parse a robots.txt document. Unknown and malformed lines are ignored.
+    pub fn parse(content: &str) -> Self {
+        let mut robots = RobotsTxt::default();
+        let mut current: Option<Group> = None;
+        // Consecutive User-agent lines share one group
+        let mut collecting_agents = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !collecting_agents {
+                        robots.groups.extend(current.take());
+                    }
+                    current
+                        .get_or_insert_with(Group::default)
+                        .agents
+                        .push(value.to_ascii_lowercase());
+                    collecting_agents = true;
+                }
+                "allow" | "disallow" => {
+                    collecting_agents = false;
+                    // An empty Disallow means "allow everything" and adds no rule
+                    if let Some(group) = current.as_mut()
+                        && !value.is_empty()
+                    {
+                        group.rules.push(Rule {
+                            allow: key.trim().eq_ignore_ascii_case("allow"),
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    collecting_agents = false;
+                    if let Some(group) = current.as_mut()
+                        && let Ok(seconds) = value.parse::<f64>()
+                        && seconds.is_finite()
+                        && seconds >= 0.0
+                    {
+                        // Delays too long for a Duration saturate; the
+                        // scheduler caps them at its `max_delay`
+                        group.crawl_delay =
+                            Some(Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX));
+                    }
+                }
+                "sitemap" if !value.is_empty() => {
+                    robots.sitemaps.push(value.to_string());
+                }
+                _ => {}
+            }
+        }
+        robots.groups.extend(current);
+        robots
+    }
+
+    /// A robots.txt that allows everything (used for 4xx responses).
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// A robots.txt that disallows everything (used when it is unreachable).
+    pub fn disallow_all() -> Self {
+        Self {
+            groups: vec![Group {
+                agents: vec!["*".to_string()],
+                rules: vec![Rule {
+                    allow: false,
+                    pattern: "/".to_string(),
+                }],
+                crawl_delay: None,
+            }],
+            sitemaps: Vec::new(),
+        }
+    }
+
+    /// Whether `user_agent` may fetch `path` (path plus optional query).
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        let mut best: Option<&Rule> = None;
+        for rule in self.matching_groups(user_agent).flat_map(|g| &g.rules) {
+            if !pattern_matches(&rule.pattern, path) {
+                continue;
+            }
+            best = match best {
+                Some(current)
+                    if current.pattern.len() > rule.pattern.len()
+                        || (current.pattern.len() == rule.pattern.len() && current.allow) =>
+                {
+                    Some(current)
+                }
+                _ => Some(rule),
+            };
+        }
+        best.is_none_or(|rule| rule.allow)
+    }
+
+    /// Crawl delay requested for `user_agent`, if any.
+    pub fn crawl_delay(&self, user_agent: &str) -> Option<Duration> {
+        self.matching_groups(user_agent)
+            .filter_map(|g| g.crawl_delay)
+            .max()
+    }
+
+    /// Sitemap URLs listed in the file.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+
+    /// Groups that apply to `user_agent`.
+    ///
+    /// The groups naming the longest matching product token win; groups for
+    /// `*` apply only when no specific group matches.
+    fn matching_groups(&self, user_agent: &str) -> impl Iterator<Item = &Group> {
+        let token = product_token(user_agent);
+        let best = self
+            .groups
+            .iter()
+            .filter_map(|group| group.specificity(&token))
+            .max();
+        self.groups.iter().filter(move |group| match best {
+            Some(len) => group.specificity(&token) == Some(len),
+            None => group.agents.iter().any(|agent| agent == "*"),
+        })
+    }
+}
+
+impl Group {
+    /// Length of the longest agent in this group matching `token`.
+    fn specificity(&self, token: &str) -> Option<usize> {
+        self.agents
+            .iter()
+            .filter(|agent| agent.as_str() != "*" && token.starts_with(agent.as_str()))
+            .map(|agent| agent.len())
+            .max()
+    }
+}
+
+/// Product token of a user agent string ("SCAPI/1.0 (+url)" -> "scapi").
+pub fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Match a robots.txt path pattern supporting `*` and a trailing `$`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return !anchored || rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    if anchored {
+        rest.ends_with(last)
+    } else {
+        rest.contains(last)
+    }
+}
+
+/// Cached robots.txt entry.
+struct CachedRobots {
+    robots: Arc<RobotsTxt>,
+    expires_at: Instant,
+}
+
+/// Per-origin robots.txt cache and checker.
+pub struct RobotsCache {
+    config: RobotsConfig,
+    entries: Mutex<LruCache<String, CachedRobots>>,
+}
+
+impl std::fmt::Debug for RobotsCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RobotsCache")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RobotsCache {
+    /// Create a cache with the given configuration.
+    pub fn new(config: RobotsConfig) -> Self {
+        let capacity = NonZeroUsize::new(config.cache_capacity.max(1)).unwrap();
+        Self {
+            config,
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Get the robots.txt configuration.
+    pub fn config(&self) -> &RobotsConfig {
+        &self.config
+    }
+
+    /// Check whether `url` may be fetched with `config`.
+    ///
+    /// Returns the crawl delay requested for our user agent, or
+    /// `FetchError::RobotsDisallowed` when robots.txt forbids the URL.
+    pub async fn check(
+        &self,
+        client: &StreamingClient,
+        url: &str,
+        config: &FetchConfig,
+    ) -> Result<Option<Duration>, FetchError> {
+        if !self.config.enabled || !config.respect_robots {
+            return Ok(None);
+        }
+
+        let parsed = url::Url::parse(url).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+        let Some(host) = parsed.host_str() else {
+            return Ok(None);
+        };
+        if !matches!(parsed.scheme(), "http" | "https") || self.config.is_exempt(host) {
+            return Ok(None);
+        }
+
+        let robots = self
+            .load(client, &parsed.origin().ascii_serialization(), config)
+            .await;
+
+        let mut path = parsed.path().to_string();
+        if let Some(query) = parsed.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        if !robots.is_allowed(&config.user_agent, &path) {
+            return Err(FetchError::RobotsDisallowed(url.to_string()));
+        }
+        Ok(robots.crawl_delay(&config.user_agent))
+    }
+
+    /// robots.txt for `origin`, from the cache or downloaded with `config`.
+    ///
+    /// Unlike `check`, this ignores whether robots.txt handling is enabled,
+    /// so it can be used to read `Sitemap:` lines.
+    pub async fn load(
+        &self,
+        client: &StreamingClient,
+        origin: &str,
+        config: &FetchConfig,
+    ) -> Arc<RobotsTxt> {
+        match self.get(origin) {
+            Some(robots) => robots,
+            None => self.fetch(client, origin, config).await,
+        }
+    }
+
+    /// Cached robots.txt for `origin`, if present and fresh.
+    pub fn get(&self, origin: &str) -> Option<Arc<RobotsTxt>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(origin) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.robots.clone()),
+            Some(_) => {
+                entries.pop(origin);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store robots.txt for `origin` for `ttl`.
+    pub fn insert(&self, origin: &str, robots: Arc<RobotsTxt>, ttl: Duration) {
+        self.entries.lock().unwrap().put(
+            origin.to_string(),
+            CachedRobots {
+                robots,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// Download and cache robots.txt for `origin`.
+    ///
+    /// Boxed with a named type because the client checks redirect targets
+    /// against robots.txt, so this future is part of the client's own.
+    fn fetch<'a>(
+        &'a self,
+        client: &'a StreamingClient,
+        origin: &'a str,
+        config: &'a FetchConfig,
+    ) -> Pin<Box<dyn Future<Output = Arc<RobotsTxt>> + Send + 'a>> {
+        Box::pin(async move {
+            let robots_url = format!("{}/robots.txt", origin);
+            let robots_config = FetchConfig {
+                timeout: config.timeout,
+                connect_timeout: config.connect_timeout,
+                read_timeout: config.read_timeout,
+                user_agent: config.user_agent.clone(),
+                verify_tls: config.verify_tls,
+                // Go out the way the page will, so origins reachable only
+                // through the proxy are not cached as unavailable
+                proxy: config.proxy.clone(),
+                max_content_size: self.config.max_size,
+                // Redirects of robots.txt itself are not checked against robots.txt
+                respect_robots: false,
+                ..FetchConfig::default()
+            };
+
+            let (robots, ttl) = match client
+                .fetch_to_string(&robots_url, &robots_config, self.config.max_size)
+                .await
+            {
+                Ok((content, _)) => (RobotsTxt::parse(&content), self.config.cache_ttl),
+                // A missing robots.txt (any 4xx except 429) means no restrictions
+                Err(FetchError::ServerError { status, .. })
+                    if (400..500).contains(&status) && status != 429 =>
+                {
+                    (RobotsTxt::allow_all(), self.config.cache_ttl)
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "robots.txt unavailable at {} ({}), disallowing origin for {:?}",
+                        robots_url,
+                        e,
+                        self.config.error_ttl
+                    );
+                    (RobotsTxt::disallow_all(), self.config.error_ttl)
+                }
+            };
+
+            let robots = Arc::new(robots);
+            self.insert(origin, robots.clone(), ttl);
+            robots
+        })
+    }
+}
+
+impl Default for RobotsCache {
+    fn default() -> Self {
+        Self::new(RobotsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+# Example robots.txt
+User-agent: *
+Disallow: /private/
+Allow: /private/public$
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: scapi
+User-agent: otherbot
+Disallow: /search
+Allow: /search/about
+Crawl-delay: 0.5
+
+Sitemap: https://example.com/sitemap.xml
+";
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(pattern_matches("/private/", "/private/page"));
+        assert!(!pattern_matches("/private/", "/priv"));
+        assert!(pattern_matches("/*.pdf$", "/docs/file.pdf"));
+        assert!(!pattern_matches("/*.pdf$", "/docs/file.pdf?x=1"));
+        assert!(pattern_matches("/a*b*c", "/a-b-c-d"));
+        assert!(pattern_matches("/exact$", "/exact"));
+        assert!(!pattern_matches("/exact$", "/exact/more"));
+    }
+
+    #[test]
+    fn test_groups_rules_and_directives() {
+        let robots = RobotsTxt::parse(ROBOTS);
+
+        // Generic group
+        let ua = "Mozilla/5.0";
+        assert!(!robots.is_allowed(ua, "/private/secret"));
+        assert!(robots.is_allowed(ua, "/private/public"));
+        assert!(!robots.is_allowed(ua, "/private/public/x"));
+        assert!(!robots.is_allowed(ua, "/files/report.pdf"));
+        assert!(robots.is_allowed(ua, "/search"));
+        assert_eq!(robots.crawl_delay(ua), Some(Duration::from_secs(2)));
+
+        // Specific group replaces the generic one
+        let ua = "SCAPI/1.0";
+        assert!(robots.is_allowed(ua, "/private/secret"));
+        assert!(!robots.is_allowed(ua, "/search?q=rust"));
+        assert!(robots.is_allowed(ua, "/search/about"));
+        assert!(robots.is_allowed(ua, "/robots.txt"));
+        assert_eq!(robots.crawl_delay(ua), Some(Duration::from_millis(500)));
+
+        let hostile = RobotsTxt::parse("User-agent: *\nCrawl-delay: 1e30\n");
+        assert_eq!(hostile.crawl_delay(ua), Some(Duration::MAX));
+
+        assert_eq!(robots.sitemaps(), ["https://example.com/sitemap.xml"]);
+        assert!(!RobotsTxt::disallow_all().is_allowed(ua, "/"));
+        assert!(RobotsTxt::allow_all().is_allowed(ua, "/anything"));
+    }
+
+    #[tokio::test]
+    async fn test_check_uses_cache_and_exemptions() {
+        let cache = RobotsCache::new(RobotsConfig {
+            exempt_hosts: vec!["partner.example".to_string()],
+            ..RobotsConfig::default()
+        });
+        cache.insert(
+            "https://example.com",
+            Arc::new(RobotsTxt::parse(ROBOTS)),
+            Duration::from_secs(60),
+        );
+        let client = StreamingClient::new().unwrap();
+        let config = FetchConfig::default();
+
+        assert_eq!(
+            cache
+                .check(&client, "https://example.com/page", &config)
+                .await
+                .unwrap(),
+            Some(Duration::from_millis(500))
+        );
+        assert!(matches!(
+            cache
+                .check(&client, "https://example.com/search?q=x", &config)
+                .await,
+            Err(FetchError::RobotsDisallowed(_))
+        ));
+
+        // Exempt hosts and opted-out requests never hit the network
+        assert!(
+            cache
+                .check(&client, "https://www.partner.example/search", &config)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let opted_out = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+        assert!(
+            cache
+                .check(&client, "https://example.com/search", &opted_out)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt_goes_through_the_proxy() {
+        use crate::domain::fetch::proxy::ProxyConfig;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A forward proxy sees the absolute URL; axum routes on its path
+        let proxied = Arc::new(AtomicUsize::new(0));
+        let counter = proxied.clone();
+        let proxy = axum::Router::new().route(
+            "/robots.txt",
+            axum::routing::get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "User-agent: *\nDisallow: /private\n"
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, proxy).await });
+
+        let cache = RobotsCache::default();
+        let client = StreamingClient::new().unwrap();
+        let config = FetchConfig {
+            proxy: Some(ProxyConfig::new(&proxy_url)),
+            ..FetchConfig::default()
+        };
+        assert!(
+            cache
+                .check(&client, "http://only-via-proxy.invalid/public", &config)
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            cache
+                .check(&client, "http://only-via-proxy.invalid/private/x", &config)
+                .await,
+            Err(FetchError::RobotsDisallowed(_))
+        ));
+        assert_eq!(proxied.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_targets_are_checked_against_their_robots_txt() {
+        use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+        use crate::infra::http::HttpClient;
+        use axum::response::Redirect;
+
+        let target = axum::Router::new()
+            .route(
+                "/robots.txt",
+                axum::routing::get(|| async { "User-agent: *\nDisallow: /private\n" }),
+            )
+            .route("/public", axum::routing::get(|| async { "public" }))
+            .route("/private", axum::routing::get(|| async { "private" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target_url = format!("http://localhost:{}", listener.local_addr().unwrap().port());
+        tokio::spawn(async move { axum::serve(listener, target).await });
+
+        // The origin itself has no robots.txt, so it allows everything
+        let (public, private) = (
+            format!("{}/public", target_url),
+            format!("{}/private", target_url),
+        );
+        let origin = axum::Router::new()
+            .route(
+                "/public",
+                axum::routing::get(move || async move { Redirect::temporary(&public) }),
+            )
+            .route(
+                "/private",
+                axum::routing::get(move || async move { Redirect::temporary(&private) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, origin).await });
+
+        let service = DefaultFetchService::new(HttpClient::new().unwrap());
+        let config = FetchConfig::default();
+        let result = service
+            .fetch(&format!("{}/public", url), &config)
+            .await
+            .unwrap();
+        assert_eq!(result.content, "public");
+
+        let err = service
+            .fetch(&format!("{}/private", url), &config)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, FetchError::RobotsDisallowed(target) if target.starts_with("http://localhost"))
+        );
+
+        // Unless the request opts out of robots.txt
+        let opted_out = FetchConfig {
+            respect_robots: false,
+            ..FetchConfig::default()
+        };
+        let result = service
+            .fetch(&format!("{}/private", url), &opted_out)
+            .await
+            .unwrap();
+        assert_eq!(result.content, "private");
+    }
+}
@@ -1,16 +1,35 @@
 //! Fetch service implementation.
 
 use crate::common::metrics::Timer;
+use crate::common::types::header;
 
+use crate::infra::encoding::detector::{self, charset_from_content_type};
 use crate::infra::http::HttpClient;
-use crate::infra::http::streaming::ResponseStream;
+use crate::infra::http::cache::CacheStatus;
+use crate::infra::http::resolver::Resolution;
+use crate::infra::http::streaming::{ResponseStream, is_textual, range_config};
 
-use super::config::FetchConfig;
+use super::auth::CredentialStore;
+use super::breaker::{BreakerConfig, CircuitBreakers};
+use super::coalesce::{CoalesceConfig, CoalesceKey, CoalesceStats, Coalescer};
+use super::config::{FetchConfig, HttpMethod};
 use super::error::FetchError;
+use super::politeness::{HostScheduler, PolitenessConfig};
+use super::probe::{self, ProbeMethod, ProbeResult};
+use super::proxy::{ProxyChoice, ProxyPool, ProxySettings};
+use super::range::ByteRange;
+use super::redirect::{
+    self, HTML_REDIRECT_SCAN_BYTES, HtmlRedirect, HtmlRedirectConfig, RedirectHop, is_html,
+};
+use super::retry::retry;
+use super::robots::{RobotsCache, RobotsConfig};
 use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
 
 /// Result of a fetch operation.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FetchResult {
     /// HTML content
     pub content: String,
@@ -22,6 +41,20 @@ pub struct FetchResult {
     pub final_url: String,
     /// Timestamp when the fetch completed
     pub timestamp: DateTime<Utc>,
+    /// Charset the content was decoded from
+    pub encoding: Option<String>,
+    /// Number of attempts made (1 when the first try succeeded)
+    pub attempts: u32,
+    /// Cache outcome (`None` when no cache is configured)
+    pub cache_status: Option<CacheStatus>,
+    /// Proxy the successful attempt went through (credentials masked)
+    pub proxy: Option<String>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Headers of the final response (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// How the final host was resolved
+    pub dns: Option<Resolution>,
 }
 
 /// Result of a streaming fetch operation.
@@ -38,6 +71,22 @@ pub struct StreamingFetchResult {
     pub timestamp: DateTime<Utc>,
     /// Content length if known
     pub content_length: Option<u64>,
+    /// Content type if known
+    pub content_type: Option<String>,
+    /// Detected charset for textual responses
+    pub encoding: Option<String>,
+    /// Number of attempts made before headers were received
+    pub attempts: u32,
+    /// Cache outcome (`None` when no cache is configured)
+    pub cache_status: Option<CacheStatus>,
+    /// Proxy the successful attempt went through (credentials masked)
+    pub proxy: Option<String>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Headers of the final response (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// How the final host was resolved
+    pub dns: Option<Resolution>,
 }
 
 impl std::fmt::Debug for StreamingFetchResult {
@@ -47,6 +96,14 @@ impl std::fmt::Debug for StreamingFetchResult {
             .field("final_url", &self.final_url)
             .field("timestamp", &self.timestamp)
             .field("content_length", &self.content_length)
+            .field("content_type", &self.content_type)
+            .field("encoding", &self.encoding)
+            .field("attempts", &self.attempts)
+            .field("cache_status", &self.cache_status)
+            .field("proxy", &self.proxy)
+            .field("redirects", &self.redirects)
+            .field("headers", &self.headers)
+            .field("dns", &self.dns)
             .finish()
     }
 }
@@ -72,69 +129,365 @@ pub trait FetchService: Send + Sync {
 pub struct DefaultFetchService {
     /// HTTP client
     pub client: HttpClient,
+    /// Per-host politeness scheduler
+    pub scheduler: Arc<HostScheduler>,
+    /// robots.txt cache and checker
+    pub robots: Arc<RobotsCache>,
+    /// Proxy selection and pool health
+    pub proxies: Arc<ProxyPool>,
+    /// Per-host credentials attached to matching requests
+    pub credentials: Arc<CredentialStore>,
+    /// Identical fetches in flight (`None` when coalescing is disabled)
+    pub coalescer: Option<Arc<Coalescer>>,
+    /// Per-host circuit breakers
+    pub breakers: Arc<CircuitBreakers>,
 }
 
 impl DefaultFetchService {
     /// Create a new fetch service with the given HTTP client.
     pub fn new(client: HttpClient) -> Self {
-        Self { client }
+        let breakers = Arc::new(CircuitBreakers::default());
+        let robots = Arc::new(RobotsCache::default());
+        Self {
+            client: client
+                .with_breakers(breakers.clone())
+                .with_robots(robots.clone()),
+            scheduler: Arc::new(HostScheduler::default()),
+            robots,
+            proxies: Arc::new(ProxyPool::default()),
+            credentials: Arc::new(CredentialStore::default()),
+            coalescer: Some(Arc::new(Coalescer::default())),
+            breakers,
+        }
+    }
+
+    /// Use custom per-host politeness limits.
+    pub fn with_politeness(mut self, politeness: PolitenessConfig) -> Self {
+        self.scheduler = Arc::new(HostScheduler::new(politeness));
+        self
+    }
+
+    /// Use a custom robots.txt configuration.
+    ///
+    /// The client checks robots.txt for every redirect target as well, so a
+    /// redirect cannot lead to a path the target's robots.txt disallows.
+    pub fn with_robots(mut self, robots: RobotsConfig) -> Self {
+        self.robots = Arc::new(RobotsCache::new(robots));
+        self.client = self.client.with_robots(self.robots.clone());
+        self
+    }
+
+    /// Use global, per-host and pooled proxies.
+    pub fn with_proxies(mut self, proxies: ProxySettings) -> Self {
+        self.proxies = Arc::new(ProxyPool::new(proxies));
+        self
+    }
+
+    /// Authenticate requests to hosts matching the store's patterns.
+    pub fn with_credentials(mut self, credentials: CredentialStore) -> Self {
+        self.credentials = Arc::new(credentials);
+        self
+    }
+
+    /// Enable or disable coalescing of identical concurrent fetches.
+    pub fn with_coalescing(mut self, coalesce: CoalesceConfig) -> Self {
+        self.coalescer = coalesce.enabled.then(|| Arc::new(Coalescer::default()));
+        self
+    }
+
+    /// Use custom circuit breaker thresholds.
+    ///
+    /// The client checks the breakers on every request, redirect hops
+    /// included, so each hop counts against its own host.
+    pub fn with_circuit_breaker(mut self, breaker: BreakerConfig) -> Self {
+        self.breakers = Arc::new(CircuitBreakers::new(breaker));
+        self.client = self.client.with_breakers(self.breakers.clone());
+        self
+    }
+
+    /// Coalescing counters (all zero when coalescing is disabled).
+    pub fn coalesce_stats(&self) -> CoalesceStats {
+        self.coalescer
+            .as_ref()
+            .map(|coalescer| coalescer.stats())
+            .unwrap_or_default()
+    }
+
+    /// Config for `url` with the stored credentials of its host attached.
+    ///
+    /// Credentials already set on the config are kept. Stored credentials are
+    /// never sent through a proxy chosen by the caller, which could read them
+    /// from plain `http://` requests.
+    fn authenticate(
+        credentials: &CredentialStore,
+        url: &str,
+        config: &FetchConfig,
+    ) -> Result<FetchConfig, FetchError> {
+        let mut config = config.clone();
+        if config.auth.is_none()
+            && let Some(auth) = credentials.find(url)
+        {
+            if let Some(proxy) = &config.proxy {
+                return Err(FetchError::InvalidRequest(format!(
+                    "{} has stored credentials and cannot be fetched through proxy {}",
+                    url,
+                    proxy.display_url()
+                )));
+            }
+            tracing::debug!("Using stored {} credentials for {}", auth.scheme(), url);
+            config.auth = Some(auth.clone());
+        }
+        Ok(config)
+    }
+
+    /// Pick the proxy for `url`, returning it with the config to fetch with.
+    ///
+    /// The config is only cloned when a proxy has to be applied.
+    fn route(
+        proxies: &ProxyPool,
+        url: &str,
+        config: &FetchConfig,
+    ) -> (Option<ProxyChoice>, Option<FetchConfig>) {
+        let choice = proxies.select(url, config);
+        let routed = choice.as_ref().map(|choice| FetchConfig {
+            proxy: Some(choice.proxy.clone()),
+            ..config.clone()
+        });
+        (choice, routed)
+    }
+
+    /// HTML-level redirect of the page at `url`, unless `hops` reached the limit.
+    fn html_redirect(
+        &self,
+        url: &str,
+        html: &[u8],
+        follow: &HtmlRedirectConfig,
+        hops: usize,
+    ) -> Option<HtmlRedirect> {
+        let redirect = redirect::find_html_redirect(html, url, follow)?;
+        if hops >= follow.max_hops {
+            tracing::debug!(
+                "Not following {:?} redirect from {} to {}: {} HTML redirects followed",
+                redirect.kind,
+                url,
+                redirect.target,
+                hops
+            );
+            return None;
+        }
+        tracing::debug!(
+            "Following {:?} redirect from {} to {}",
+            redirect.kind,
+            url,
+            redirect.target
+        );
+        Some(redirect)
+    }
+
+    /// Check robots.txt for `url` and apply any crawl delay it requests.
+    async fn check_robots(
+        client: &HttpClient,
+        robots: &RobotsCache,
+        scheduler: &HostScheduler,
+        proxies: &ProxyPool,
+        url: &str,
+        config: &FetchConfig,
+    ) -> Result<(), FetchError> {
+        let (_, routed) = Self::route(proxies, url, config);
+        let config = routed.as_ref().unwrap_or(config);
+        if let Some(crawl_delay) = robots.check(client.streaming(), url, config).await? {
+            scheduler.apply_crawl_delay(url, crawl_delay);
+        }
+        Ok(())
     }
 }
 
-impl FetchService for DefaultFetchService {
-    fn fetch(
+impl DefaultFetchService {
+    /// Fetch one page, sharing the response of an identical fetch in flight.
+    fn fetch_page(
         &self,
         url: &str,
         config: &FetchConfig,
-    ) -> impl std::future::Future<Output = Result<FetchResult, FetchError>> + Send {
+    ) -> impl std::future::Future<Output = Result<FetchResult, FetchError>> + Send + 'static {
+        let flight = self.coalescer.clone().zip(CoalesceKey::new(url, config));
+        let upstream = self.fetch_upstream(url, config);
+        async move {
+            match flight {
+                Some((coalescer, key)) => coalescer.fetch(key, upstream).await,
+                None => upstream.await,
+            }
+        }
+    }
+
+    /// Stream one page, sharing the body of an identical stream in flight.
+    fn stream_page(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+    ) -> impl std::future::Future<Output = Result<StreamingFetchResult, FetchError>> + Send + 'static
+    {
+        let flight = self.coalescer.clone().zip(CoalesceKey::new(url, config));
+        let upstream = self.stream_upstream(url, config);
+        let max_size = config.max_content_size;
+        async move {
+            match flight {
+                Some((coalescer, key)) => coalescer.stream(key, upstream, max_size).await,
+                None => upstream.await,
+            }
+        }
+    }
+
+    /// Fetch one page, without following HTML-level redirects.
+    fn fetch_upstream(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+    ) -> impl std::future::Future<Output = Result<FetchResult, FetchError>> + Send + 'static {
         let client = self.client.clone();
+        let scheduler = self.scheduler.clone();
+        let robots = self.robots.clone();
+        let proxies = self.proxies.clone();
+        let breakers = self.breakers.clone();
         let url = url.to_string();
-        let config = config.clone();
+        let config = Self::authenticate(&self.credentials, &url, config);
 
         async move {
+            let config = config?;
             // Start timing the operation
             let timer = Timer::start("fetch");
 
-            // Perform fetch operation
-            let content = client.fetch(&url, &config).await?;
+            // Local sources skip robots.txt, host slots and proxies
+            let local = client.streaming().is_local(&url);
+            if !local {
+                breakers.check(&url)?;
+                Self::check_robots(&client, &robots, &scheduler, &proxies, &url, &config).await?;
+            }
+
+            // Perform fetch operation; retries cover the whole body read, and
+            // every attempt waits for its per-host slot and picks a proxy
+            let ((content, metadata, proxy), attempts) =
+                retry(&config.retry, config.method, |_| async {
+                    let permit = if local {
+                        None
+                    } else {
+                        scheduler.acquire(&url).await
+                    };
+                    let (choice, routed) = if local {
+                        (None, None)
+                    } else {
+                        Self::route(&proxies, &url, &config)
+                    };
+                    let result = client
+                        .streaming()
+                        .fetch_to_string(
+                            &url,
+                            routed.as_ref().unwrap_or(&config),
+                            config.max_content_size,
+                        )
+                        .await;
+                    if let Some(permit) = &permit {
+                        permit.record(match &result {
+                            Ok((_, metadata)) => Some(metadata.status_code),
+                            Err(e) => e.status_code(),
+                        });
+                    }
+                    if let Some(choice) = &choice {
+                        proxies.report(choice, result.as_ref().err());
+                    }
+                    let proxy = choice.map(|choice| choice.proxy.display_url());
+                    result.map(|(content, metadata)| (content, metadata, proxy))
+                })
+                .await?;
 
             // Build result
             let result = FetchResult {
-                content: content.clone(),
                 length: content.len(),
-                // TODO: Get actual status code and final URL from client response if we modify client to return full response
-                // For now, assuming success since client.fetch returns content string on success
-                status_code: 200,
-                final_url: url,
+                content,
+                status_code: metadata.status_code,
+                final_url: metadata.final_url,
                 timestamp: Utc::now(),
+                encoding: metadata.encoding,
+                attempts,
+                cache_status: metadata.cache_status,
+                proxy,
+                redirects: metadata.redirects,
+                headers: metadata.headers,
+                dns: metadata.dns,
             };
 
             // Log completion
             tracing::info!(
-                "Fetched {} bytes from {} in {}ms",
+                "Fetched {} bytes from {} in {}ms ({} attempt(s))",
                 result.length,
                 result.final_url,
-                timer.finish_ms()
+                timer.finish_ms(),
+                result.attempts
             );
 
             Ok(result)
         }
     }
 
-    fn fetch_stream(
+    /// Stream one page, without following HTML-level redirects.
+    fn stream_upstream(
         &self,
         url: &str,
         config: &FetchConfig,
-    ) -> impl std::future::Future<Output = Result<StreamingFetchResult, FetchError>> + Send {
+    ) -> impl std::future::Future<Output = Result<StreamingFetchResult, FetchError>> + Send + 'static
+    {
         let client = self.client.clone();
+        let scheduler = self.scheduler.clone();
+        let robots = self.robots.clone();
+        let proxies = self.proxies.clone();
+        let breakers = self.breakers.clone();
         let url = url.to_string();
-        let config = config.clone();
+        let config = Self::authenticate(&self.credentials, &url, config);
 
         async move {
+            let config = config?;
             let timer = Timer::start("fetch_stream");
 
-            // Perform streaming fetch
-            let result = client.streaming().fetch_stream(&url, &config).await?;
+            let local = client.streaming().is_local(&url);
+            if !local {
+                breakers.check(&url)?;
+                Self::check_robots(&client, &robots, &scheduler, &proxies, &url, &config).await?;
+            }
+
+            // Perform streaming fetch; only failures before the body starts are
+            // retried. The host permit is held until the stream is dropped.
+            let ((result, proxy), attempts) = retry(&config.retry, config.method, |_| async {
+                let permit = if local {
+                    None
+                } else {
+                    scheduler.acquire(&url).await
+                };
+                let (choice, routed) = if local {
+                    (None, None)
+                } else {
+                    Self::route(&proxies, &url, &config)
+                };
+                let result = client
+                    .streaming()
+                    .fetch_stream(&url, routed.as_ref().unwrap_or(&config))
+                    .await;
+                if let Some(choice) = &choice {
+                    proxies.report(choice, result.as_ref().err());
+                }
+                let proxy = choice.map(|choice| choice.proxy.display_url());
+
+                let Some(permit) = permit else {
+                    return result.map(|result| (result, proxy));
+                };
+                permit.record(match &result {
+                    Ok(result) => Some(result.status_code),
+                    Err(e) => e.status_code(),
+                });
+                result.map(|mut result| {
+                    result.stream = result.stream.with_guard(permit);
+                    (result, proxy)
+                })
+            })
+            .await?;
 
             let domain_result = StreamingFetchResult {
                 stream: result.stream,
@@ -142,16 +495,210 @@ impl FetchService for DefaultFetchService {
                 final_url: result.final_url.clone(),
                 timestamp: Utc::now(),
                 content_length: result.content_length,
+                content_type: result.content_type,
+                encoding: result.encoding,
+                attempts,
+                cache_status: result.cache_status,
+                proxy,
+                redirects: result.redirects,
+                headers: result.headers,
+                dns: result.dns,
             };
 
             tracing::info!(
-                "Started streaming from {} (status {}) in {}ms",
+                "Started streaming from {} (status {}) in {}ms ({} attempt(s))",
                 domain_result.final_url,
                 domain_result.status_code,
-                timer.finish_ms()
+                timer.finish_ms(),
+                domain_result.attempts
             );
 
             Ok(domain_result)
         }
     }
+
+    /// Inspect `url` without downloading its body.
+    ///
+    /// Sends `HEAD`, falling back to a `GET` for the first `PROBE_BYTES`
+    /// bytes when the server rejects it. Error statuses are part of the
+    /// result; only failures to get a response at all are errors.
+    pub async fn probe(&self, url: &str, config: &FetchConfig) -> Result<ProbeResult, FetchError> {
+        let started = Instant::now();
+        let head_config = probe::probe_config(config);
+        let head = self.stream_page(url, &head_config).await?;
+        if !probe::head_rejected(head.status_code) {
+            let charset = head
+                .content_type
+                .as_deref()
+                .and_then(charset_from_content_type)
+                .map(|encoding| encoding.name().to_string());
+            return Ok(probe_result(
+                head,
+                ProbeMethod::Head,
+                None,
+                charset,
+                started,
+            ));
+        }
+
+        tracing::debug!(
+            "HEAD {} rejected with {}, probing with a ranged GET",
+            url,
+            head.status_code
+        );
+        let StreamingFetchResult {
+            status_code: head_status,
+            attempts: head_attempts,
+            ..
+        } = head;
+        let range = ByteRange::new(0, probe::PROBE_BYTES - 1)?;
+        let get_config = range_config(
+            &FetchConfig {
+                method: HttpMethod::Get,
+                ..head_config
+            },
+            range,
+            None,
+        );
+        let mut result = self.stream_page(url, &get_config).await?;
+        // Servers ignoring the range send everything: only the start is read
+        let prefix = result.stream.peek(probe::PROBE_BYTES as usize).await?;
+        let charset = is_textual(result.content_type.as_deref())
+            .then(|| detector::detect(result.content_type.as_deref(), &prefix))
+            .map(|detected| detected.name().to_string());
+        result.attempts += head_attempts;
+        Ok(probe_result(
+            result,
+            ProbeMethod::RangedGet,
+            Some(head_status),
+            charset,
+            started,
+        ))
+    }
+}
+
+/// Probe result from the final response of a probe.
+fn probe_result(
+    result: StreamingFetchResult,
+    method: ProbeMethod,
+    head_status: Option<u16>,
+    charset: Option<String>,
+    started: Instant,
+) -> ProbeResult {
+    ProbeResult {
+        content_length: probe::content_length(result.status_code, &result.headers),
+        server_timing: probe::server_timing(&result.headers),
+        status_code: result.status_code,
+        final_url: result.final_url,
+        method,
+        head_status,
+        content_type: result.content_type,
+        charset,
+        redirects: result.redirects,
+        headers: result.headers,
+        dns: result.dns,
+        attempts: result.attempts,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+impl FetchService for DefaultFetchService {
+    async fn fetch(&self, url: &str, config: &FetchConfig) -> Result<FetchResult, FetchError> {
+        let mut result = self.fetch_page(url, config).await?;
+        let Some(follow) = &config.html_redirects else {
+            return Ok(result);
+        };
+
+        let mut visited = HashSet::from([result.final_url.clone()]);
+        let mut hops = 0;
+        while let Some(redirect) = is_html(header(&result.headers, "content-type"))
+            .then(|| self.html_redirect(&result.final_url, result.content.as_bytes(), follow, hops))
+            .flatten()
+            .filter(|redirect| visited.insert(redirect.target.to_string()))
+        {
+            let started = Instant::now();
+            let from = url::Url::parse(&result.final_url)
+                .map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+            let hop_config = redirect::html_redirect_config(config, &from, &redirect.target);
+            let next = self
+                .fetch_page(redirect.target.as_str(), &hop_config)
+                .await?;
+
+            let mut redirects = std::mem::take(&mut result.redirects);
+            redirects.push(RedirectHop {
+                url: result.final_url,
+                status_code: result.status_code,
+                location: redirect.location,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                kind: redirect.kind,
+            });
+            redirects.extend(next.redirects.iter().cloned());
+            result = FetchResult {
+                attempts: result.attempts + next.attempts,
+                redirects,
+                ..next
+            };
+            hops += 1;
+        }
+        Ok(result)
+    }
+
+    async fn fetch_stream(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        let mut result = self.stream_page(url, config).await?;
+        let Some(follow) = &config.html_redirects else {
+            return Ok(result);
+        };
+
+        let mut visited = HashSet::from([result.final_url.clone()]);
+        let mut hops = 0;
+        loop {
+            if !is_html(result.content_type.as_deref()) {
+                break;
+            }
+            // Scanned bytes are replayed, so the stream stays complete
+            let head = result.stream.peek(HTML_REDIRECT_SCAN_BYTES).await?;
+            let Some(redirect) = self
+                .html_redirect(&result.final_url, &head, follow, hops)
+                .filter(|redirect| visited.insert(redirect.target.to_string()))
+            else {
+                break;
+            };
+
+            let started = Instant::now();
+            let from = url::Url::parse(&result.final_url)
+                .map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+            let hop_config = redirect::html_redirect_config(config, &from, &redirect.target);
+            // Dropping the page's stream releases its host slot first
+            let StreamingFetchResult {
+                final_url,
+                status_code,
+                attempts,
+                mut redirects,
+                ..
+            } = result;
+            let next = self
+                .stream_page(redirect.target.as_str(), &hop_config)
+                .await?;
+
+            redirects.push(RedirectHop {
+                url: final_url,
+                status_code,
+                location: redirect.location,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                kind: redirect.kind,
+            });
+            redirects.extend(next.redirects.iter().cloned());
+            result = StreamingFetchResult {
+                attempts: attempts + next.attempts,
+                redirects,
+                ..next
+            };
+            hops += 1;
+        }
+        Ok(result)
+    }
 }
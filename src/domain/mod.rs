@@ -7,3 +7,5 @@ pub mod parse;
 
 pub mod extract;
 pub mod select;
+pub mod session;
+pub mod sitemap;
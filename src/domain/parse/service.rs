@@ -17,6 +17,8 @@ pub struct ParseResult {
     pub structure: DomStructure,
     /// The actual VDOM (needed for selection)
     pub vdom: std::sync::Arc<crate::infra::parser::VDom>,
+    /// Charset declared or detected in the document (when `detect_encoding` is set)
+    pub encoding: Option<String>,
 }
 
 /// Trait for parse services.
@@ -59,16 +61,24 @@ impl ParseService for DefaultParseService {
     fn parse(
         &self,
         html: &str,
-        _config: &ParseConfig,
+        config: &ParseConfig,
     ) -> impl std::future::Future<Output = Result<ParseResult, ParseError>> + Send {
         let parser = self.parser.clone();
         let cache = self.cache.clone();
         let html_str = html.to_string();
+        let detect_encoding = config.detect_encoding;
 
         async move {
             // Start timing the operation
             let _timer = Timer::start("parse");
 
+            // Input is already UTF-8, so this reports the document's own charset
+            let encoding = detect_encoding.then(|| {
+                crate::infra::encoding::detect(None, html_str.as_bytes())
+                    .name()
+                    .to_string()
+            });
+
             // Calculate hash
             use std::collections::hash_map::DefaultHasher;
             use std::hash::{Hash, Hasher};
@@ -110,6 +120,7 @@ impl ParseService for DefaultParseService {
                         max_depth: 0,
                         structure,
                         vdom,
+                        encoding,
                     });
                 }
             }
@@ -153,6 +164,7 @@ impl ParseService for DefaultParseService {
                 max_depth: 0,
                 structure,
                 vdom: vdom_arc,
+                encoding,
             })
         }
     }
@@ -0,0 +1,30 @@
+//! Configuration for sessions.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Configuration for the session store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Idle time after which a session expires, unless the session sets its own
+    pub default_ttl: Duration,
+    /// Largest TTL a session may request
+    pub max_ttl: Duration,
+    /// Maximum number of live sessions
+    pub max_sessions: usize,
+    /// Directory sessions are persisted to (`None` keeps them in memory only)
+    pub persist_dir: Option<PathBuf>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            default_ttl: Duration::from_secs(30 * 60),
+            max_ttl: Duration::from_secs(24 * 60 * 60),
+            max_sessions: 10_000,
+            persist_dir: None,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Error types for session operations.
+
+use thiserror::Error;
+
+/// Errors that can occur during session operations.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    /// Unknown or expired session
+    #[error("Session not found: {0}")]
+    NotFound(String),
+
+    /// Invalid session parameters
+    #[error("Invalid session: {0}")]
+    InvalidRequest(String),
+
+    /// Too many live sessions
+    #[error("Session limit reached: {0}")]
+    LimitReached(String),
+
+    /// Reading or writing persisted sessions failed
+    #[error("Session persistence error: {0}")]
+    Persistence(String),
+}
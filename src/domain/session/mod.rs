@@ -0,0 +1,13 @@
+//! Named scraping sessions.
+//!
+//! A session bundles a cookie jar, default headers and an optional sticky
+//! proxy that fetches referencing it share.
+
+pub mod config;
+pub mod error;
+pub mod store;
+
+// Re-exports
+pub use config::SessionConfig;
+pub use error::SessionError;
+pub use store::{NewSession, Session, SessionStore};
@@ -0,0 +1,403 @@
+//! In-memory session store with optional disk persistence.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::config::SessionConfig;
+use super::error::SessionError;
+use crate::common::fs;
+use crate::domain::fetch::config::FetchConfig;
+use crate::domain::fetch::cookies::{Cookie, CookieJar};
+use crate::domain::fetch::proxy::ProxyConfig;
+
+/// Parameters for a new session.
+#[derive(Debug, Clone, Default)]
+pub struct NewSession {
+    /// Idle TTL (defaults to `SessionConfig::default_ttl`)
+    pub ttl: Option<Duration>,
+    /// Headers sent with every request of the session
+    pub headers: HashMap<String, String>,
+    /// Cookies the jar starts with
+    pub cookies: Vec<Cookie>,
+    /// Proxy used for every request of the session
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// A named session shared by several fetches.
+#[derive(Debug)]
+pub struct Session {
+    /// Session identifier (UUID)
+    pub id: String,
+    /// Creation time
+    pub created_at: DateTime<Utc>,
+    /// Idle time after which the session expires
+    pub ttl: Duration,
+    /// Default request headers (request headers take precedence)
+    pub headers: HashMap<String, String>,
+    /// Sticky proxy (a request proxy takes precedence)
+    pub proxy: Option<ProxyConfig>,
+    /// Cookies collected by the session
+    pub cookies: Arc<CookieJar>,
+    last_used: Mutex<DateTime<Utc>>,
+}
+
+impl Session {
+    /// Time the session expires unless it is used again.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        *self.last_used.lock().unwrap()
+            + chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX)
+    }
+
+    /// Whether the session has expired at `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at() <= now
+    }
+
+    /// Apply the session's cookie jar, default headers and proxy to `config`.
+    pub fn apply(&self, config: &mut FetchConfig) {
+        for (name, value) in &self.headers {
+            if !config.headers.keys().any(|n| n.eq_ignore_ascii_case(name)) {
+                config.headers.insert(name.clone(), value.clone());
+            }
+        }
+        if config.proxy.is_none() {
+            config.proxy = self.proxy.clone();
+        }
+        config.cookie_jar = Some(self.cookies.clone());
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Utc::now();
+    }
+
+    fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            last_used: *self.last_used.lock().unwrap(),
+            ttl: self.ttl,
+            headers: self.headers.clone(),
+            proxy: self.proxy.clone(),
+            cookies: self.cookies.snapshot(),
+        }
+    }
+
+    fn restore(snapshot: SessionSnapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            created_at: snapshot.created_at,
+            ttl: snapshot.ttl,
+            headers: snapshot.headers,
+            proxy: snapshot.proxy,
+            cookies: Arc::new(CookieJar::from_cookies(snapshot.cookies)),
+            last_used: Mutex::new(snapshot.last_used),
+        }
+    }
+}
+
+/// On-disk form of a session.
+#[derive(Serialize, Deserialize)]
+struct SessionSnapshot {
+    id: String,
+    created_at: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+    ttl: Duration,
+    headers: HashMap<String, String>,
+    proxy: Option<ProxyConfig>,
+    cookies: Vec<Cookie>,
+}
+
+/// Store of live sessions.
+#[derive(Debug)]
+pub struct SessionStore {
+    config: SessionConfig,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    /// Held while a session is written to disk
+    saving: Mutex<()>,
+}
+
+impl SessionStore {
+    /// Create an empty store.
+    pub fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// Create a store, loading unexpired sessions from `persist_dir` if set.
+    pub fn open(config: SessionConfig) -> Result<Self, SessionError> {
+        let store = Self::new(config);
+        let Some(dir) = store.config.persist_dir.clone() else {
+            return Ok(store);
+        };
+
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| SessionError::Persistence(format!("create {}: {}", dir.display(), e)))?;
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| SessionError::Persistence(format!("read {}: {}", dir.display(), e)))?;
+
+        let now = Utc::now();
+        let mut sessions = store.sessions.lock().unwrap();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let snapshot = std::fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<SessionSnapshot>(&data).ok());
+            match snapshot.map(Session::restore) {
+                Some(session) if !session.is_expired(now) => {
+                    sessions.insert(session.id.clone(), Arc::new(session));
+                }
+                Some(_) => {
+                    let _ = std::fs::remove_file(&path);
+                }
+                None => tracing::warn!("Skipping unreadable session file {}", path.display()),
+            }
+        }
+        tracing::info!("Loaded {} persisted sessions", sessions.len());
+        drop(sessions);
+        Ok(store)
+    }
+
+    /// Store configuration.
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// Create a session.
+    pub fn create(&self, new: NewSession) -> Result<Arc<Session>, SessionError> {
+        let ttl = new.ttl.unwrap_or(self.config.default_ttl);
+        if ttl.is_zero() || ttl > self.config.max_ttl {
+            return Err(SessionError::InvalidRequest(format!(
+                "ttl must be between 1s and {}s",
+                self.config.max_ttl.as_secs()
+            )));
+        }
+        if let Some(proxy) = &new.proxy {
+            proxy
+                .validate()
+                .map_err(|e| SessionError::InvalidRequest(e.to_string()))?;
+        }
+
+        self.purge_expired();
+        let now = Utc::now();
+        let session = Arc::new(Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: now,
+            ttl,
+            headers: new.headers,
+            proxy: new.proxy,
+            cookies: Arc::new(CookieJar::from_cookies(new.cookies)),
+            last_used: Mutex::new(now),
+        });
+
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            if sessions.len() >= self.config.max_sessions {
+                return Err(SessionError::LimitReached(format!(
+                    "{} live sessions",
+                    sessions.len()
+                )));
+            }
+            sessions.insert(session.id.clone(), session.clone());
+        }
+        self.save(&session)?;
+        Ok(session)
+    }
+
+    /// Look up a live session, extending its lifetime.
+    pub fn get(&self, id: &str) -> Result<Arc<Session>, SessionError> {
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| SessionError::NotFound(id.to_string()))?;
+
+        if session.is_expired(Utc::now()) {
+            self.remove(id);
+            return Err(SessionError::NotFound(id.to_string()));
+        }
+        session.touch();
+        Ok(session)
+    }
+
+    /// Delete a session.
+    pub fn delete(&self, id: &str) -> Result<(), SessionError> {
+        if self.remove(id) {
+            Ok(())
+        } else {
+            Err(SessionError::NotFound(id.to_string()))
+        }
+    }
+
+    /// Persist `session` (no-op without `persist_dir`, or once the session
+    /// is no longer in the store).
+    pub fn save(&self, session: &Session) -> Result<(), SessionError> {
+        let Some(path) = self.path_for(&session.id) else {
+            return Ok(());
+        };
+        // Saves of one store run one at a time, so the newest snapshot wins
+        let _saving = self.saving.lock().unwrap();
+        if !self.holds(session) {
+            return Ok(());
+        }
+
+        let snapshot = session.snapshot();
+        fs::write_atomic(&path, |file| Ok(serde_json::to_writer(file, &snapshot)?))
+            .map_err(|e| SessionError::Persistence(format!("write {}: {}", path.display(), e)))?;
+        // Deleted while being written: do not bring it back after a restart
+        if !self.holds(session) {
+            let _ = std::fs::remove_file(&path);
+        }
+        Ok(())
+    }
+
+    /// Persist `session` like [`SessionStore::save`], off the runtime when
+    /// called from it; failures are logged.
+    pub fn save_in_background(self: &Arc<Self>, session: Arc<Session>) {
+        let store = self.clone();
+        fs::spawn_blocking_io(move || {
+            if let Err(e) = store.save(&session) {
+                tracing::warn!("Failed to persist session {}: {}", session.id, e);
+            }
+        });
+    }
+
+    /// Whether `session` is the live session stored under its id.
+    fn holds(&self, session: &Session) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&session.id)
+            .is_some_and(|held| std::ptr::eq(Arc::as_ptr(held), session))
+    }
+
+    /// Drop expired sessions, returning how many were removed.
+    pub fn purge_expired(&self) -> usize {
+        let now = Utc::now();
+        let expired: Vec<String> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.is_expired(now))
+            .map(|session| session.id.clone())
+            .collect();
+        for id in &expired {
+            self.remove(id);
+        }
+        expired.len()
+    }
+
+    /// Number of sessions held (including expired ones not yet purged).
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Whether the store holds no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn remove(&self, id: &str) -> bool {
+        let removed = self.sessions.lock().unwrap().remove(id).is_some();
+        if removed && let Some(path) = self.path_for(id) {
+            let _ = std::fs::remove_file(path);
+        }
+        removed
+    }
+
+    /// File a session is persisted to; ids are UUIDs so they are safe file names.
+    fn path_for(&self, id: &str) -> Option<PathBuf> {
+        let dir = self.config.persist_dir.as_ref()?;
+        uuid::Uuid::parse_str(id).ok()?;
+        Some(dir.join(format!("{}.json", id)))
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(SessionConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_prefers_request_settings() {
+        let store = SessionStore::default();
+        let session = store
+            .create(NewSession {
+                headers: [
+                    ("Accept-Language".to_string(), "de".to_string()),
+                    ("X-Token".to_string(), "abc".to_string()),
+                ]
+                .into(),
+                proxy: Some(ProxyConfig::new("http://sticky:8080")),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut config = FetchConfig {
+            headers: [("accept-language".to_string(), "en".to_string())].into(),
+            ..Default::default()
+        };
+        store.get(&session.id).unwrap().apply(&mut config);
+
+        assert_eq!(config.headers["accept-language"], "en");
+        assert_eq!(config.headers["X-Token"], "abc");
+        assert_eq!(config.proxy, Some(ProxyConfig::new("http://sticky:8080")));
+        assert!(Arc::ptr_eq(
+            config.cookie_jar.as_ref().unwrap(),
+            &session.cookies
+        ));
+
+        store.delete(&session.id).unwrap();
+        assert!(matches!(
+            store.get(&session.id),
+            Err(SessionError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_persisted_sessions_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("scapi-sessions-{}", uuid::Uuid::new_v4()));
+        let config = SessionConfig {
+            persist_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let store = SessionStore::open(config.clone()).unwrap();
+        let session = store.create(NewSession::default()).unwrap();
+        let origin = url::Url::parse("https://example.com/").unwrap();
+        session.cookies.store_response(&origin, ["sid=42"]);
+        store.save(&session).unwrap();
+
+        let reopened = SessionStore::open(config).unwrap();
+        let restored = reopened.get(&session.id).unwrap();
+        assert_eq!(
+            restored.cookies.cookies_for(&origin),
+            vec![("sid".to_string(), "42".to_string())]
+        );
+
+        reopened.delete(&session.id).unwrap();
+        assert!(!dir.join(format!("{}.json", session.id)).exists());
+
+        // A fetch finishing after the delete does not bring the session back
+        reopened.save(&restored).unwrap();
+        assert!(!dir.join(format!("{}.json", session.id)).exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
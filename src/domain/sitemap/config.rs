@@ -0,0 +1,36 @@
+//! Configuration for sitemap discovery.
+
+use serde::{Deserialize, Serialize};
+
+/// Limits and fallbacks for sitemap discovery.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SitemapConfig {
+    /// Paths probed when robots.txt lists no sitemaps
+    pub well_known_paths: Vec<String>,
+    /// How many levels of nested sitemap indexes are followed
+    pub max_depth: usize,
+    /// Maximum number of sitemap files fetched per request
+    pub max_sitemaps: usize,
+    /// Maximum number of URL entries returned per request
+    pub max_urls: usize,
+    /// Maximum size of one sitemap, compressed or not (the protocol allows 50MB)
+    pub max_size: usize,
+}
+
+impl Default for SitemapConfig {
+    fn default() -> Self {
+        Self {
+            well_known_paths: vec![
+                "/sitemap.xml".to_string(),
+                "/sitemap_index.xml".to_string(),
+                "/sitemap.xml.gz".to_string(),
+                "/sitemap.txt".to_string(),
+            ],
+            max_depth: 3,
+            max_sitemaps: 100,
+            max_urls: 100_000,
+            max_size: 50 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Error types for sitemap operations.
+
+use thiserror::Error;
+
+use crate::domain::fetch::error::FetchError;
+
+/// Errors that can occur while discovering or reading sitemaps.
+#[derive(Debug, Error)]
+pub enum SitemapError {
+    /// The site or sitemap URL is not a valid http(s) URL
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// A sitemap could not be fetched
+    #[error("Failed to fetch sitemap {url}: {source}")]
+    Fetch {
+        /// Sitemap URL
+        url: String,
+        /// Underlying fetch error
+        #[source]
+        source: FetchError,
+    },
+
+    /// A sitemap is not valid XML, RSS, Atom or text
+    #[error("Malformed sitemap {url}: {message}")]
+    Parse {
+        /// Sitemap URL
+        url: String,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// A depth, sitemap or URL limit stopped the walk
+    #[error("Sitemap limit reached: {0}")]
+    LimitReached(String),
+}
+
+impl SitemapError {
+    /// Sitemap the error concerns, if any.
+    pub fn sitemap(&self) -> Option<&str> {
+        match self {
+            SitemapError::Fetch { url, .. } | SitemapError::Parse { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Sitemap discovery and parsing.
+//!
+//! Sitemaps are discovered from robots.txt `Sitemap:` lines, falling back to
+//! well-known paths. XML sitemaps and sitemap indexes, gzip-compressed
+//! sitemaps, RSS/Atom feeds and plain-text URL lists are parsed; nested
+//! indexes are followed within configurable limits.
+
+pub mod config;
+pub mod error;
+pub mod parser;
+pub mod service;
+
+// Re-exports
+pub use config::SitemapConfig;
+pub use error::SitemapError;
+pub use parser::{Sitemap, SitemapEntry};
+pub use service::SitemapService;
@@ -0,0 +1,311 @@
+//! Parsers for XML sitemaps, sitemap indexes, RSS/Atom feeds and text sitemaps.
+
+use flate2::read::GzDecoder;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+use super::error::SitemapError;
+
+/// Elements holding one entry: `<url>`, `<sitemap>`, RSS `<item>`, Atom `<entry>`.
+const ITEM_ELEMENTS: &[&str] = &["url", "sitemap", "item", "entry"];
+
+/// A URL listed in a sitemap.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SitemapEntry {
+    /// Page URL
+    pub loc: String,
+    /// Last modification (as written in the sitemap)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lastmod: Option<String>,
+    /// Expected change frequency (`daily`, `weekly`, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changefreq: Option<String>,
+    /// Priority between 0.0 and 1.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<f32>,
+    /// Sitemap the entry was listed in
+    pub sitemap: String,
+}
+
+/// A parsed sitemap document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sitemap {
+    /// Page URLs (`<urlset>`, RSS, Atom or text)
+    Urls(Vec<SitemapEntry>),
+    /// Nested sitemap URLs (`<sitemapindex>`)
+    Index(Vec<String>),
+}
+
+/// Parse the sitemap fetched from `url`.
+///
+/// Gzip content is detected from its magic bytes and decompressed up to
+/// `max_size` bytes. Relative and non-http(s) URLs are resolved against
+/// `url` or dropped.
+pub fn parse(url: &str, body: &[u8], max_size: usize) -> Result<Sitemap, SitemapError> {
+    let error = |message: String| SitemapError::Parse {
+        url: url.to_string(),
+        message,
+    };
+    let base = url::Url::parse(url).map_err(|e| SitemapError::InvalidUrl(e.to_string()))?;
+
+    let decompressed;
+    let body = if body.starts_with(&[0x1f, 0x8b]) {
+        let mut buffer = Vec::new();
+        GzDecoder::new(body)
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut buffer)
+            .map_err(|e| error(format!("gzip: {}", e)))?;
+        if buffer.len() > max_size {
+            return Err(error(format!(
+                "more than {} bytes after decompression",
+                max_size
+            )));
+        }
+        decompressed = buffer;
+        &decompressed[..]
+    } else {
+        body
+    };
+    let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+
+    if body.trim_ascii_start().starts_with(b"<") {
+        parse_xml(&base, body).map_err(error)
+    } else {
+        Ok(Sitemap::Urls(parse_text(&base, body)))
+    }
+}
+
+/// One absolute URL per line; anything that is not an http(s) URL is ignored.
+fn parse_text(base: &url::Url, body: &[u8]) -> Vec<SitemapEntry> {
+    String::from_utf8_lossy(body)
+        .lines()
+        .filter_map(|line| url::Url::parse(line.trim()).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|url| url.to_string())
+        .map(|loc| SitemapEntry {
+            loc,
+            sitemap: base.to_string(),
+            ..SitemapEntry::default()
+        })
+        .collect()
+}
+
+fn parse_xml(base: &url::Url, body: &[u8]) -> Result<Sitemap, String> {
+    let mut reader = Reader::from_reader(body);
+    reader.config_mut().trim_text(true);
+
+    let mut root = None;
+    let mut depth = 0;
+    // Entry being read and the depth of its element
+    let mut item: Option<(SitemapEntry, usize)> = None;
+    let mut field: Option<String> = None;
+    let mut text = String::new();
+    let mut urls = Vec::new();
+    let mut sitemaps = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("at byte {}: {}", reader.buffer_position(), e))?;
+        match event {
+            Event::Start(element) => {
+                depth += 1;
+                let name = local_name(&element);
+                if root.is_none() {
+                    root = Some(name.clone());
+                }
+                match &mut item {
+                    None if ITEM_ELEMENTS.contains(&name.as_str()) => {
+                        item = Some((SitemapEntry::default(), depth));
+                    }
+                    Some((entry, item_depth)) if depth == *item_depth + 1 => {
+                        atom_link(entry, &element, &name);
+                        field = Some(name);
+                        text.clear();
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(element) => {
+                if let Some((entry, item_depth)) = &mut item
+                    && depth == *item_depth
+                {
+                    atom_link(entry, &element, &local_name(&element));
+                }
+            }
+            Event::Text(content) if field.is_some() => {
+                let content = content.unescape().map_err(|e| e.to_string())?;
+                text.push_str(&content);
+            }
+            Event::CData(content) if field.is_some() => {
+                text.push_str(&String::from_utf8_lossy(&content));
+            }
+            Event::End(_) => {
+                if let Some((entry, item_depth)) = &mut item {
+                    if depth == *item_depth + 1
+                        && let Some(name) = field.take()
+                    {
+                        apply_field(entry, &name, text.trim());
+                    } else if depth == *item_depth {
+                        let (mut entry, _) = item.take().unwrap();
+                        if let Some(loc) = resolve(base, &entry.loc) {
+                            if root.as_deref() == Some("sitemapindex") {
+                                sitemaps.push(loc);
+                            } else {
+                                entry.loc = loc;
+                                entry.sitemap = base.to_string();
+                                urls.push(entry);
+                            }
+                        }
+                    }
+                }
+                depth -= 1;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match root.as_deref() {
+        Some("sitemapindex") => Ok(Sitemap::Index(sitemaps)),
+        Some("urlset" | "rss" | "rdf" | "feed") => Ok(Sitemap::Urls(urls)),
+        Some(other) => Err(format!("unexpected root element <{}>", other)),
+        None => Err("no root element".to_string()),
+    }
+}
+
+/// Store the text of an entry's child element.
+fn apply_field(entry: &mut SitemapEntry, name: &str, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    match name {
+        "loc" => entry.loc = value.to_string(),
+        // RSS <link>; Atom links carry an href instead
+        "link" if entry.loc.is_empty() => entry.loc = value.to_string(),
+        "lastmod" | "pubdate" | "updated" => entry.lastmod = Some(value.to_string()),
+        "published" if entry.lastmod.is_none() => entry.lastmod = Some(value.to_string()),
+        "changefreq" => entry.changefreq = Some(value.to_ascii_lowercase()),
+        "priority" => entry.priority = value.parse().ok(),
+        _ => {}
+    }
+}
+
+/// Take the page URL from an Atom `<link href>` (no `rel` or `rel="alternate"`).
+fn atom_link(entry: &mut SitemapEntry, element: &BytesStart, name: &str) {
+    if name != "link" || !entry.loc.is_empty() {
+        return;
+    }
+    let attribute = |key: &str| {
+        element
+            .try_get_attribute(key)
+            .ok()
+            .flatten()
+            .and_then(|attribute| attribute.unescape_value().ok().map(|v| v.into_owned()))
+    };
+    if attribute("rel").is_none_or(|rel| rel == "alternate")
+        && let Some(href) = attribute("href")
+    {
+        entry.loc = href;
+    }
+}
+
+/// Lowercase local name of an element (namespace prefix dropped).
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).to_ascii_lowercase()
+}
+
+/// Resolve `value` against `base`, keeping only http(s) URLs.
+fn resolve(base: &url::Url, value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let url = base.join(value).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SITEMAP: &str = "https://example.com/sitemap.xml";
+
+    fn urls(sitemap: Sitemap) -> Vec<SitemapEntry> {
+        match sitemap {
+            Sitemap::Urls(urls) => urls,
+            Sitemap::Index(_) => panic!("expected a urlset"),
+        }
+    }
+
+    #[test]
+    fn test_parse_urlset_and_index() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+  <url>
+    <loc>https://example.com/a?x=1&amp;y=2</loc>
+    <lastmod>2024-05-01</lastmod>
+    <changefreq>Weekly</changefreq>
+    <priority>0.8</priority>
+    <image:image><image:loc>https://cdn.example.com/a.jpg</image:loc></image:image>
+  </url>
+  <url><loc><![CDATA[/b]]></loc></url>
+  <url><loc>ftp://example.com/c</loc></url>
+</urlset>"#;
+        let entries = urls(parse(SITEMAP, xml.as_bytes(), 1024).unwrap());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].loc, "https://example.com/a?x=1&y=2");
+        assert_eq!(entries[0].lastmod.as_deref(), Some("2024-05-01"));
+        assert_eq!(entries[0].changefreq.as_deref(), Some("weekly"));
+        assert_eq!(entries[0].priority, Some(0.8));
+        assert_eq!(entries[1].loc, "https://example.com/b");
+        assert_eq!(entries[1].sitemap, SITEMAP);
+
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/posts.xml.gz</loc><lastmod>2024-05-01</lastmod></sitemap>
+  <sitemap><loc>pages.xml</loc></sitemap>
+</sitemapindex>"#;
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzipped.write_all(index.as_bytes()).unwrap();
+        assert_eq!(
+            parse(SITEMAP, &gzipped.finish().unwrap(), 1024).unwrap(),
+            Sitemap::Index(vec![
+                "https://example.com/posts.xml.gz".to_string(),
+                "https://example.com/pages.xml".to_string()
+            ])
+        );
+        assert!(matches!(
+            parse(SITEMAP, b"<html><body>Not found</body></html>", 1024),
+            Err(SitemapError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_feeds_and_text() {
+        let rss = r#"<rss version="2.0"><channel><link>https://example.com/</link>
+<item><title>Post</title><link>https://example.com/post</link>
+<pubDate>Wed, 01 May 2024 10:00:00 GMT</pubDate></item></channel></rss>"#;
+        let entries = urls(parse(SITEMAP, rss.as_bytes(), 1024).unwrap());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].loc, "https://example.com/post");
+        assert!(entries[0].lastmod.is_some());
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry>
+<link rel="edit" href="https://example.com/edit/1"/>
+<link href="https://example.com/entry/1"/><updated>2024-05-01T10:00:00Z</updated>
+</entry></feed>"#;
+        let entries = urls(parse(SITEMAP, atom.as_bytes(), 1024).unwrap());
+        assert_eq!(entries[0].loc, "https://example.com/entry/1");
+        assert_eq!(entries[0].lastmod.as_deref(), Some("2024-05-01T10:00:00Z"));
+
+        let text = "\u{feff}https://example.com/1\n\n# comment\nhttps://example.com/2\r\n";
+        let entries =
+            urls(parse("https://example.com/sitemap.txt", text.as_bytes(), 1024).unwrap());
+        let locs: Vec<_> = entries.iter().map(|entry| entry.loc.as_str()).collect();
+        assert_eq!(locs, ["https://example.com/1", "https://example.com/2"]);
+    }
+}
@@ -0,0 +1,284 @@
+//! Sitemap discovery and traversal.
+
+use futures::{Stream, StreamExt};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use super::config::SitemapConfig;
+use super::error::SitemapError;
+use super::parser::{self, Sitemap, SitemapEntry};
+use crate::domain::fetch::config::{FetchConfig, HttpMethod};
+use crate::domain::fetch::service::{DefaultFetchService, FetchService};
+
+/// Discovers sitemaps and streams the URLs they list.
+///
+/// Sitemaps are fetched through the fetch service, so robots.txt, per-host
+/// politeness, proxies and egress rules apply to them like to any page.
+pub struct SitemapService {
+    fetch: Arc<DefaultFetchService>,
+    config: SitemapConfig,
+}
+
+impl SitemapService {
+    /// Create a service fetching through `fetch`.
+    pub fn new(fetch: Arc<DefaultFetchService>, config: SitemapConfig) -> Self {
+        Self { fetch, config }
+    }
+
+    /// Get the sitemap configuration.
+    pub fn config(&self) -> &SitemapConfig {
+        &self.config
+    }
+
+    /// Find the sitemaps of the site `site` belongs to.
+    ///
+    /// robots.txt `Sitemap:` lines win; when there are none, the well-known
+    /// paths are probed with `HEAD` and the first one that answers is used.
+    pub async fn discover(
+        &self,
+        site: &str,
+        config: &FetchConfig,
+    ) -> Result<Vec<String>, SitemapError> {
+        let parsed = url::Url::parse(site).map_err(|e| SitemapError::InvalidUrl(e.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+            return Err(SitemapError::InvalidUrl(format!(
+                "{} is not an http(s) URL",
+                site
+            )));
+        }
+        let origin = parsed.origin().ascii_serialization();
+
+        let robots = self
+            .fetch
+            .robots
+            .load(self.fetch.client.streaming(), &origin, config)
+            .await;
+        let listed: Vec<String> = robots
+            .sitemaps()
+            .iter()
+            .filter_map(|sitemap| parsed.join(sitemap).ok())
+            .map(|sitemap| sitemap.to_string())
+            .collect();
+        if !listed.is_empty() {
+            return Ok(listed);
+        }
+
+        let probe = FetchConfig {
+            method: HttpMethod::Head,
+            body: None,
+            ..config.clone()
+        };
+        for path in &self.config.well_known_paths {
+            let url = format!("{}{}", origin, path);
+            match self.fetch.fetch_stream(&url, &probe).await {
+                Ok(result) => return Ok(vec![result.final_url]),
+                Err(e) => tracing::debug!("No sitemap at {}: {}", url, e),
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Stream the URL entries of `sitemaps`, following nested indexes.
+    ///
+    /// A sitemap that cannot be fetched or parsed yields an error item and
+    /// the walk goes on with the next one. Hitting `max_sitemaps` or
+    /// `max_urls` yields `SitemapError::LimitReached` and ends the stream;
+    /// indexes nested deeper than `max_depth` are reported and skipped.
+    pub fn entries(
+        &self,
+        sitemaps: Vec<String>,
+        config: FetchConfig,
+    ) -> impl Stream<Item = Result<SitemapEntry, SitemapError>> + Send + 'static {
+        let fetch = self.fetch.clone();
+        let limits = self.config.clone();
+
+        async_stream::stream! {
+            let mut queue: VecDeque<(String, usize)> =
+                sitemaps.into_iter().map(|url| (url, 0)).collect();
+            let mut visited = HashSet::new();
+            let mut fetched = 0;
+            let mut urls = 0;
+
+            'walk: while let Some((url, depth)) = queue.pop_front() {
+                if !visited.insert(url.clone()) {
+                    continue;
+                }
+                if fetched >= limits.max_sitemaps {
+                    yield Err(SitemapError::LimitReached(format!(
+                        "{} sitemaps fetched",
+                        limits.max_sitemaps
+                    )));
+                    break;
+                }
+                fetched += 1;
+
+                match read(&fetch, &url, &config, limits.max_size).await {
+                    Ok(Sitemap::Index(children)) if depth >= limits.max_depth => {
+                        tracing::debug!("Skipping {} nested sitemaps of {}", children.len(), url);
+                        yield Err(SitemapError::LimitReached(format!(
+                            "{} is nested more than {} levels deep",
+                            url, limits.max_depth
+                        )));
+                    }
+                    Ok(Sitemap::Index(children)) => {
+                        queue.extend(children.into_iter().map(|child| (child, depth + 1)));
+                    }
+                    Ok(Sitemap::Urls(entries)) => {
+                        for entry in entries {
+                            if urls >= limits.max_urls {
+                                yield Err(SitemapError::LimitReached(format!(
+                                    "{} URLs listed",
+                                    limits.max_urls
+                                )));
+                                break 'walk;
+                            }
+                            urls += 1;
+                            yield Ok(entry);
+                        }
+                    }
+                    Err(e) => yield Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Fetch and parse one sitemap, reading at most `max_size` bytes.
+async fn read(
+    fetch: &DefaultFetchService,
+    url: &str,
+    config: &FetchConfig,
+    max_size: usize,
+) -> Result<Sitemap, SitemapError> {
+    let fetch_error = |source| SitemapError::Fetch {
+        url: url.to_string(),
+        source,
+    };
+    // Raw bytes: gzip sitemaps must not be transcoded
+    let config = FetchConfig {
+        method: HttpMethod::Get,
+        body: None,
+        transcode_to_utf8: false,
+        max_content_size: max_size,
+        ..config.clone()
+    };
+
+    let mut result = fetch
+        .fetch_stream(url, &config)
+        .await
+        .map_err(fetch_error)?;
+    let mut body = Vec::new();
+    while let Some(chunk) = result.stream.next().await {
+        body.extend_from_slice(&chunk.map_err(fetch_error)?);
+    }
+    parser::parse(&result.final_url, &body, max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::http::HttpClient;
+
+    #[tokio::test]
+    async fn test_discover_and_walk_nested_sitemaps() {
+        let xml = |body: String| {
+            (
+                [(axum::http::header::CONTENT_TYPE, "application/xml")],
+                body,
+            )
+        };
+        let app = axum::Router::new()
+            .route(
+                "/robots.txt",
+                axum::routing::get(|| async { "User-agent: *\nSitemap: /sitemap_index.xml\n" }),
+            )
+            .route(
+                "/sitemap_index.xml",
+                axum::routing::get(move || async move {
+                    xml("<sitemapindex><sitemap><loc>/posts.xml</loc></sitemap>\
+                         <sitemap><loc>/nested.xml</loc></sitemap>\
+                         <sitemap><loc>/missing.xml</loc></sitemap></sitemapindex>"
+                        .to_string())
+                }),
+            )
+            .route(
+                "/nested.xml",
+                axum::routing::get(move || async move {
+                    xml("<sitemapindex><sitemap><loc>/pages.txt</loc></sitemap>\
+                         <sitemap><loc>/posts.xml</loc></sitemap></sitemapindex>"
+                        .to_string())
+                }),
+            )
+            .route(
+                "/posts.xml",
+                axum::routing::get(move || async move {
+                    let urls: String = (1..=3)
+                        .map(|i| {
+                            format!("<url><loc>/post/{}</loc><priority>0.5</priority></url>", i)
+                        })
+                        .collect();
+                    xml(format!("<urlset>{}</urlset>", urls))
+                }),
+            )
+            .route(
+                "/pages.txt",
+                axum::routing::get(|| async { "/about\nhttp://127.0.0.1/page\n" }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let fetch = Arc::new(DefaultFetchService::new(HttpClient::new().unwrap()));
+        let config = FetchConfig::default();
+        let service = SitemapService::new(fetch.clone(), SitemapConfig::default());
+
+        let sitemaps = service.discover(&base, &config).await.unwrap();
+        assert_eq!(sitemaps, [format!("{}/sitemap_index.xml", base)]);
+
+        let items: Vec<_> = service
+            .entries(sitemaps.clone(), config.clone())
+            .collect()
+            .await;
+        let locs: Vec<_> = items
+            .iter()
+            .filter_map(|item| item.as_ref().ok())
+            .map(|entry| entry.loc.clone())
+            .collect();
+        assert_eq!(
+            locs,
+            [
+                format!("{}/post/1", base),
+                format!("{}/post/2", base),
+                format!("{}/post/3", base),
+                "http://127.0.0.1/page".to_string(),
+            ]
+        );
+        let failed: Vec<_> = items
+            .iter()
+            .filter_map(|item| item.as_ref().err())
+            .collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].sitemap(), Some(&*format!("{}/missing.xml", base)));
+
+        // Limits end the walk with an error item
+        let limited = SitemapService::new(
+            fetch,
+            SitemapConfig {
+                max_depth: 0,
+                max_urls: 2,
+                ..SitemapConfig::default()
+            },
+        );
+        let items: Vec<_> = limited.entries(sitemaps, config.clone()).collect().await;
+        assert!(matches!(
+            items.as_slice(),
+            [Err(SitemapError::LimitReached(_))]
+        ));
+        let items: Vec<_> = limited
+            .entries(vec![format!("{}/posts.xml", base)], config)
+            .collect()
+            .await;
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[2], Err(SitemapError::LimitReached(_))));
+    }
+}
@@ -0,0 +1,154 @@
+//! Charset detection for HTML documents.
+//!
+//! Follows the precedence browsers use: byte order mark, then the
+//! `Content-Type` charset, then a `<meta>` prescan of the document head,
+//! and finally statistical sniffing.
+
+use encoding_rs::{Encoding, UTF_8};
+use regex::bytes::Regex;
+use std::sync::OnceLock;
+
+/// Number of leading bytes scanned for `<meta>` charset declarations.
+pub const PRESCAN_BYTES: usize = 1024;
+
+/// Where the detected encoding came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingSource {
+    /// Byte order mark
+    Bom,
+    /// `charset` parameter of the Content-Type header
+    Header,
+    /// `<meta charset>` or `<meta http-equiv="Content-Type">`
+    Meta,
+    /// Statistical sniffing of the content
+    Sniffed,
+    /// Nothing to go on (empty content)
+    Default,
+}
+
+/// Result of charset detection.
+#[derive(Debug, Clone, Copy)]
+pub struct DetectedEncoding {
+    /// Detected encoding
+    pub encoding: &'static Encoding,
+    /// How it was detected
+    pub source: EncodingSource,
+}
+
+impl DetectedEncoding {
+    /// Canonical encoding name (e.g. "Shift_JIS", "windows-1251").
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+}
+
+/// Detect the encoding of `bytes`, given the response Content-Type if known.
+pub fn detect(content_type: Option<&str>, bytes: &[u8]) -> DetectedEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return DetectedEncoding {
+            encoding,
+            source: EncodingSource::Bom,
+        };
+    }
+
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return DetectedEncoding {
+            encoding,
+            source: EncodingSource::Header,
+        };
+    }
+
+    let prefix = &bytes[..bytes.len().min(PRESCAN_BYTES)];
+    if let Some(encoding) = charset_from_meta(prefix) {
+        return DetectedEncoding {
+            encoding,
+            source: EncodingSource::Meta,
+        };
+    }
+
+    if bytes.is_empty() {
+        return DetectedEncoding {
+            encoding: UTF_8,
+            source: EncodingSource::Default,
+        };
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    DetectedEncoding {
+        encoding: detector.guess(None, true),
+        source: EncodingSource::Sniffed,
+    }
+}
+
+/// Decode `bytes` to UTF-8 using a detected encoding.
+///
+/// Malformed sequences are replaced with U+FFFD; the returned flag reports
+/// whether any replacement happened.
+pub fn decode(bytes: &[u8], detected: &DetectedEncoding) -> (String, bool) {
+    let (text, had_errors) = detected.encoding.decode_with_bom_removal(bytes);
+    (text.into_owned(), had_errors)
+}
+
+/// Extract the encoding from a Content-Type header value.
+pub fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        Encoding::for_label(value.as_bytes()).map(|e| e.output_encoding())
+    })
+}
+
+/// Find a charset declared in `<meta charset>` or `<meta http-equiv>` tags.
+pub fn charset_from_meta(prefix: &[u8]) -> Option<&'static Encoding> {
+    static META_CHARSET: OnceLock<Regex> = OnceLock::new();
+    let re = META_CHARSET.get_or_init(|| {
+        Regex::new(r#"(?i)<meta\b[^>]*?charset\s*=\s*["']?\s*([A-Za-z0-9_:.\-]+)"#)
+            .expect("valid meta charset regex")
+    });
+
+    let caps = re.captures(prefix)?;
+    // A UTF-16 declaration in an ASCII-compatible document means UTF-8
+    Encoding::for_label(caps.get(1)?.as_bytes()).map(|e| e.output_encoding())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection_precedence() {
+        let html = b"\xEF\xBB\xBF<meta charset=\"shift_jis\">";
+        assert_eq!(
+            detect(Some("text/html; charset=gbk"), html).source,
+            EncodingSource::Bom
+        );
+
+        let html = b"<meta charset=\"shift_jis\">";
+        let detected = detect(Some("text/html; charset=\"GBK\""), html);
+        assert_eq!(detected.source, EncodingSource::Header);
+        assert_eq!(detected.name(), "GBK");
+
+        let html =
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1251\">";
+        let detected = detect(Some("text/html"), html);
+        assert_eq!(detected.source, EncodingSource::Meta);
+        assert_eq!(detected.name(), "windows-1251");
+    }
+
+    #[test]
+    fn test_sniff_and_decode_shift_jis() {
+        let (bytes, _, _) =
+            encoding_rs::SHIFT_JIS.encode("<p>日本語のテキストです。こんにちは世界</p>");
+        let detected = detect(None, &bytes);
+        assert_eq!(detected.source, EncodingSource::Sniffed);
+        assert_eq!(detected.name(), "Shift_JIS");
+
+        let (text, had_errors) = decode(&bytes, &detected);
+        assert!(!had_errors);
+        assert_eq!(text, "<p>日本語のテキストです。こんにちは世界</p>");
+    }
+}
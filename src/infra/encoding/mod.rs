@@ -0,0 +1,6 @@
+//! Character encoding detection and transcoding.
+
+pub mod detector;
+
+// Re-exports
+pub use detector::{DetectedEncoding, EncodingSource, decode, detect};
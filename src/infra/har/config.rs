@@ -0,0 +1,37 @@
+//! Configuration for HAR recording and sources.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Configuration for HAR recording and `har://` sources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarConfig {
+    /// Record every network fetch for export
+    pub enabled: bool,
+    /// Directory `har://` sources are read from
+    pub directory: PathBuf,
+    /// Number of most recent entries kept for export
+    pub max_entries: usize,
+    /// Request and response bodies are cut at this many bytes per entry
+    pub max_entry_bytes: usize,
+    /// Approximate memory the recorded entries may use
+    pub max_total_bytes: usize,
+    /// Bearer token required to export or clear the recording (no API
+    /// access when unset)
+    #[serde(skip_serializing)]
+    pub export_token: Option<String>,
+}
+
+impl Default for HarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("har"),
+            max_entries: 1000,
+            max_entry_bytes: 1024 * 1024,
+            max_total_bytes: 64 * 1024 * 1024,
+            export_token: None,
+        }
+    }
+}
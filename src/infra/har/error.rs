@@ -0,0 +1,33 @@
+//! Error types for HAR files.
+
+use thiserror::Error;
+
+/// Errors that can occur while reading or writing HAR files.
+#[derive(Debug, Error)]
+pub enum HarError {
+    /// Reading or writing a HAR file failed
+    #[error("HAR I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The file is not valid HAR
+    #[error("Malformed HAR: {0}")]
+    Format(String),
+
+    /// No file or entry matches the request
+    #[error("HAR entry not found: {0}")]
+    NotFound(String),
+
+    /// A `har://` source could not be parsed or points outside the HAR directory
+    #[error("Invalid HAR source: {0}")]
+    InvalidSource(String),
+}
+
+impl From<serde_json::Error> for HarError {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            HarError::Io(error.into())
+        } else {
+            HarError::Format(error.to_string())
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! HAR 1.2 import and export.
+//!
+//! Network fetches, including every redirect hop, can be recorded with their
+//! timings and exported as a HAR document. HAR files captured elsewhere (for
+//! example in a browser) are read back as `har://file#entry` sources for
+//! select and extract.
+
+pub mod config;
+pub mod error;
+pub mod model;
+pub mod reader;
+pub mod recorder;
+
+// Re-exports
+pub use config::HarConfig;
+pub use error::HarError;
+pub use model::{Har, HarContent, HarEntry, HarLog};
+pub use reader::{HarArchive, HarSource};
+pub use recorder::HarRecorder;
@@ -0,0 +1,242 @@
+//! HAR 1.2 document model.
+//!
+//! Field names follow the HAR 1.2 specification. Everything that browsers
+//! commonly omit defaults on import, and unknown (`_custom`) fields are
+//! ignored.
+
+use serde::{Deserialize, Serialize};
+
+/// HAR version written on export.
+pub const HAR_VERSION: &str = "1.2";
+
+/// A HAR document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Har {
+    /// The log
+    pub log: HarLog,
+}
+
+/// The `log` object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarLog {
+    /// Format version
+    pub version: String,
+    /// Application that created the log
+    pub creator: HarCreator,
+    /// Pages (browser captures only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pages: Vec<HarPage>,
+    /// Exchanges, in the order they were started
+    pub entries: Vec<HarEntry>,
+}
+
+impl Default for HarLog {
+    fn default() -> Self {
+        Self {
+            version: HAR_VERSION.to_string(),
+            creator: HarCreator::default(),
+            pages: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// The `creator` object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarCreator {
+    /// Application name
+    pub name: String,
+    /// Application version
+    pub version: String,
+}
+
+impl Default for HarCreator {
+    fn default() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// A page of a browser capture.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarPage {
+    /// Page start time (ISO 8601)
+    pub started_date_time: String,
+    /// Page identifier referenced by `HarEntry::pageref`
+    pub id: String,
+    /// Page title
+    pub title: String,
+}
+
+/// One request/response exchange.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarEntry {
+    /// Page the entry belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pageref: Option<String>,
+    /// Request start time (ISO 8601)
+    pub started_date_time: String,
+    /// Total time in milliseconds
+    pub time: f64,
+    /// The request
+    pub request: HarRequest,
+    /// The response
+    pub response: HarResponse,
+    /// Cache usage (always empty on export)
+    pub cache: serde_json::Map<String, serde_json::Value>,
+    /// Timing breakdown
+    pub timings: HarTimings,
+    /// Address of the server
+    #[serde(rename = "serverIPAddress", skip_serializing_if = "Option::is_none")]
+    pub server_ip_address: Option<String>,
+}
+
+/// The `request` object.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarRequest {
+    /// Request method
+    pub method: String,
+    /// Absolute URL
+    pub url: String,
+    /// HTTP version
+    pub http_version: String,
+    /// Cookies sent
+    pub cookies: Vec<HarCookie>,
+    /// Headers sent
+    pub headers: Vec<HarHeader>,
+    /// Query string parameters
+    pub query_string: Vec<HarHeader>,
+    /// Request body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    /// Size of the headers, -1 if unknown
+    pub headers_size: i64,
+    /// Size of the body, -1 if unknown
+    pub body_size: i64,
+}
+
+/// The `response` object.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarResponse {
+    /// Status code
+    pub status: u16,
+    /// Reason phrase
+    pub status_text: String,
+    /// HTTP version
+    pub http_version: String,
+    /// Cookies set
+    pub cookies: Vec<HarCookie>,
+    /// Headers received
+    pub headers: Vec<HarHeader>,
+    /// Response body
+    pub content: HarContent,
+    /// `Location` of a redirect, empty otherwise
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    /// Size of the headers, -1 if unknown
+    pub headers_size: i64,
+    /// Size of the body as received, -1 if unknown
+    pub body_size: i64,
+}
+
+/// A `name`/`value` pair (headers and query parameters).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarHeader {
+    /// Name
+    pub name: String,
+    /// Value
+    pub value: String,
+}
+
+/// A cookie.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarCookie {
+    /// Cookie name
+    pub name: String,
+    /// Cookie value
+    pub value: String,
+}
+
+/// The `postData` object.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarPostData {
+    /// Body media type
+    pub mime_type: String,
+    /// Body text
+    pub text: String,
+}
+
+/// The `content` object.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarContent {
+    /// Length of the (decoded) body
+    pub size: i64,
+    /// Media type from `Content-Type`
+    pub mime_type: String,
+    /// Body, as text or base64 (see `encoding`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `base64` when `text` is base64-encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+impl HarContent {
+    /// Body bytes, decoding base64 content.
+    pub fn body(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        use base64::Engine;
+
+        let text = self.text.as_deref().unwrap_or_default();
+        if self.encoding.as_deref() == Some("base64") {
+            base64::engine::general_purpose::STANDARD.decode(text.trim())
+        } else {
+            Ok(text.as_bytes().to_vec())
+        }
+    }
+}
+
+/// The `timings` object, in milliseconds; -1 means not applicable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarTimings {
+    /// Waiting for a connection
+    pub blocked: f64,
+    /// DNS resolution
+    pub dns: f64,
+    /// Connecting
+    pub connect: f64,
+    /// Sending the request
+    pub send: f64,
+    /// Waiting for the response headers
+    pub wait: f64,
+    /// Reading the response body
+    pub receive: f64,
+    /// TLS handshake (part of `connect`)
+    pub ssl: f64,
+}
+
+impl Default for HarTimings {
+    fn default() -> Self {
+        Self {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: 0.0,
+            receive: 0.0,
+            ssl: -1.0,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! HAR files as `har://` sources.
+
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use super::error::HarError;
+use super::model::{Har, HarEntry};
+
+/// A `har://file[#entry]` source.
+///
+/// `file` is relative to the HAR directory and `entry` is the zero-based
+/// index into `log.entries`. Without an entry the source stands for every
+/// entry with a response body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarSource {
+    /// HAR file, relative to the HAR directory
+    pub file: String,
+    /// Index of the entry to read
+    pub entry: Option<usize>,
+}
+
+impl HarSource {
+    /// Whether `source` uses the `har://` scheme.
+    pub fn is_har(source: &str) -> bool {
+        source
+            .get(..6)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("har://"))
+    }
+}
+
+impl FromStr for HarSource {
+    type Err = HarError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if !Self::is_har(source) {
+            return Err(HarError::InvalidSource(format!(
+                "{} is not a har:// URL",
+                source
+            )));
+        }
+        let rest = &source[6..];
+        let (file, entry) = match rest.split_once('#') {
+            Some((file, entry)) => (file, Some(entry)),
+            None => (rest, None),
+        };
+
+        let file = percent_encoding::percent_decode_str(file)
+            .decode_utf8_lossy()
+            .into_owned();
+        if file.is_empty() {
+            return Err(HarError::InvalidSource(format!(
+                "{} names no HAR file",
+                source
+            )));
+        }
+        let entry = match entry.filter(|entry| !entry.is_empty()) {
+            Some(entry) => Some(entry.parse().map_err(|_| {
+                HarError::InvalidSource(format!("{} is not an entry index", entry))
+            })?),
+            None => None,
+        };
+        Ok(Self { file, entry })
+    }
+}
+
+/// Directory of HAR files that `har://` sources are resolved against.
+#[derive(Debug, Clone)]
+pub struct HarArchive {
+    root: PathBuf,
+}
+
+impl HarArchive {
+    /// Resolve sources against `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// HAR directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of `file`, which must stay inside the HAR directory.
+    pub fn path(&self, file: &str) -> Result<PathBuf, HarError> {
+        let relative = Path::new(file);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(HarError::InvalidSource(format!(
+                "{} is outside the HAR directory",
+                file
+            )));
+        }
+        let path = self.root.join(relative);
+        if !path.is_file() {
+            return Err(HarError::NotFound(format!("HAR file {}", file)));
+        }
+        Ok(path)
+    }
+
+    /// Read and parse `file`.
+    pub fn load(&self, file: &str) -> Result<Har, HarError> {
+        let reader = BufReader::new(std::fs::File::open(self.path(file)?)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Entries of `file` that carry a response body, with their indexes.
+    pub fn documents(&self, file: &str) -> Result<Vec<(usize, HarEntry)>, HarError> {
+        Ok(self
+            .load(file)?
+            .log
+            .entries
+            .into_iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                entry
+                    .response
+                    .content
+                    .text
+                    .as_ref()
+                    .is_some_and(|text| !text.is_empty())
+            })
+            .collect())
+    }
+
+    /// Entry `index` of `file`.
+    pub fn find(&self, file: &str, index: usize) -> Result<HarEntry, HarError> {
+        self.load(file)?
+            .log
+            .entries
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| HarError::NotFound(format!("entry {} in {}", index, file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::recorder::{HarRecorder, entry};
+    use super::*;
+    use crate::infra::warc::{ExchangeSink, WarcExchange};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_export_and_read_back() {
+        let exchange = |url: &str, status: u16, body: &'static [u8]| WarcExchange {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            request_headers: vec![("cookie".to_string(), "sid=42; theme=dark".to_string())],
+            request_body: None,
+            version: "HTTP/1.1".to_string(),
+            status,
+            response_headers: vec![("content-type".to_string(), "text/html".to_string())],
+            body: Bytes::from_static(body),
+            truncated: false,
+            ip: Some("127.0.0.1".parse().unwrap()),
+            date: chrono::Utc::now(),
+            wait: std::time::Duration::from_millis(20),
+        };
+
+        let recorder = HarRecorder::new(2);
+        recorder.record(&exchange("https://example.com/?q=a", 200, b"<p>first</p>"));
+        recorder.record(&exchange("https://example.com/gone", 404, b""));
+        recorder.record(&exchange("https://example.com/logo", 200, &[0xff, 0xd8]));
+        let har = recorder.export();
+        assert_eq!(har.log.version, "1.2");
+        assert_eq!(har.log.entries.len(), 2);
+        assert_eq!(har.log.entries[0].response.status_text, "Not Found");
+        assert_eq!(har.log.entries[0].request.cookies.len(), 2);
+        let logo = &har.log.entries[1].response.content;
+        assert_eq!(logo.encoding.as_deref(), Some("base64"));
+        assert_eq!(logo.body().unwrap(), [0xff, 0xd8]);
+
+        let first = entry(
+            &exchange("https://example.com/?q=a", 200, b"<p>first</p>"),
+            chrono::Utc::now(),
+        );
+        assert_eq!(first.request.query_string[0].value, "a");
+        assert!(first.timings.wait >= 20.0);
+
+        let dir = std::env::temp_dir().join(format!("scapi-har-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut saved = har.clone();
+        saved.log.entries.insert(0, first);
+        std::fs::write(dir.join("qa.har"), serde_json::to_vec(&saved).unwrap()).unwrap();
+
+        let archive = HarArchive::new(&dir);
+        assert_eq!(archive.load("qa.har").unwrap(), saved);
+        let indexes: Vec<_> = archive
+            .documents("qa.har")
+            .unwrap()
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(indexes, [0, 2]);
+
+        let source: HarSource = "har://qa.har#2".parse().unwrap();
+        assert_eq!(source.entry, Some(2));
+        assert_eq!(
+            archive.find(&source.file, 2).unwrap().request.url,
+            "https://example.com/logo"
+        );
+        assert!(matches!(
+            archive.find("qa.har", 9),
+            Err(HarError::NotFound(_))
+        ));
+        assert!(matches!(
+            "har://qa.har#first".parse::<HarSource>(),
+            Err(HarError::InvalidSource(_))
+        ));
+        assert!(matches!(
+            archive.load("../qa.har"),
+            Err(HarError::InvalidSource(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,401 @@
+//! In-memory HAR recorder fed by the streaming client.
+
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::model::{
+    Har, HarContent, HarCookie, HarEntry, HarHeader, HarLog, HarPostData, HarRequest, HarResponse,
+    HarTimings,
+};
+use crate::common::types::header;
+use crate::domain::fetch::auth::REDACTED;
+use crate::infra::warc::{ExchangeSink, WarcExchange};
+
+/// Headers whose values never end up in an entry.
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+/// Keeps the most recent fetches as HAR entries for export.
+///
+/// Every redirect hop is its own entry, like in a browser capture.
+/// Credentials and cookie values are redacted, bodies are cut at
+/// `max_entry_bytes` and the oldest entries are dropped once the recording
+/// exceeds `max_entries` or `max_total_bytes`.
+#[derive(Debug)]
+pub struct HarRecorder {
+    max_entries: usize,
+    max_entry_bytes: usize,
+    max_total_bytes: usize,
+    export_token: Option<String>,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Entries with their approximate size in bytes
+    entries: VecDeque<(usize, HarEntry)>,
+    bytes: usize,
+}
+
+impl HarRecorder {
+    /// Keep up to `max_entries` entries, dropping the oldest first.
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            max_entry_bytes: usize::MAX,
+            max_total_bytes: usize::MAX,
+            export_token: None,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Cut recorded bodies at `entry` bytes and keep at most `total` bytes
+    /// of entries.
+    pub fn with_max_bytes(mut self, entry: usize, total: usize) -> Self {
+        self.max_entry_bytes = entry;
+        self.max_total_bytes = total;
+        self
+    }
+
+    /// Require `token` to export or clear the recording; without one the
+    /// recording cannot be read through the API.
+    pub fn with_export_token(mut self, token: Option<String>) -> Self {
+        self.export_token = token.filter(|token| !token.is_empty());
+        self
+    }
+
+    /// Whether `token` grants access to the recording.
+    pub fn authorize(&self, token: Option<&str>) -> bool {
+        match (&self.export_token, token) {
+            (Some(expected), Some(token)) => {
+                // Constant time for tokens of the expected length
+                expected.len() == token.len()
+                    && expected
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0, |diff, (a, b)| diff | (a ^ b))
+                        == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Add an entry.
+    pub fn push(&self, entry: HarEntry) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let size = entry_bytes(&entry);
+        if size > self.max_total_bytes {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        while entries.entries.len() >= self.max_entries
+            || entries.bytes + size > self.max_total_bytes
+        {
+            let Some((dropped, _)) = entries.entries.pop_front() else {
+                break;
+            };
+            entries.bytes -= dropped;
+        }
+        entries.bytes += size;
+        entries.entries.push_back((size, entry));
+    }
+
+    /// Number of recorded entries.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    /// Approximate size of the recorded entries in bytes.
+    pub fn bytes(&self) -> usize {
+        self.entries.lock().unwrap().bytes
+    }
+
+    /// Whether nothing has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The recorded entries as a HAR document, oldest first.
+    pub fn export(&self) -> Har {
+        let mut entries: Vec<HarEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        entries.sort_by(|a, b| a.started_date_time.cmp(&b.started_date_time));
+        Har {
+            log: HarLog {
+                entries,
+                ..HarLog::default()
+            },
+        }
+    }
+
+    /// Drop every recorded entry.
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = Entries::default();
+    }
+}
+
+impl ExchangeSink for HarRecorder {
+    fn record(&self, exchange: &WarcExchange) {
+        let limit = self.max_entry_bytes;
+        if exchange.body.len() <= limit
+            && exchange
+                .request_body
+                .as_ref()
+                .is_none_or(|body| body.len() <= limit)
+        {
+            self.push(entry(exchange, Utc::now()));
+            return;
+        }
+
+        let mut cut = exchange.clone();
+        cut.request_body = cut
+            .request_body
+            .map(|body| body.slice(..body.len().min(limit)));
+        cut.body = cut.body.slice(..cut.body.len().min(limit));
+        cut.truncated = true;
+        let mut entry = entry(&cut, Utc::now());
+        entry.response.content.size = exchange.body.len() as i64;
+        if let Some(body) = &exchange.request_body {
+            entry.request.body_size = body.len() as i64;
+        }
+        self.push(entry);
+    }
+
+    fn max_body(&self) -> usize {
+        self.max_entry_bytes
+    }
+}
+
+/// Build the HAR entry of an exchange whose body finished at `finished`.
+///
+/// Values of credential and cookie headers are replaced by `REDACTED`;
+/// cookie names are kept.
+pub fn entry(exchange: &WarcExchange, finished: DateTime<Utc>) -> HarEntry {
+    let millis = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    let wait = millis(exchange.wait);
+    let receive = (finished - exchange.date)
+        .to_std()
+        .map(|total| millis(total.saturating_sub(exchange.wait)))
+        .unwrap_or_default();
+
+    let pairs = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(name, value)| HarHeader {
+                name: name.clone(),
+                value: if is_sensitive(name) {
+                    REDACTED.to_string()
+                } else {
+                    value.clone()
+                },
+            })
+            .collect()
+    };
+
+    let query_string = url::Url::parse(&exchange.url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| HarHeader {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let request_cookies = header(&exchange.request_headers, "cookie")
+        .map(|value| value.split(';').filter_map(cookie).collect())
+        .unwrap_or_default();
+    let post_data = exchange.request_body.as_ref().map(|body| HarPostData {
+        mime_type: header(&exchange.request_headers, "content-type")
+            .unwrap_or_default()
+            .to_string(),
+        text: String::from_utf8_lossy(body).into_owned(),
+    });
+
+    let response_cookies = exchange
+        .response_headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, value)| value.split(';').next().and_then(cookie))
+        .collect();
+    let (text, encoding) = match std::str::from_utf8(&exchange.body) {
+        _ if exchange.body.is_empty() => (None, None),
+        Ok(text) => (Some(text.to_string()), None),
+        Err(_) => (
+            Some(base64::engine::general_purpose::STANDARD.encode(&exchange.body)),
+            Some("base64".to_string()),
+        ),
+    };
+
+    HarEntry {
+        pageref: None,
+        started_date_time: exchange.date.to_rfc3339_opts(SecondsFormat::Millis, true),
+        time: wait + receive,
+        request: HarRequest {
+            method: exchange.method.clone(),
+            url: exchange.url.clone(),
+            http_version: exchange.version.clone(),
+            cookies: request_cookies,
+            headers: pairs(&exchange.request_headers),
+            query_string,
+            post_data,
+            headers_size: -1,
+            body_size: exchange
+                .request_body
+                .as_ref()
+                .map_or(0, |body| body.len() as i64),
+        },
+        response: HarResponse {
+            status: exchange.status,
+            status_text: reqwest::StatusCode::from_u16(exchange.status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or_default()
+                .to_string(),
+            http_version: exchange.version.clone(),
+            cookies: response_cookies,
+            headers: pairs(&exchange.response_headers),
+            content: HarContent {
+                size: exchange.body.len() as i64,
+                mime_type: header(&exchange.response_headers, "content-type")
+                    .unwrap_or_default()
+                    .to_string(),
+                text,
+                encoding,
+            },
+            redirect_url: header(&exchange.response_headers, "location")
+                .unwrap_or_default()
+                .to_string(),
+            headers_size: -1,
+            body_size: if exchange.truncated {
+                -1
+            } else {
+                exchange.body.len() as i64
+            },
+        },
+        cache: serde_json::Map::new(),
+        timings: HarTimings {
+            wait,
+            receive,
+            ..HarTimings::default()
+        },
+        server_ip_address: exchange.ip.map(|ip| ip.to_string()),
+    }
+}
+
+/// Whether the value of header `name` must not be recorded.
+fn is_sensitive(name: &str) -> bool {
+    SENSITIVE_HEADERS
+        .iter()
+        .any(|sensitive| name.eq_ignore_ascii_case(sensitive))
+}
+
+/// Parse the name of one `name=value` cookie pair, redacting its value.
+fn cookie(pair: &str) -> Option<HarCookie> {
+    let (name, _) = pair.trim().split_once('=')?;
+    Some(HarCookie {
+        name: name.trim().to_string(),
+        value: REDACTED.to_string(),
+    })
+}
+
+/// Approximate memory held by an entry.
+fn entry_bytes(entry: &HarEntry) -> usize {
+    let headers = |headers: &[HarHeader]| {
+        headers
+            .iter()
+            .map(|header| header.name.len() + header.value.len())
+            .sum::<usize>()
+    };
+    entry.request.url.len()
+        + headers(&entry.request.headers)
+        + headers(&entry.request.query_string)
+        + entry
+            .request
+            .post_data
+            .as_ref()
+            .map_or(0, |post| post.text.len())
+        + headers(&entry.response.headers)
+        + entry.response.content.text.as_ref().map_or(0, String::len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn exchange(body: &'static [u8]) -> WarcExchange {
+        WarcExchange {
+            url: "https://example.com/login".to_string(),
+            method: "POST".to_string(),
+            request_headers: vec![
+                ("authorization".to_string(), "Bearer t0ken".to_string()),
+                ("Cookie".to_string(), "sid=s3cr3t".to_string()),
+                ("accept".to_string(), "text/html".to_string()),
+            ],
+            request_body: Some(Bytes::from_static(b"user=a&password=hunter2")),
+            version: "HTTP/1.1".to_string(),
+            status: 200,
+            response_headers: vec![(
+                "set-cookie".to_string(),
+                "sid=n3w-s3cr3t; Secure".to_string(),
+            )],
+            body: Bytes::from_static(body),
+            truncated: false,
+            ip: None,
+            date: Utc::now(),
+            wait: std::time::Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_redacts_credentials_and_caps_bytes() {
+        let recorder = HarRecorder::new(10).with_max_bytes(8, 200);
+        recorder.record(&exchange(b"0123456789abcdef"));
+        let entries = recorder.export().log.entries;
+        let exported = serde_json::to_string(&entries).unwrap();
+        assert!(!exported.contains("t0ken") && !exported.contains("s3cr3t"));
+        assert_eq!(entries[0].request.cookies[0].name, "sid");
+        assert_eq!(entries[0].request.headers[2].value, "text/html");
+        let content = &entries[0].response.content;
+        assert_eq!(content.text.as_deref(), Some("01234567"));
+        assert_eq!(content.size, 16);
+        assert_eq!(
+            entries[0].request.post_data.as_ref().unwrap().text,
+            "user=a&p"
+        );
+        assert_eq!(entries[0].response.body_size, -1);
+
+        // The oldest entries make room under the total cap
+        for _ in 0..5 {
+            recorder.record(&exchange(b"0123456789abcdef"));
+        }
+        assert!(recorder.bytes() <= 200);
+        assert!(recorder.len() < 6);
+    }
+
+    #[test]
+    fn test_export_token() {
+        let recorder = HarRecorder::new(1);
+        assert!(!recorder.authorize(None) && !recorder.authorize(Some("")));
+        let recorder = recorder.with_export_token(Some("s3cret".to_string()));
+        assert!(recorder.authorize(Some("s3cret")));
+        assert!(!recorder.authorize(Some("s3cre")) && !recorder.authorize(None));
+    }
+}
@@ -0,0 +1,1048 @@
+//! RFC 9111 HTTP response cache.
+//!
+//! Responses to GET requests are stored per URL, with one variant per
+//! combination of the request headers named in `Vary`. Fresh entries are
+//! served without touching the network; stale entries are revalidated with
+//! `If-None-Match`/`If-Modified-Since` and refreshed on `304 Not Modified`.
+//! The cache is shared by every caller, so `private` responses are not
+//! stored, `s-maxage` takes precedence over `max-age`, and requests carrying
+//! credentials, cookies or a session cookie jar bypass it entirely.
+
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures::stream::Stream;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::common::fs;
+use crate::domain::fetch::config::{FetchConfig, HttpMethod};
+use crate::domain::fetch::error::FetchError;
+use crate::domain::fetch::redirect::RedirectHop;
+
+/// Maximum number of `Vary` variants kept per URL.
+const MAX_VARIANTS: usize = 8;
+
+/// Delta-seconds values are capped here (RFC 9111 section 1.2.2).
+const MAX_DELTA_SECONDS: u64 = 1 << 31;
+
+/// Number of locks URLs are striped over when updating stored variants.
+const STORE_LOCKS: usize = 64;
+
+/// How a response was produced with respect to the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStatus {
+    /// Served from cache without contacting the origin
+    Hit,
+    /// Fetched from the origin (and stored if cacheable)
+    Miss,
+    /// Stale entry confirmed by the origin with 304 Not Modified
+    Revalidated,
+    /// Request not eligible for caching
+    Bypass,
+}
+
+impl CacheStatus {
+    /// Value used in the `X-Scapi-Cache` response header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Revalidated => "REVALIDATED",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+impl std::fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where cached responses are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStorageKind {
+    /// In-process LRU
+    #[default]
+    Memory,
+    /// One file per URL under `CacheConfig::directory`
+    Disk,
+}
+
+/// HTTP cache configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Whether responses are cached at all
+    pub enabled: bool,
+    /// Storage backend
+    pub storage: CacheStorageKind,
+    /// Directory for disk storage
+    pub directory: PathBuf,
+    /// Maximum number of URLs kept in memory
+    pub max_entries: usize,
+    /// Responses with larger bodies are not stored
+    pub max_entry_size: usize,
+    /// Total body bytes kept in memory storage
+    pub max_total_bytes: usize,
+    /// Derive freshness from Last-Modified when no explicit lifetime is given
+    pub heuristic_freshness: bool,
+    /// Upper bound for heuristic freshness
+    pub max_heuristic_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            storage: CacheStorageKind::Memory,
+            directory: PathBuf::from("cache/http"),
+            max_entries: 1024,
+            max_entry_size: 10 * 1024 * 1024,
+            max_total_bytes: 256 * 1024 * 1024,
+            heuristic_freshness: true,
+            max_heuristic_ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// A stored response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Final URL after redirects
+    pub final_url: String,
+    /// Response headers (lowercase names)
+    pub headers: Vec<(String, String)>,
+    /// Request header values this variant was stored for (per `Vary`)
+    pub vary: Vec<(String, Option<String>)>,
+    /// When the response was received or last revalidated
+    pub stored_at: DateTime<Utc>,
+    /// Redirects that led from the request URL to `final_url`
+    #[serde(default)]
+    pub redirects: Vec<RedirectHop>,
+    /// Response body
+    #[serde(skip)]
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    /// Build an entry from response parts; the body is filled in later.
+    pub fn from_response(
+        status: u16,
+        final_url: &str,
+        headers: &reqwest::header::HeaderMap,
+        request: &FetchConfig,
+    ) -> Self {
+        let headers = super::streaming::header_pairs(headers);
+        let vary = vary_fields(&headers)
+            .map(|name| {
+                let value = request_header(request, final_url, &name);
+                (name, value)
+            })
+            .collect();
+
+        Self {
+            status,
+            final_url: final_url.to_string(),
+            headers,
+            vary,
+            stored_at: Utc::now(),
+            redirects: Vec::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// First value of response header `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether this variant was stored for the same `Vary` header values.
+    fn matches(&self, url: &str, request: &FetchConfig) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request_header(request, url, name) == *value)
+    }
+
+    /// Freshness lifetime per RFC 9111 section 4.2.1.
+    fn freshness_lifetime(&self, config: &CacheConfig) -> Duration {
+        let cache_control = CacheControl::parse(self.header("cache-control"));
+        if let Some(max_age) = cache_control.s_maxage.or(cache_control.max_age) {
+            return max_age;
+        }
+
+        let date = self
+            .header("date")
+            .and_then(parse_http_date)
+            .unwrap_or(self.stored_at);
+        if let Some(expires) = self.header("expires") {
+            // An invalid Expires value means "already expired"
+            return parse_http_date(expires)
+                .and_then(|expires| (expires - date).to_std().ok())
+                .unwrap_or_default();
+        }
+
+        if config.heuristic_freshness
+            && let Some(last_modified) = self.header("last-modified").and_then(parse_http_date)
+            && let Ok(since) = (date - last_modified).to_std()
+        {
+            return (since / 10).min(config.max_heuristic_ttl);
+        }
+        Duration::ZERO
+    }
+
+    /// Current age per RFC 9111 section 4.2.3.
+    fn age(&self, now: DateTime<Utc>) -> Duration {
+        let initial = self
+            .header("age")
+            .and_then(parse_delta_seconds)
+            .unwrap_or_default();
+        initial.saturating_add((now - self.stored_at).to_std().unwrap_or_default())
+    }
+
+    /// Whether the entry can be served for `request` without revalidation.
+    fn is_fresh(&self, config: &CacheConfig, request: &CacheControl) -> bool {
+        let response = CacheControl::parse(self.header("cache-control"));
+        if response.no_cache || request.no_cache {
+            return false;
+        }
+
+        let age = self.age(Utc::now());
+        if request.max_age.is_some_and(|max_age| age > max_age) {
+            return false;
+        }
+        age < self.freshness_lifetime(config)
+    }
+
+    /// Conditional request headers for revalidation.
+    pub fn validators(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = self.header("etag") {
+            headers.push(("if-none-match", etag.to_string()));
+        }
+        if let Some(last_modified) = self.header("last-modified") {
+            headers.push(("if-modified-since", last_modified.to_string()));
+        }
+        headers
+    }
+
+    /// Merge headers from a `304 Not Modified` response (section 4.3.4).
+    fn freshen(&mut self, headers: &reqwest::header::HeaderMap) {
+        for (name, value) in headers {
+            // Framing headers describe the empty 304 body, not ours
+            if matches!(name.as_str(), "content-length" | "transfer-encoding") {
+                continue;
+            }
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            self.headers.retain(|(n, _)| n != name.as_str());
+            self.headers
+                .push((name.as_str().to_string(), value.to_string()));
+        }
+        self.stored_at = Utc::now();
+    }
+}
+
+/// Parsed `Cache-Control` directives we act on.
+#[derive(Debug, Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<Duration>,
+    s_maxage: Option<Duration>,
+}
+
+impl CacheControl {
+    fn parse(value: Option<&str>) -> Self {
+        let mut cache_control = Self::default();
+        for directive in value.unwrap_or_default().split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "private" => cache_control.private = true,
+                "max-age" => cache_control.max_age = argument.and_then(parse_delta_seconds),
+                "s-maxage" => cache_control.s_maxage = argument.and_then(parse_delta_seconds),
+                _ => {}
+            }
+        }
+        cache_control
+    }
+
+    /// Directives a request sends via `Cache-Control` (and `Pragma: no-cache`).
+    fn from_request(request: &FetchConfig) -> Self {
+        let mut cache_control =
+            Self::parse(request_header(request, "", "cache-control").as_deref());
+        if request_header(request, "", "pragma").is_some_and(|pragma| pragma.contains("no-cache")) {
+            cache_control.no_cache = true;
+        }
+        cache_control
+    }
+}
+
+/// Parse delta-seconds (RFC 9111 section 1.2.2), capping values that are too
+/// large at 2^31 seconds.
+fn parse_delta_seconds(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let seconds = value.parse().unwrap_or(MAX_DELTA_SECONDS);
+    Some(Duration::from_secs(seconds.min(MAX_DELTA_SECONDS)))
+}
+
+/// Parse an HTTP-date (IMF-fixdate / RFC 2822 style).
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Lowercase field names listed in the `Vary` header(s).
+fn vary_fields(headers: &[(String, String)]) -> impl Iterator<Item = String> + '_ {
+    headers
+        .iter()
+        .filter(|(name, _)| name == "vary")
+        .flat_map(|(_, value)| value.split(','))
+        .map(|field| field.trim().to_ascii_lowercase())
+        .filter(|field| !field.is_empty())
+}
+
+/// Value of request header `name` as it will be sent for `request` to `url`.
+fn request_header(request: &FetchConfig, url: &str, name: &str) -> Option<String> {
+    if name.eq_ignore_ascii_case("user-agent") {
+        return Some(request.user_agent.clone());
+    }
+    if name.eq_ignore_ascii_case("cookie") {
+        return super::streaming::cookie_header(request, url);
+    }
+    request
+        .headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.clone())
+}
+
+/// Result of looking a request up in the cache.
+#[derive(Debug)]
+pub enum CacheLookup {
+    /// Request is not cacheable (method, body or `no-store`)
+    Bypass,
+    /// Nothing stored for this request
+    Miss,
+    /// Stored response that can be served as-is
+    Fresh(CachedResponse),
+    /// Stored response that must be revalidated first
+    Stale(CachedResponse),
+}
+
+/// Backend for cached responses, keyed by URL.
+pub trait CacheStorage: Send + Sync {
+    /// All stored variants for `key`.
+    fn get(&self, key: &str) -> Vec<CachedResponse>;
+
+    /// Replace the stored variants for `key`.
+    fn put(&self, key: &str, variants: Vec<CachedResponse>);
+
+    /// Drop everything stored for `key`.
+    fn remove(&self, key: &str);
+}
+
+/// In-memory LRU storage.
+///
+/// The least recently used URLs are dropped once more than `capacity` URLs
+/// or `max_total_bytes` of bodies are stored.
+pub struct MemoryStorage {
+    max_total_bytes: usize,
+    entries: Mutex<MemoryEntries>,
+}
+
+struct MemoryEntries {
+    entries: LruCache<String, Vec<CachedResponse>>,
+    /// Total body bytes of `entries`
+    bytes: usize,
+}
+
+impl MemoryStorage {
+    /// Create storage holding at most `capacity` URLs.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            max_total_bytes: usize::MAX,
+            entries: Mutex::new(MemoryEntries {
+                entries: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// Keep at most `total` bytes of response bodies.
+    pub fn with_max_total_bytes(mut self, total: usize) -> Self {
+        self.max_total_bytes = total;
+        self
+    }
+
+    /// Total body bytes currently stored.
+    pub fn bytes(&self) -> usize {
+        self.entries.lock().unwrap().bytes
+    }
+}
+
+/// Total body size of `variants`.
+fn body_bytes(variants: &[CachedResponse]) -> usize {
+    variants.iter().map(|response| response.body.len()).sum()
+}
+
+impl CacheStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Vec<CachedResponse> {
+        self.entries
+            .lock()
+            .unwrap()
+            .entries
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn put(&self, key: &str, variants: Vec<CachedResponse>) {
+        let size = body_bytes(&variants);
+        let mut entries = self.entries.lock().unwrap();
+        // Either the previous variants of `key` or the evicted LRU entry
+        if let Some((_, dropped)) = entries.entries.push(key.to_string(), variants) {
+            entries.bytes -= body_bytes(&dropped);
+        }
+        entries.bytes += size;
+        while entries.bytes > self.max_total_bytes {
+            let Some((_, dropped)) = entries.entries.pop_lru() else {
+                break;
+            };
+            entries.bytes -= body_bytes(&dropped);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(dropped) = entries.entries.pop(key) {
+            entries.bytes -= body_bytes(&dropped);
+        }
+    }
+}
+
+/// On-disk storage: one file per URL.
+///
+/// Each file starts with a JSON line holding the key, the variant metadata
+/// and body lengths, followed by the concatenated bodies.
+pub struct DiskStorage {
+    directory: PathBuf,
+}
+
+/// Header line of a cache file.
+#[derive(Serialize, Deserialize)]
+struct DiskRecord {
+    key: String,
+    variants: Vec<(CachedResponse, usize)>,
+}
+
+impl DiskStorage {
+    /// Create storage under `directory`, creating it if needed.
+    pub fn new(directory: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.cache", fnv1a(key)))
+    }
+
+    fn read(&self, key: &str) -> std::io::Result<Vec<CachedResponse>> {
+        let mut reader = BufReader::new(std::fs::File::open(self.path_for(key))?);
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let record: DiskRecord = serde_json::from_str(&header)?;
+        // Hash collision: the file belongs to another URL
+        if record.key != key {
+            return Ok(Vec::new());
+        }
+
+        let mut variants = Vec::with_capacity(record.variants.len());
+        for (mut response, length) in record.variants {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            response.body = Bytes::from(body);
+            variants.push(response);
+        }
+        Ok(variants)
+    }
+
+    fn write(&self, key: &str, variants: &[CachedResponse]) -> std::io::Result<()> {
+        let record = DiskRecord {
+            key: key.to_string(),
+            variants: variants
+                .iter()
+                .map(|response| (response.clone(), response.body.len()))
+                .collect(),
+        };
+
+        fs::write_atomic(&self.path_for(key), |file| {
+            serde_json::to_writer(&mut *file, &record)?;
+            file.write_all(b"\n")?;
+            for response in variants {
+                file.write_all(&response.body)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// FNV-1a hash of `key`: stable across builds, unlike std's default hasher.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl CacheStorage for DiskStorage {
+    fn get(&self, key: &str) -> Vec<CachedResponse> {
+        match self.read(key) {
+            Ok(variants) => variants,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable cache entry for {}: {}", key, e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn put(&self, key: &str, variants: Vec<CachedResponse>) {
+        if let Err(e) = self.write(key, &variants) {
+            tracing::warn!("Failed to write cache entry for {}: {}", key, e);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path_for(key));
+    }
+}
+
+/// HTTP response cache.
+pub struct HttpCache {
+    config: CacheConfig,
+    storage: Arc<dyn CacheStorage>,
+    /// Serialize updates of the same URL (striped by hash)
+    locks: Vec<Mutex<()>>,
+}
+
+impl std::fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpCache {
+    /// Create a cache with the storage selected in `config`.
+    pub fn new(config: CacheConfig) -> Result<Self, FetchError> {
+        let storage: Arc<dyn CacheStorage> = match config.storage {
+            CacheStorageKind::Memory => Arc::new(
+                MemoryStorage::new(config.max_entries).with_max_total_bytes(config.max_total_bytes),
+            ),
+            CacheStorageKind::Disk => {
+                Arc::new(DiskStorage::new(config.directory.clone()).map_err(|e| {
+                    FetchError::Other(format!(
+                        "Failed to open cache directory {}: {}",
+                        config.directory.display(),
+                        e
+                    ))
+                })?)
+            }
+        };
+        Ok(Self::with_storage(config, storage))
+    }
+
+    /// Create a cache backed by custom storage.
+    pub fn with_storage(config: CacheConfig, storage: Arc<dyn CacheStorage>) -> Self {
+        Self {
+            config,
+            storage,
+            locks: (0..STORE_LOCKS).map(|_| Mutex::new(())).collect(),
+        }
+    }
+
+    /// Get the cache configuration.
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Look up a stored response for `url` requested with `request`.
+    pub fn lookup(&self, url: &str, request: &FetchConfig) -> CacheLookup {
+        match Self::request_directives(request) {
+            Some(cache_control) => self.select(self.storage.get(url), url, request, &cache_control),
+            None => CacheLookup::Bypass,
+        }
+    }
+
+    /// Look up like [`HttpCache::lookup`]; disk reads run off the runtime.
+    pub async fn lookup_async(self: &Arc<Self>, url: &str, request: &FetchConfig) -> CacheLookup {
+        if matches!(self.config.storage, CacheStorageKind::Memory) {
+            return self.lookup(url, request);
+        }
+        let Some(cache_control) = Self::request_directives(request) else {
+            return CacheLookup::Bypass;
+        };
+        let (storage, key) = (self.storage.clone(), url.to_string());
+        let variants = tokio::task::spawn_blocking(move || storage.get(&key))
+            .await
+            .unwrap_or_default();
+        self.select(variants, url, request, &cache_control)
+    }
+
+    /// Cache directives of `request`, or `None` if it bypasses the cache.
+    fn request_directives(request: &FetchConfig) -> Option<CacheControl> {
+        if request.method != HttpMethod::Get || request.body.is_some() {
+            return None;
+        }
+        // Partial responses are neither stored nor served from a full one
+        if request
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("range"))
+        {
+            return None;
+        }
+        // Responses to credentials or cookies belong to their caller, and
+        // the cache is shared by every caller and session
+        if request.auth.is_some()
+            || request.cookie_jar.is_some()
+            || !request.cookies.is_empty()
+            || request.headers.keys().any(|name| {
+                ["authorization", "proxy-authorization", "cookie"]
+                    .iter()
+                    .any(|private| name.eq_ignore_ascii_case(private))
+            })
+        {
+            return None;
+        }
+        let cache_control = CacheControl::from_request(request);
+        if cache_control.no_store {
+            return None;
+        }
+        Some(cache_control)
+    }
+
+    /// Pick the stored variant matching `request`.
+    fn select(
+        &self,
+        variants: Vec<CachedResponse>,
+        url: &str,
+        request: &FetchConfig,
+        cache_control: &CacheControl,
+    ) -> CacheLookup {
+        let Some(response) = variants
+            .into_iter()
+            .find(|response| response.matches(url, request))
+        else {
+            return CacheLookup::Miss;
+        };
+
+        if response.is_fresh(&self.config, cache_control) {
+            CacheLookup::Fresh(response)
+        } else {
+            CacheLookup::Stale(response)
+        }
+    }
+
+    /// Whether a response with these headers may be stored.
+    pub fn is_storable(&self, status: u16, headers: &reqwest::header::HeaderMap) -> bool {
+        if !matches!(status, 200 | 203) {
+            return false;
+        }
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let cache_control = CacheControl::parse(header("cache-control"));
+        if cache_control.no_store || cache_control.private {
+            return false;
+        }
+        if header("vary").is_some_and(|vary| vary.split(',').any(|v| v.trim() == "*")) {
+            return false;
+        }
+        if header("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .is_some_and(|length| length > self.config.max_entry_size)
+        {
+            return false;
+        }
+
+        // Without freshness information or validators the entry is useless
+        header("cache-control").is_some()
+            || header("expires").is_some()
+            || header("etag").is_some()
+            || header("last-modified").is_some()
+    }
+
+    /// Store `response` as the variant for its `Vary` values.
+    pub fn store(&self, url: &str, response: CachedResponse) {
+        let _guard = self.lock(url);
+        let mut variants = self.storage.get(url);
+        variants.retain(|variant| variant.vary != response.vary);
+        variants.insert(0, response);
+        variants.truncate(MAX_VARIANTS);
+        self.storage.put(url, variants);
+    }
+
+    /// Store `response` like [`HttpCache::store`]; disk writes run off the
+    /// runtime when called from it.
+    pub fn store_in_background(self: &Arc<Self>, url: &str, response: CachedResponse) {
+        if matches!(self.config.storage, CacheStorageKind::Memory) {
+            self.store(url, response);
+            return;
+        }
+        let (cache, url) = (self.clone(), url.to_string());
+        fs::spawn_blocking_io(move || cache.store(&url, response));
+    }
+
+    /// Refresh a stale entry after `304 Not Modified` and store it in the
+    /// background.
+    pub fn revalidated(
+        self: &Arc<Self>,
+        url: &str,
+        mut response: CachedResponse,
+        headers: &reqwest::header::HeaderMap,
+    ) -> CachedResponse {
+        response.freshen(headers);
+        self.store_in_background(url, response.clone());
+        response
+    }
+
+    /// Drop stored responses for `url` (after an unsafe request succeeded).
+    pub fn invalidate(&self, url: &str) {
+        let _guard = self.lock(url);
+        self.storage.remove(url);
+    }
+
+    /// Lock guarding the stored variants of `url`.
+    fn lock(&self, url: &str) -> std::sync::MutexGuard<'_, ()> {
+        self.locks[(fnv1a(url) % STORE_LOCKS as u64) as usize]
+            .lock()
+            .unwrap()
+    }
+}
+
+/// Body stream that stores the response in the cache once fully read.
+///
+/// Responses that error out or exceed `max_entry_size` are not stored.
+pub struct CachingStream<S> {
+    inner: S,
+    buffer: BytesMut,
+    pending: Option<(Arc<HttpCache>, String, CachedResponse)>,
+}
+
+impl<S> CachingStream<S> {
+    /// Wrap `inner`, storing `response` under `url` when it completes.
+    pub fn new(inner: S, cache: Arc<HttpCache>, url: &str, response: CachedResponse) -> Self {
+        Self {
+            inner,
+            buffer: BytesMut::new(),
+            pending: Some((cache, url.to_string(), response)),
+        }
+    }
+}
+
+impl<S> Stream for CachingStream<S>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Unpin,
+{
+    type Item = Result<Bytes, reqwest::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = Pin::new(&mut this.inner).poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some((cache, _, _)) = &this.pending {
+                    if this.buffer.len() + chunk.len() > cache.config.max_entry_size {
+                        this.pending = None;
+                        this.buffer = BytesMut::new();
+                    } else {
+                        this.buffer.extend_from_slice(chunk);
+                    }
+                }
+            }
+            Poll::Ready(Some(Err(_))) => this.pending = None,
+            Poll::Ready(None) => {
+                if let Some((cache, url, mut response)) = this.pending.take() {
+                    response.body = std::mem::take(&mut this.buffer).freeze();
+                    cache.store_in_background(&url, response);
+                }
+            }
+            Poll::Pending => {}
+        }
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    fn cached(pairs: &[(&'static str, &str)], request: &FetchConfig) -> CachedResponse {
+        let mut response =
+            CachedResponse::from_response(200, "https://example.com/", &headers(pairs), request);
+        response.body = Bytes::from_static(b"<html></html>");
+        response
+    }
+
+    #[test]
+    fn test_freshness_and_revalidation() {
+        let cache = Arc::new(HttpCache::new(CacheConfig::default()).unwrap());
+        let request = FetchConfig::default();
+        let url = "https://example.com/";
+
+        cache.store(url, cached(&[("cache-control", "max-age=60")], &request));
+        assert!(matches!(cache.lookup(url, &request), CacheLookup::Fresh(_)));
+
+        // Request directives force revalidation or skip the cache
+        let mut no_cache = FetchConfig::default();
+        no_cache
+            .headers
+            .insert("Cache-Control".into(), "no-cache".into());
+        assert!(matches!(
+            cache.lookup(url, &no_cache),
+            CacheLookup::Stale(_)
+        ));
+        no_cache
+            .headers
+            .insert("Cache-Control".into(), "no-store".into());
+        assert!(matches!(cache.lookup(url, &no_cache), CacheLookup::Bypass));
+
+        // Expired entries are stale and carry validators
+        let date = Utc::now().to_rfc2822();
+        cache.store(
+            url,
+            cached(
+                &[("date", &date), ("expires", &date), ("etag", "\"v1\"")],
+                &request,
+            ),
+        );
+        let CacheLookup::Stale(stale) = cache.lookup(url, &request) else {
+            panic!("expected stale entry");
+        };
+        assert_eq!(
+            stale.validators(),
+            vec![("if-none-match", "\"v1\"".to_string())]
+        );
+
+        // A 304 refreshes the headers and keeps the body
+        let refreshed = cache.revalidated(
+            url,
+            stale,
+            &headers(&[("cache-control", "max-age=60"), ("content-length", "0")]),
+        );
+        assert_eq!(refreshed.body, Bytes::from_static(b"<html></html>"));
+        assert!(matches!(cache.lookup(url, &request), CacheLookup::Fresh(_)));
+
+        // Absurd Age values are capped instead of overflowing
+        for age in ["18446744073709551615", "99999999999999999999999"] {
+            let mut response = cached(&[("cache-control", "max-age=60"), ("age", age)], &request);
+            response.stored_at -= chrono::Duration::seconds(1);
+            cache.store(url, response);
+            assert!(matches!(cache.lookup(url, &request), CacheLookup::Stale(_)));
+        }
+    }
+
+    #[test]
+    fn test_vary_selects_variant() {
+        let cache = HttpCache::new(CacheConfig::default()).unwrap();
+        let url = "https://example.com/";
+        let mut english = FetchConfig::default();
+        english
+            .headers
+            .insert("Accept-Language".into(), "en".into());
+        let mut german = FetchConfig::default();
+        german.headers.insert("accept-language".into(), "de".into());
+
+        let vary = [("cache-control", "max-age=60"), ("vary", "Accept-Language")];
+        cache.store(url, cached(&vary, &english));
+        assert!(matches!(cache.lookup(url, &english), CacheLookup::Fresh(_)));
+        assert!(matches!(cache.lookup(url, &german), CacheLookup::Miss));
+
+        cache.store(url, cached(&vary, &german));
+        assert!(matches!(cache.lookup(url, &english), CacheLookup::Fresh(_)));
+        assert!(matches!(cache.lookup(url, &german), CacheLookup::Fresh(_)));
+
+        assert!(!cache.is_storable(200, &headers(&[("vary", "*"), ("etag", "x")])));
+        assert!(!cache.is_storable(200, &headers(&[("cache-control", "no-store")])));
+        assert!(!cache.is_storable(200, &headers(&[("cache-control", "private, max-age=60")])));
+        assert!(!cache.is_storable(200, &headers(&[])));
+    }
+
+    #[test]
+    fn test_sessions_and_credentials_bypass_the_shared_cache() {
+        use crate::domain::fetch::cookies::CookieJar;
+
+        let cache = HttpCache::new(CacheConfig::default()).unwrap();
+        let url = "https://example.com/";
+        let anonymous = FetchConfig::default();
+        cache.store(url, cached(&[("cache-control", "max-age=60")], &anonymous));
+        assert!(matches!(
+            cache.lookup(url, &anonymous),
+            CacheLookup::Fresh(_)
+        ));
+
+        // Neither session sees the other's (or the anonymous) response
+        let alice = FetchConfig {
+            cookie_jar: Some(Arc::new(CookieJar::new())),
+            ..FetchConfig::default()
+        };
+        let bob = FetchConfig {
+            cookie_jar: Some(Arc::new(CookieJar::new())),
+            ..FetchConfig::default()
+        };
+        assert!(matches!(cache.lookup(url, &alice), CacheLookup::Bypass));
+        assert!(matches!(cache.lookup(url, &bob), CacheLookup::Bypass));
+
+        let mut cookies = FetchConfig::default();
+        cookies.cookies.insert("sid".into(), "1".into());
+        assert!(matches!(cache.lookup(url, &cookies), CacheLookup::Bypass));
+        let mut authorization = FetchConfig::default();
+        authorization
+            .headers
+            .insert("Authorization".into(), "Bearer secret".into());
+        assert!(matches!(
+            cache.lookup(url, &authorization),
+            CacheLookup::Bypass
+        ));
+    }
+
+    #[test]
+    fn test_disk_storage_round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("scapi-cache-test-{}", uuid::Uuid::new_v4()));
+        let storage = DiskStorage::new(directory.clone()).unwrap();
+        let request = FetchConfig::default();
+
+        let mut second = cached(&[("etag", "\"b\"")], &request);
+        second.body = Bytes::from_static(b"second body");
+        storage.put(
+            "https://example.com/",
+            vec![cached(&[("etag", "\"a\"")], &request), second],
+        );
+
+        let variants = storage.get("https://example.com/");
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].body, Bytes::from_static(b"<html></html>"));
+        assert_eq!(variants[1].body, Bytes::from_static(b"second body"));
+        assert_eq!(variants[1].header("etag"), Some("\"b\""));
+        assert!(storage.get("https://example.com/other").is_empty());
+
+        storage.remove("https://example.com/");
+        assert!(storage.get("https://example.com/").is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_memory_storage_byte_budget() {
+        let request = FetchConfig::default();
+        let response = cached(&[("cache-control", "max-age=60")], &request);
+        let size = response.body.len();
+        let storage = MemoryStorage::new(16).with_max_total_bytes(2 * size);
+
+        storage.put("https://example.com/a", vec![response.clone()]);
+        storage.put("https://example.com/b", vec![response.clone()]);
+        storage.get("https://example.com/a");
+        storage.put("https://example.com/c", vec![response.clone()]);
+        assert_eq!(storage.bytes(), 2 * size);
+        assert!(storage.get("https://example.com/b").is_empty());
+        assert_eq!(storage.get("https://example.com/a").len(), 1);
+
+        // Replacing and removing variants releases their bytes
+        storage.put("https://example.com/a", Vec::new());
+        assert_eq!(storage.bytes(), size);
+        storage.remove("https://example.com/c");
+        assert_eq!(storage.bytes(), 0);
+    }
+
+    #[test]
+    fn test_concurrent_stores_keep_every_variant() {
+        let directory =
+            std::env::temp_dir().join(format!("scapi-cache-test-{}", uuid::Uuid::new_v4()));
+        let config = CacheConfig {
+            storage: CacheStorageKind::Disk,
+            directory: directory.clone(),
+            ..CacheConfig::default()
+        };
+        let cache = Arc::new(HttpCache::new(config).unwrap());
+        let url = "https://example.com/";
+        let vary = [("cache-control", "max-age=60"), ("vary", "Accept-Language")];
+
+        let threads: Vec<_> = (0..MAX_VARIANTS)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    let mut request = FetchConfig::default();
+                    request
+                        .headers
+                        .insert("Accept-Language".into(), format!("lang-{}", i));
+                    cache.store(url, cached(&vary, &request));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(cache.storage.get(url).len(), MAX_VARIANTS);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disk_lookup_off_the_runtime() {
+        let directory =
+            std::env::temp_dir().join(format!("scapi-cache-test-{}", uuid::Uuid::new_v4()));
+        let config = CacheConfig {
+            storage: CacheStorageKind::Disk,
+            directory: directory.clone(),
+            ..CacheConfig::default()
+        };
+        let cache = Arc::new(HttpCache::new(config).unwrap());
+        let request = FetchConfig::default();
+        let url = "https://example.com/";
+
+        assert!(matches!(
+            cache.lookup_async(url, &request).await,
+            CacheLookup::Miss
+        ));
+        cache.store(url, cached(&[("cache-control", "max-age=60")], &request));
+        let CacheLookup::Fresh(hit) = cache.lookup_async(url, &request).await else {
+            panic!("expected fresh entry");
+        };
+        assert_eq!(hit.body, Bytes::from_static(b"<html></html>"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
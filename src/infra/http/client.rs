@@ -1,64 +1,120 @@
 //! HTTP client wrapper.
 
-use reqwest::{Client, Response};
-use std::time::Duration;
+use reqwest::Response;
+use std::sync::Arc;
 
+use crate::domain::fetch::breaker::CircuitBreakers;
 use crate::domain::fetch::config::FetchConfig;
+use crate::domain::fetch::egress::EgressPolicy;
 use crate::domain::fetch::error::FetchError;
+use crate::domain::fetch::robots::RobotsCache;
 
+use crate::infra::har::HarRecorder;
+use crate::infra::http::cache::HttpCache;
+use crate::infra::http::local::LocalSource;
+use crate::infra::http::pool::ClientPool;
+use crate::infra::http::resolver::{DnsResolver, egress_violation};
 use crate::infra::http::streaming::StreamingClient;
+use crate::infra::http::tls::TlsSettings;
+use crate::infra::http::transport::Transport;
+use crate::infra::warc::WarcWriter;
 
 /// HTTP client wrapper.
 #[derive(Debug, Clone)]
 pub struct HttpClient {
-    /// Inner reqwest client
-    client: Client,
+    /// Pooled reqwest clients, shared with the streaming client
+    pool: ClientPool,
+    /// Configuration used by `get`/`post`
+    default_config: FetchConfig,
     streaming_client: StreamingClient,
 }
 
 impl HttpClient {
     /// Create a new HTTP client with default configuration.
     pub fn new() -> Result<Self, FetchError> {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .map_err(|e| FetchError::NetworkError(e.to_string()))?;
+        Self::with_config(&FetchConfig::default())
+    }
+
+    /// Create a new HTTP client with custom configuration.
+    ///
+    /// The configuration is the default for `get`/`post`; `fetch` and the
+    /// streaming client always use the configuration passed per call.
+    pub fn with_config(config: &FetchConfig) -> Result<Self, FetchError> {
+        let pool = ClientPool::default();
 
-        let streaming_client = StreamingClient::new(100 * 1024 * 1024)?; // 100MB default
+        // Build eagerly so an invalid configuration fails at construction
+        pool.client_for(config)?;
+
+        let streaming_client = StreamingClient::with_pool(pool.clone());
 
         Ok(Self {
-            client,
+            pool,
+            default_config: config.clone(),
             streaming_client,
         })
     }
 
-    /// Create a new HTTP client with custom configuration.
-    pub fn with_config(config: &FetchConfig) -> Result<Self, FetchError> {
-        let mut builder = Client::builder()
-            .timeout(config.timeout)
-            .connect_timeout(config.connect_timeout)
-            .user_agent(&config.user_agent);
+    /// Cache responses in `cache` (RFC 9111 semantics).
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.streaming_client = self.streaming_client.with_cache(Arc::new(cache));
+        self
+    }
 
-        if !config.verify_tls {
-            builder = builder.danger_accept_invalid_certs(true);
-        }
+    /// Enforce `policy` on every request made through this client.
+    pub fn with_egress(mut self, policy: EgressPolicy) -> Self {
+        self.pool = self.pool.with_egress(Arc::new(policy));
+        self.streaming_client = self.streaming_client.with_client_pool(self.pool.clone());
+        self
+    }
 
-        if config.follow_redirects {
-            builder = builder.redirect(reqwest::redirect::Policy::limited(config.max_redirects));
-        } else {
-            builder = builder.redirect(reqwest::redirect::Policy::none());
-        }
+    /// Resolve hosts through `dns` (overrides, name servers, cache).
+    pub fn with_dns(mut self, dns: DnsResolver) -> Self {
+        self.pool = self.pool.with_dns(Arc::new(dns));
+        self.streaming_client = self.streaming_client.with_client_pool(self.pool.clone());
+        self
+    }
 
-        let client = builder
-            .build()
-            .map_err(|e| FetchError::NetworkError(e.to_string()))?;
+    /// Apply `tls` (trusted roots, client certificates, pins) to every request.
+    pub fn with_tls(mut self, tls: TlsSettings) -> Self {
+        self.pool = self.pool.with_tls(Arc::new(tls));
+        self.streaming_client = self.streaming_client.with_client_pool(self.pool.clone());
+        self
+    }
 
-        let streaming_client = StreamingClient::new(config.max_content_size)?;
+    /// Serve `file://`, `data:` and fixture URLs from `local`.
+    pub fn with_local_sources(mut self, local: LocalSource) -> Self {
+        self.streaming_client = self.streaming_client.with_local_sources(Arc::new(local));
+        self
+    }
 
-        Ok(Self {
-            client,
-            streaming_client,
-        })
+    /// Record every network exchange to `warc`.
+    pub fn with_warc(mut self, warc: WarcWriter) -> Self {
+        self.streaming_client = self.streaming_client.with_warc(Arc::new(warc));
+        self
+    }
+
+    /// Record every network exchange, with timings, to `har`.
+    pub fn with_har(mut self, har: Arc<HarRecorder>) -> Self {
+        self.streaming_client = self.streaming_client.with_har(har);
+        self
+    }
+
+    /// Send requests through `transport` (live, recording or replaying).
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.streaming_client = self.streaming_client.with_transport(transport);
+        self
+    }
+
+    /// Check every request, redirect hops included, against `breakers`.
+    pub fn with_breakers(mut self, breakers: Arc<CircuitBreakers>) -> Self {
+        self.streaming_client = self.streaming_client.with_breakers(breakers);
+        self
+    }
+
+    /// Check every redirect target against its robots.txt in `robots`.
+    pub fn with_robots(mut self, robots: Arc<RobotsCache>) -> Self {
+        self.streaming_client = self.streaming_client.with_robots(robots);
+        self
     }
 
     /// Fetch content from a URL.
@@ -73,22 +129,44 @@ impl HttpClient {
     }
 
     /// Perform a GET request.
+    ///
+    /// Redirects are followed by reqwest; each target is checked against the
+    /// egress policy.
     pub async fn get(&self, url: &str) -> Result<Response, FetchError> {
-        self.client
+        self.check_egress(url).await?;
+        self.pool
+            .client_for_url(&self.default_config, url)?
             .get(url)
+            .timeout(self.default_config.timeout)
             .send()
             .await
-            .map_err(|e| FetchError::NetworkError(e.to_string()))
+            .map_err(send_error)
     }
 
     /// Perform a POST request.
     pub async fn post(&self, url: &str, body: &str) -> Result<Response, FetchError> {
-        self.client
+        self.check_egress(url).await?;
+        self.pool
+            .client_for_url(&self.default_config, url)?
             .post(url)
+            .timeout(self.default_config.timeout)
             .body(body.to_string())
             .send()
             .await
-            .map_err(|e| FetchError::NetworkError(e.to_string()))
+            .map_err(send_error)
+    }
+
+    /// Check `url` against the egress policy; reqwest connects to IP
+    /// literals without asking the resolver.
+    async fn check_egress(&self, url: &str) -> Result<(), FetchError> {
+        match self.pool.egress() {
+            Some(egress) => {
+                egress
+                    .check_target(url, self.default_config.proxy.as_ref())
+                    .await
+            }
+            None => Ok(()),
+        }
     }
 
     /// Get streaming client for manual control
@@ -97,6 +175,11 @@ impl HttpClient {
     }
 }
 
+/// Map a reqwest error, keeping egress violations of redirect targets.
+fn send_error(error: reqwest::Error) -> FetchError {
+    egress_violation(&error).unwrap_or_else(|| FetchError::NetworkError(error.to_string()))
+}
+
 impl Default for HttpClient {
     fn default() -> Self {
         Self::new().expect("Failed to create HTTP client")
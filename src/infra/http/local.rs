@@ -0,0 +1,415 @@
+//! Local sources: `file://` and `data:` URLs and fixture directories.
+//!
+//! Local sources never touch the network. `file://` URLs are confined to a
+//! configured root directory, `data:` URLs carry their body inline, and
+//! fixture hosts map `http(s)://host/path` onto files under a directory so
+//! saved pages can be served under their original URLs.
+
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::domain::fetch::error::FetchError;
+
+/// File served for directory URLs.
+const INDEX_FILE: &str = "index.html";
+
+/// Local source configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalSourceConfig {
+    /// Directory `file://` URLs are confined to (`None` disables `file://`)
+    pub file_root: Option<PathBuf>,
+    /// Whether `data:` URLs are served
+    pub data_urls: bool,
+    /// Hosts served from local directories instead of the network
+    pub fixtures: HashMap<String, PathBuf>,
+}
+
+impl Default for LocalSourceConfig {
+    fn default() -> Self {
+        Self {
+            file_root: None,
+            data_urls: true,
+            fixtures: HashMap::new(),
+        }
+    }
+}
+
+/// Body and metadata of a local source.
+#[derive(Debug, Clone)]
+pub struct LocalResponse {
+    /// 200, or 404 when no file exists for the URL
+    pub status: u16,
+    /// Media type from the `data:` URL or guessed from the file extension
+    pub content_type: Option<String>,
+    /// Response body
+    pub body: Bytes,
+}
+
+/// Resolver for local sources.
+#[derive(Debug, Clone, Default)]
+pub struct LocalSource {
+    config: LocalSourceConfig,
+}
+
+impl LocalSource {
+    /// Create a local source from its configuration.
+    pub fn new(mut config: LocalSourceConfig) -> Self {
+        config.fixtures = config
+            .fixtures
+            .into_iter()
+            .map(|(host, dir)| (host.trim_end_matches('.').to_ascii_lowercase(), dir))
+            .collect();
+        Self { config }
+    }
+
+    /// Source configuration.
+    pub fn config(&self) -> &LocalSourceConfig {
+        &self.config
+    }
+
+    /// Whether `url` is served locally rather than over the network.
+    pub fn handles(&self, url: &url::Url) -> bool {
+        match url.scheme() {
+            "file" | "data" => true,
+            "http" | "https" => self.fixture_root(url).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Load `url`, failing if its body exceeds `max_size` bytes.
+    pub async fn fetch(
+        &self,
+        url: &url::Url,
+        max_size: usize,
+    ) -> Result<LocalResponse, FetchError> {
+        match url.scheme() {
+            "data" if self.config.data_urls => {
+                let response = parse_data_url(url)?;
+                check_size(response.body.len() as u64, max_size)?;
+                Ok(response)
+            }
+            "data" => Err(FetchError::UnsupportedProtocol(
+                "data: URLs are disabled".to_string(),
+            )),
+            "file" => {
+                let root = self.config.file_root.as_ref().ok_or_else(|| {
+                    FetchError::UnsupportedProtocol(
+                        "file:// URLs are disabled (no file root configured)".to_string(),
+                    )
+                })?;
+                let path = url
+                    .to_file_path()
+                    .map_err(|_| FetchError::InvalidUrl(format!("{} is not a local path", url)))?;
+                read_confined(root, &path, max_size).await
+            }
+            _ => {
+                let root = self.fixture_root(url).ok_or_else(|| {
+                    FetchError::UnsupportedProtocol(format!("{} is not a local source", url))
+                })?;
+                let mut path = root.to_path_buf();
+                for segment in url.path_segments().into_iter().flatten() {
+                    let segment = percent_encoding::percent_decode_str(segment).decode_utf8_lossy();
+                    if !segment.is_empty() {
+                        path.push(segment.as_ref());
+                    }
+                }
+                read_confined(root, &path, max_size).await
+            }
+        }
+    }
+
+    /// Fixture directory for the host of `url`, if any.
+    fn fixture_root(&self, url: &url::Url) -> Option<&Path> {
+        let host = url.host_str()?.trim_end_matches('.').to_ascii_lowercase();
+        self.config.fixtures.get(&host).map(PathBuf::as_path)
+    }
+}
+
+/// Read `path` (or its index file) if it lies inside `root`.
+///
+/// The path is checked lexically first, then canonicalized with its index
+/// file appended, so `..` segments and symlinks cannot escape the root. A
+/// missing file yields a 404 response only when its nearest existing
+/// ancestor is inside the root, so outside paths cannot be probed.
+async fn read_confined(
+    root: &Path,
+    path: &Path,
+    max_size: usize,
+) -> Result<LocalResponse, FetchError> {
+    let canonical_root = tokio::fs::canonicalize(root)
+        .await
+        .map_err(|e| FetchError::Other(format!("local root {}: {}", root.display(), e)))?;
+    let outside = |path: &Path| {
+        FetchError::EgressDenied(format!(
+            "{} is outside {}",
+            path.display(),
+            canonical_root.display()
+        ))
+    };
+
+    let mut path = normalize(path);
+    let lexical_root = std::path::absolute(root).map(|root| normalize(&root));
+    if !path.starts_with(&canonical_root) && !lexical_root.is_ok_and(|root| path.starts_with(root))
+    {
+        return Err(outside(&path));
+    }
+    if tokio::fs::metadata(&path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        path.push(INDEX_FILE);
+    }
+
+    let path = match tokio::fs::canonicalize(&path).await {
+        Ok(path) => path,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let mut ancestors = path.ancestors().skip(1);
+            let existing = loop {
+                let Some(ancestor) = ancestors.next() else {
+                    return Err(outside(&path));
+                };
+                if let Ok(ancestor) = tokio::fs::canonicalize(ancestor).await {
+                    break ancestor;
+                }
+            };
+            if !existing.starts_with(&canonical_root) {
+                return Err(outside(&path));
+            }
+            return Ok(not_found());
+        }
+        Err(e) => return Err(FetchError::Other(format!("{}: {}", path.display(), e))),
+    };
+    if !path.starts_with(&canonical_root) {
+        return Err(outside(&path));
+    }
+
+    let metadata = match tokio::fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(not_found()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(not_found()),
+        Err(e) => return Err(FetchError::Other(format!("{}: {}", path.display(), e))),
+    };
+    check_size(metadata.len(), max_size)?;
+
+    let body = tokio::fs::read(&path)
+        .await
+        .map_err(|e| FetchError::Other(format!("{}: {}", path.display(), e)))?;
+    Ok(LocalResponse {
+        status: 200,
+        content_type: Some(guess_content_type(&path).to_string()),
+        body: Bytes::from(body),
+    })
+}
+
+/// Resolve `.` and `..` segments without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn not_found() -> LocalResponse {
+    LocalResponse {
+        status: 404,
+        content_type: Some("text/plain; charset=utf-8".to_string()),
+        body: Bytes::from_static(b"Not Found"),
+    }
+}
+
+fn check_size(len: u64, max_size: usize) -> Result<(), FetchError> {
+    if len > max_size as u64 {
+        return Err(FetchError::ContentTooLarge(format!(
+            "Local body of {} bytes exceeds limit {}",
+            len, max_size
+        )));
+    }
+    Ok(())
+}
+
+/// Decode a `data:[<mediatype>][;base64],<data>` URL (RFC 2397).
+fn parse_data_url(url: &url::Url) -> Result<LocalResponse, FetchError> {
+    // The fragment is not part of the data
+    let raw = &url.as_str()["data:".len()..];
+    let raw = raw.split_once('#').map_or(raw, |(data, _)| data);
+    let (meta, data) = raw
+        .split_once(',')
+        .ok_or_else(|| FetchError::InvalidUrl("data: URL without ','".to_string()))?;
+
+    let meta = percent_encoding::percent_decode_str(meta).decode_utf8_lossy();
+    let (media_type, is_base64) = match meta.trim().strip_suffix(";base64") {
+        Some(media_type) => (media_type.trim(), true),
+        None => (meta.trim(), false),
+    };
+    let content_type = if media_type.is_empty() {
+        "text/plain;charset=US-ASCII".to_string()
+    } else if media_type.starts_with(';') {
+        format!("text/plain{}", media_type)
+    } else {
+        media_type.to_string()
+    };
+
+    let bytes: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+    let body = if is_base64 {
+        let compact: Vec<u8> = bytes
+            .into_iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(compact)
+            .map_err(|e| FetchError::InvalidUrl(format!("data: URL base64: {}", e)))?
+    } else {
+        bytes
+    };
+
+    Ok(LocalResponse {
+        status: 200,
+        content_type: Some(content_type),
+        body: Bytes::from(body),
+    })
+}
+
+/// Content type for a file, from its extension.
+fn guess_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "xhtml" => "application/xhtml+xml",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "gz" => "application/gzip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> url::Url {
+        url::Url::parse(url).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_data_urls() {
+        let source = LocalSource::default();
+
+        let plain = source
+            .fetch(&url("data:,Hello%2C%20World"), 1024)
+            .await
+            .unwrap();
+        assert_eq!(plain.body, Bytes::from_static(b"Hello, World"));
+        assert_eq!(
+            plain.content_type.as_deref(),
+            Some("text/plain;charset=US-ASCII")
+        );
+
+        let html = source
+            .fetch(&url("data:text/html;base64,PGgxPmhpPC9oMT4="), 1024)
+            .await
+            .unwrap();
+        assert_eq!(html.body, Bytes::from_static(b"<h1>hi</h1>"));
+        assert_eq!(html.content_type.as_deref(), Some("text/html"));
+
+        assert!(matches!(
+            source.fetch(&url("data:,0123456789"), 4).await,
+            Err(FetchError::ContentTooLarge(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_file_root_and_fixtures_are_confined() {
+        let base = std::env::temp_dir().join(format!("scapi-local-{}", uuid::Uuid::new_v4()));
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/index.html"), "<p>docs</p>").unwrap();
+        std::fs::write(base.join("secret.txt"), "secret").unwrap();
+
+        let source = LocalSource::new(LocalSourceConfig {
+            file_root: Some(root.clone()),
+            fixtures: [("Example.COM".to_string(), root.clone())].into(),
+            ..Default::default()
+        });
+
+        let file_url = url::Url::from_file_path(root.join("docs")).unwrap();
+        let page = source.fetch(&file_url, 1024).await.unwrap();
+        assert_eq!(page.status, 200);
+        assert_eq!(page.body, Bytes::from_static(b"<p>docs</p>"));
+        assert_eq!(page.content_type.as_deref(), Some("text/html"));
+
+        let escape = url::Url::from_file_path(root.join("../secret.txt")).unwrap();
+        assert!(source.fetch(&escape, 1024).await.is_err());
+
+        let fixture = url("https://example.com/docs/?q=1");
+        assert!(source.handles(&fixture));
+        assert_eq!(source.fetch(&fixture, 1024).await.unwrap().status, 200);
+        let missing = source.fetch(&url("https://example.com/nope"), 1024).await;
+        assert_eq!(missing.unwrap().status, 404);
+        let encoded = url("https://example.com/..%2Fsecret.txt");
+        assert!(source.fetch(&encoded, 1024).await.is_err());
+        assert!(!source.handles(&url("https://example.org/")));
+
+        // Outside paths are denied whether or not they exist
+        let missing_outside = url::Url::from_file_path(base.join("nope.txt")).unwrap();
+        assert!(matches!(
+            source.fetch(&missing_outside, 1024).await,
+            Err(FetchError::EgressDenied(_))
+        ));
+
+        #[cfg(unix)]
+        {
+            std::fs::create_dir_all(root.join("linked")).unwrap();
+            std::os::unix::fs::symlink(base.join("secret.txt"), root.join("linked/index.html"))
+                .unwrap();
+            std::os::unix::fs::symlink(&base, root.join("up")).unwrap();
+            for escape in [
+                "linked",
+                "linked/index.html",
+                "up/secret.txt",
+                "up/nope.txt",
+            ] {
+                let escape = url::Url::from_file_path(root.join(escape)).unwrap();
+                assert!(
+                    matches!(
+                        source.fetch(&escape, 1024).await,
+                        Err(FetchError::EgressDenied(_))
+                    ),
+                    "{}",
+                    escape
+                );
+            }
+        }
+
+        let disabled = LocalSource::default();
+        assert!(matches!(
+            disabled.fetch(&file_url, 1024).await,
+            Err(FetchError::UnsupportedProtocol(_))
+        ));
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -1,8 +1,23 @@
 //! HTTP client infrastructure.
 
+pub mod cache;
 pub mod client;
+pub mod local;
+pub mod pool;
+pub mod resolver;
 pub mod streaming;
+pub mod tls;
+pub mod transport;
 
 // Re-exports
+pub use cache::{CacheConfig, CacheStatus, HttpCache};
 pub use client::HttpClient;
+pub use local::{LocalSource, LocalSourceConfig};
+pub use pool::ClientPool;
+pub use resolver::{DnsConfig, DnsResolver, IpPreference, Resolution, ResolutionSource};
 pub use streaming::{ResponseStream, StreamingClient, StreamingFetchResult};
+pub use tls::{HostTlsConfig, TlsConfig, TlsSettings};
+pub use transport::{
+    FixtureStore, LiveTransport, RecordTransport, ReplayTransport, Transport, TransportConfig,
+    TransportMode, TransportResponse,
+};
@@ -0,0 +1,316 @@
+//! Pool of reqwest clients keyed by client-level fetch settings.
+
+use lru::LruCache;
+use reqwest::Client;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::domain::fetch::config::FetchConfig;
+use crate::domain::fetch::egress::EgressPolicy;
+use crate::domain::fetch::error::FetchError;
+use crate::domain::fetch::proxy::ProxyConfig;
+use crate::infra::http::resolver::{ConnectResolver, DnsResolver};
+use crate::infra::http::tls::TlsSettings;
+
+/// Default number of distinct clients kept alive.
+const DEFAULT_POOL_CAPACITY: usize = 32;
+
+/// The subset of `FetchConfig` that has to be baked into a `reqwest::Client`.
+///
+/// Per-request settings (total timeout, size limit) are applied on each call
+/// and are deliberately not part of the key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClientKey {
+    user_agent: String,
+    follow_redirects: bool,
+    max_redirects: usize,
+    verify_tls: bool,
+    connect_timeout: Duration,
+    proxy: Option<ProxyConfig>,
+    /// Host pattern of the TLS settings applied
+    tls_profile: Option<String>,
+}
+
+impl ClientKey {
+    /// Derive the client key for a fetch configuration.
+    pub fn from_config(config: &FetchConfig) -> Self {
+        Self {
+            user_agent: config.user_agent.clone(),
+            follow_redirects: config.follow_redirects,
+            max_redirects: config.max_redirects,
+            verify_tls: config.verify_tls,
+            connect_timeout: config.connect_timeout,
+            proxy: config.proxy.clone(),
+            tls_profile: None,
+        }
+    }
+
+    /// Build a new client for this key, resolving hosts through `dns`,
+    /// enforcing `egress` if set and applying `tls`.
+    fn build(
+        &self,
+        egress: Option<&Arc<EgressPolicy>>,
+        dns: &Arc<DnsResolver>,
+        tls: &TlsSettings,
+    ) -> Result<Client, FetchError> {
+        let mut builder = Client::builder()
+            .user_agent(&self.user_agent)
+            .connect_timeout(self.connect_timeout)
+            .dns_resolver(Arc::new(ConnectResolver::new(dns.clone(), egress.cloned())));
+
+        if !tls.is_default() {
+            builder = builder.use_preconfigured_tls(
+                tls.client_config(self.tls_profile.as_deref(), self.verify_tls)?,
+            );
+        } else if !self.verify_tls {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+            let mut reqwest_proxy = reqwest::Proxy::all(&proxy.url).map_err(|e| {
+                FetchError::InvalidRequest(format!("proxy {}: {}", proxy.display_url(), e))
+            })?;
+            if let (Some(username), Some(password)) = (&proxy.username, &proxy.password) {
+                reqwest_proxy = reqwest_proxy.basic_auth(username, password);
+            }
+            builder = builder.proxy(reqwest_proxy);
+        }
+
+        if self.follow_redirects {
+            // reqwest connects to IP literals without asking the resolver, so
+            // redirect targets are checked against the policy here as well
+            let max_redirects = self.max_redirects;
+            let egress = egress.cloned();
+            builder = builder.redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() > max_redirects {
+                    return attempt.error(FetchError::TooManyRedirects(format!(
+                        "more than {} redirects",
+                        max_redirects
+                    )));
+                }
+                match egress
+                    .as_ref()
+                    .map(|egress| egress.check_url(attempt.url()))
+                {
+                    Some(Err(e)) => attempt.error(e),
+                    _ => attempt.follow(),
+                }
+            }));
+        } else {
+            builder = builder.redirect(reqwest::redirect::Policy::none());
+        }
+
+        builder
+            .build()
+            .map_err(|e| FetchError::NetworkError(e.to_string()))
+    }
+}
+
+/// LRU pool of reqwest clients, one per distinct `ClientKey`.
+///
+/// Cloning the pool is cheap and shares the underlying clients, so connection
+/// reuse works across every `HttpClient`/`StreamingClient` built from it.
+#[derive(Debug, Clone)]
+pub struct ClientPool {
+    clients: Arc<Mutex<LruCache<ClientKey, Client>>>,
+    /// Egress policy every pooled client enforces
+    egress: Option<Arc<EgressPolicy>>,
+    /// Resolver every pooled client connects through
+    dns: Arc<DnsResolver>,
+    /// Trusted roots, client certificates and pins
+    tls: Arc<TlsSettings>,
+}
+
+impl ClientPool {
+    /// Create a pool holding at most `capacity` clients.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            clients: Arc::new(Mutex::new(LruCache::new(capacity))),
+            egress: None,
+            dns: Arc::new(DnsResolver::default()),
+            tls: Arc::new(TlsSettings::default()),
+        }
+    }
+
+    /// Create a pool of the same capacity whose clients enforce `policy`.
+    ///
+    /// Clients already pooled were built without the policy, so none are kept.
+    pub fn with_egress(&self, policy: Arc<EgressPolicy>) -> Self {
+        let capacity = self.clients.lock().unwrap().cap();
+        Self {
+            clients: Arc::new(Mutex::new(LruCache::new(capacity))),
+            egress: Some(policy),
+            dns: self.dns.clone(),
+            tls: self.tls.clone(),
+        }
+    }
+
+    /// Create a pool of the same capacity whose clients resolve through `dns`.
+    ///
+    /// Clients already pooled were built with the previous resolver, so none
+    /// are kept.
+    pub fn with_dns(&self, dns: Arc<DnsResolver>) -> Self {
+        let capacity = self.clients.lock().unwrap().cap();
+        Self {
+            clients: Arc::new(Mutex::new(LruCache::new(capacity))),
+            egress: self.egress.clone(),
+            dns,
+            tls: self.tls.clone(),
+        }
+    }
+
+    /// Create a pool of the same capacity whose clients apply `tls`.
+    ///
+    /// Clients already pooled were built with the previous settings, so none
+    /// are kept.
+    pub fn with_tls(&self, tls: Arc<TlsSettings>) -> Self {
+        let capacity = self.clients.lock().unwrap().cap();
+        Self {
+            clients: Arc::new(Mutex::new(LruCache::new(capacity))),
+            egress: self.egress.clone(),
+            dns: self.dns.clone(),
+            tls,
+        }
+    }
+
+    /// Egress policy enforced by the pooled clients, if any.
+    pub fn egress(&self) -> Option<&Arc<EgressPolicy>> {
+        self.egress.as_ref()
+    }
+
+    /// Resolver the pooled clients connect through.
+    pub fn dns(&self) -> &Arc<DnsResolver> {
+        &self.dns
+    }
+
+    /// Get the client for a configuration, building it on first use.
+    pub fn client_for(&self, config: &FetchConfig) -> Result<Client, FetchError> {
+        self.client_for_key(ClientKey::from_config(config))
+    }
+
+    /// Get the client for a configuration and the TLS settings of `url`'s host.
+    pub fn client_for_url(&self, config: &FetchConfig, url: &str) -> Result<Client, FetchError> {
+        self.client_for_key(ClientKey {
+            tls_profile: self.tls.profile_for(url).map(str::to_string),
+            ..ClientKey::from_config(config)
+        })
+    }
+
+    /// Get a client for a configuration and `url`'s host that returns
+    /// redirects to the caller instead of following them.
+    pub fn client_without_redirects(
+        &self,
+        config: &FetchConfig,
+        url: &str,
+    ) -> Result<Client, FetchError> {
+        self.client_for_key(ClientKey {
+            follow_redirects: false,
+            max_redirects: 0,
+            tls_profile: self.tls.profile_for(url).map(str::to_string),
+            ..ClientKey::from_config(config)
+        })
+    }
+
+    fn client_for_key(&self, key: ClientKey) -> Result<Client, FetchError> {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = key.build(self.egress.as_ref(), &self.dns, &self.tls)?;
+        tracing::debug!(
+            "Created pooled HTTP client (user agent {:?}, proxy {:?})",
+            key.user_agent,
+            key.proxy.as_ref().map(ProxyConfig::display_url)
+        );
+        clients.put(key, client.clone());
+        Ok(client)
+    }
+
+    /// Number of clients currently pooled.
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOL_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_reuses_clients_per_config() {
+        let pool = ClientPool::new(4);
+        let config = FetchConfig::default();
+
+        pool.client_for(&config).unwrap();
+        pool.client_for(&config).unwrap();
+        assert_eq!(pool.len(), 1);
+
+        // Per-request settings must not fan out into new clients
+        let per_request = FetchConfig {
+            timeout: Duration::from_secs(1),
+            max_content_size: 1024,
+            ..FetchConfig::default()
+        };
+        pool.client_for(&per_request).unwrap();
+        assert_eq!(pool.len(), 1);
+
+        let other_agent = FetchConfig {
+            user_agent: "other/1.0".to_string(),
+            ..FetchConfig::default()
+        };
+        pool.client_for(&other_agent).unwrap();
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_followed_redirects_to_ip_literals_are_checked() {
+        use crate::domain::fetch::egress::EgressConfig;
+        use crate::infra::http::HttpClient;
+        use axum::response::Redirect;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let target = format!("http://127.0.0.1:{}/", port);
+        let server = axum::Router::new()
+            .route(
+                "/",
+                axum::routing::get(move || async move { Redirect::temporary(&target) }),
+            )
+            .route("/ok", axum::routing::get(|| async { "ok" }));
+        tokio::spawn(async move { axum::serve(listener, server).await });
+
+        // localhost is trusted, the loopback literal it redirects to is not
+        let trusted = ["localhost".to_string()];
+        let policy = EgressPolicy::new(EgressConfig::default()).with_trusted_hosts(&trusted);
+        let client = HttpClient::new().unwrap().with_egress(policy);
+
+        let response = client
+            .get(&format!("http://localhost:{}/ok", port))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert!(matches!(
+            client.get(&format!("http://localhost:{}/", port)).await,
+            Err(FetchError::EgressDenied(_))
+        ));
+        assert!(matches!(
+            client.get(&format!("http://127.0.0.1:{}/ok", port)).await,
+            Err(FetchError::EgressDenied(_))
+        ));
+    }
+}
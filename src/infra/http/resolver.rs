@@ -0,0 +1,367 @@
+//! DNS resolution for fetches: static overrides, upstream name servers, an
+//! in-process cache and address family preference, plus the resolver the
+//! pooled clients connect through, which also enforces the egress policy.
+
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{
+    LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts,
+};
+use hyper::client::connect::dns::Name;
+use lru::LruCache;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::domain::fetch::egress::{EgressPolicy, normalize_host};
+use crate::domain::fetch::error::FetchError;
+
+/// Which address family to connect over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpPreference {
+    /// Keep the resolver's order
+    #[default]
+    Any,
+    /// Try IPv4 addresses first
+    Ipv4,
+    /// Try IPv6 addresses first
+    Ipv6,
+    /// Use IPv4 addresses only
+    Ipv4Only,
+    /// Use IPv6 addresses only
+    Ipv6Only,
+}
+
+impl IpPreference {
+    /// Order or filter `addrs` by preference.
+    fn apply(self, mut addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+        match self {
+            IpPreference::Any => {}
+            IpPreference::Ipv4 => addrs.sort_by_key(|ip| ip.is_ipv6()),
+            IpPreference::Ipv6 => addrs.sort_by_key(|ip| ip.is_ipv4()),
+            IpPreference::Ipv4Only => addrs.retain(IpAddr::is_ipv4),
+            IpPreference::Ipv6Only => addrs.retain(IpAddr::is_ipv6),
+        }
+        addrs
+    }
+}
+
+/// DNS configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// Fixed addresses per host, like curl's `--resolve`
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    /// Name servers to query; the system resolver is used when empty
+    pub nameservers: Vec<SocketAddr>,
+    /// Cache answers in process
+    pub cache_enabled: bool,
+    /// Maximum number of cached hosts
+    pub cache_capacity: usize,
+    /// How long system resolver answers (which carry no TTL) are cached
+    pub default_ttl: Duration,
+    /// Upper bound on the TTL of cached answers
+    pub max_ttl: Duration,
+    /// Address family preference
+    pub prefer: IpPreference,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            nameservers: Vec::new(),
+            cache_enabled: true,
+            cache_capacity: 1024,
+            default_ttl: Duration::from_secs(30),
+            max_ttl: Duration::from_secs(300),
+            prefer: IpPreference::Any,
+        }
+    }
+}
+
+/// Where a resolution came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionSource {
+    /// The host is an IP address
+    Literal,
+    /// A configured override
+    Override,
+    /// The in-process cache
+    Cache,
+    /// The configured name servers
+    Upstream,
+    /// The system resolver
+    System,
+}
+
+/// How a fetch's host was resolved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resolution {
+    /// Host name
+    pub host: String,
+    /// Addresses, in the order they are tried
+    pub addresses: Vec<IpAddr>,
+    /// Address the response came from, when known
+    pub remote_ip: Option<IpAddr>,
+    /// Where the addresses came from
+    pub source: ResolutionSource,
+    /// Time spent resolving, in milliseconds
+    pub duration_ms: f64,
+}
+
+/// Resolver with overrides, optional upstream name servers and a TTL cache.
+pub struct DnsResolver {
+    config: DnsConfig,
+    upstream: Option<TokioAsyncResolver>,
+    cache: Mutex<LruCache<String, (Vec<IpAddr>, Instant)>>,
+}
+
+impl DnsResolver {
+    /// Create a resolver from its configuration.
+    pub fn new(config: DnsConfig) -> Self {
+        let upstream = (!config.nameservers.is_empty()).then(|| {
+            let nameservers: Vec<NameServerConfig> = config
+                .nameservers
+                .iter()
+                .flat_map(|addr| {
+                    [
+                        NameServerConfig::new(*addr, Protocol::Udp),
+                        NameServerConfig::new(*addr, Protocol::Tcp),
+                    ]
+                })
+                .collect();
+            let mut options = ResolverOpts::default();
+            // Both families are queried; the preference is applied afterwards
+            options.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+            TokioAsyncResolver::tokio(
+                ResolverConfig::from_parts(None, Vec::new(), nameservers),
+                options,
+            )
+        });
+        let overrides = config
+            .overrides
+            .iter()
+            .map(|(host, addrs)| (normalize_host(host), addrs.clone()))
+            .collect();
+        let capacity = NonZeroUsize::new(config.cache_capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            config: DnsConfig {
+                overrides,
+                ..config
+            },
+            upstream,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// DNS configuration.
+    pub fn config(&self) -> &DnsConfig {
+        &self.config
+    }
+
+    /// Resolve `host`, preferring overrides, then the cache.
+    pub async fn resolve(&self, host: &str) -> Result<Resolution, FetchError> {
+        let started = Instant::now();
+        let host = normalize_host(host);
+        let resolution = |addresses: Vec<IpAddr>, source| Resolution {
+            host: host.clone(),
+            addresses,
+            remote_ip: None,
+            source,
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        };
+
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(resolution(vec![ip], ResolutionSource::Literal));
+        }
+        if let Some(addrs) = self.config.overrides.get(&host) {
+            let addrs = self.preferred(&host, addrs.clone())?;
+            return Ok(resolution(addrs, ResolutionSource::Override));
+        }
+        if self.config.cache_enabled
+            && let Some(addrs) = self.cached(&host)
+        {
+            return Ok(resolution(addrs, ResolutionSource::Cache));
+        }
+
+        let failed = |e: &dyn std::fmt::Display| {
+            FetchError::NetworkError(format!("DNS lookup of {} failed: {}", host, e))
+        };
+        let (addrs, ttl, source) = match &self.upstream {
+            Some(upstream) => {
+                let lookup = upstream
+                    .lookup_ip(host.as_str())
+                    .await
+                    .map_err(|e| failed(&e))?;
+                let ttl = lookup
+                    .valid_until()
+                    .saturating_duration_since(Instant::now());
+                (lookup.iter().collect(), ttl, ResolutionSource::Upstream)
+            }
+            None => {
+                let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                    .await
+                    .map_err(|e| failed(&e))?
+                    .map(|addr| addr.ip())
+                    .collect();
+                (addrs, self.config.default_ttl, ResolutionSource::System)
+            }
+        };
+        let addrs = self.preferred(&host, addrs)?;
+
+        let ttl = ttl.min(self.config.max_ttl);
+        if self.config.cache_enabled && !ttl.is_zero() {
+            self.cache
+                .lock()
+                .unwrap()
+                .put(host.clone(), (addrs.clone(), Instant::now() + ttl));
+        }
+        Ok(resolution(addrs, source))
+    }
+
+    /// Drop every cached answer.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(host) {
+            Some((addrs, expires_at)) if *expires_at > Instant::now() => Some(addrs.clone()),
+            Some(_) => {
+                cache.pop(host);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn preferred(&self, host: &str, addrs: Vec<IpAddr>) -> Result<Vec<IpAddr>, FetchError> {
+        let addrs = self.config.prefer.apply(addrs);
+        if addrs.is_empty() {
+            return Err(FetchError::NetworkError(format!(
+                "{} has no usable address ({:?})",
+                host, self.config.prefer
+            )));
+        }
+        Ok(addrs)
+    }
+}
+
+impl Default for DnsResolver {
+    fn default() -> Self {
+        Self::new(DnsConfig::default())
+    }
+}
+
+impl std::fmt::Debug for DnsResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DnsResolver")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resolver the pooled clients connect through.
+///
+/// Hosts are resolved by the `DnsResolver`; with an egress policy, resolution
+/// fails when a host resolves to an address the policy denies. Connections
+/// are made to exactly the addresses checked here, which closes the gap
+/// between an up-front check and the resolution the client does.
+#[derive(Debug, Clone)]
+pub struct ConnectResolver {
+    dns: Arc<DnsResolver>,
+    policy: Option<Arc<EgressPolicy>>,
+}
+
+impl ConnectResolver {
+    /// Create a resolver using `dns` and enforcing `policy`, if any.
+    pub fn new(dns: Arc<DnsResolver>, policy: Option<Arc<EgressPolicy>>) -> Self {
+        Self { dns, policy }
+    }
+}
+
+impl Resolve for ConnectResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = self.dns.clone();
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let resolution = dns.resolve(&host).await?;
+            if let Some(policy) = &policy {
+                for ip in &resolution.addresses {
+                    policy.check_ip(&host, *ip)?;
+                }
+            }
+            let addrs: Vec<SocketAddr> = resolution
+                .addresses
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Recover an egress violation from a client error's source chain.
+pub(crate) fn egress_violation(error: &reqwest::Error) -> Option<FetchError> {
+    let mut source = std::error::Error::source(error);
+    while let Some(error) = source {
+        if let Some(FetchError::EgressDenied(message)) = error.downcast_ref::<FetchError>() {
+            return Some(FetchError::EgressDenied(message.clone()));
+        }
+        source = error.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_overrides_cache_and_preference() {
+        let v4: IpAddr = "10.0.0.5".parse().unwrap();
+        let v6: IpAddr = "fd00::5".parse().unwrap();
+        let resolver = DnsResolver::new(DnsConfig {
+            overrides: [("Staging.Example.com".to_string(), vec![v6, v4])].into(),
+            prefer: IpPreference::Ipv4,
+            ..DnsConfig::default()
+        });
+
+        let resolution = resolver.resolve("staging.example.com.").await.unwrap();
+        assert_eq!(resolution.source, ResolutionSource::Override);
+        assert_eq!(resolution.addresses, [v4, v6]);
+        assert_eq!(
+            resolver.resolve("[::1]").await.unwrap().source,
+            ResolutionSource::Literal
+        );
+
+        let first = resolver.resolve("localhost").await.unwrap();
+        assert_eq!(first.source, ResolutionSource::System);
+        let second = resolver.resolve("localhost").await.unwrap();
+        assert_eq!(second.source, ResolutionSource::Cache);
+        assert_eq!(second.addresses, first.addresses);
+        resolver.clear_cache();
+        assert_eq!(
+            resolver.resolve("localhost").await.unwrap().source,
+            ResolutionSource::System
+        );
+
+        let only_v6 = DnsResolver::new(DnsConfig {
+            overrides: [("v4.example.com".to_string(), vec![v4])].into(),
+            prefer: IpPreference::Ipv6Only,
+            ..DnsConfig::default()
+        });
+        assert!(matches!(
+            only_v6.resolve("v4.example.com").await,
+            Err(FetchError::NetworkError(_))
+        ));
+    }
+}
@@ -2,13 +2,32 @@
 
 use bytes::{Bytes, BytesMut};
 use futures::stream::{Stream, StreamExt};
-use reqwest::Client;
+use std::borrow::Cow;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::time::{Instant, Sleep};
 
-use crate::domain::fetch::config::FetchConfig;
+use crate::domain::fetch::auth::{self, Credentials, DigestChallenge};
+use crate::domain::fetch::breaker::CircuitBreakers;
+use crate::domain::fetch::config::{FetchConfig, HttpMethod, RequestBody};
 use crate::domain::fetch::error::FetchError;
+use crate::domain::fetch::range::{self, ByteRange, ContentRange, ResumeState};
+use crate::domain::fetch::redirect::{self, RedirectHop, RedirectKind};
+use crate::domain::fetch::robots::RobotsCache;
+use crate::infra::encoding::detector::{self, DetectedEncoding, PRESCAN_BYTES};
+use crate::infra::har::HarRecorder;
+use crate::infra::http::cache::{
+    CacheLookup, CacheStatus, CachedResponse, CachingStream, HttpCache,
+};
+use crate::infra::http::local::{LocalResponse, LocalSource};
+use crate::infra::http::pool::ClientPool;
+use crate::infra::http::resolver::Resolution;
+use crate::infra::http::transport::{BodyStream, LiveTransport, Transport, TransportResponse};
+use crate::infra::warc::{ExchangeSink, RecordingStream, WarcExchange, WarcWriter};
+use std::sync::Arc;
 
 /// Streaming fetch result with metadata
 pub struct StreamingFetchResult {
@@ -20,29 +39,168 @@ pub struct StreamingFetchResult {
     pub final_url: String,
     /// Content-Length header (if present)
     pub content_length: Option<u64>,
+    /// Content-Type header (if present)
+    pub content_type: Option<String>,
+    /// Detected charset for textual responses
+    pub encoding: Option<String>,
+    /// Cache outcome (`None` when no cache is configured)
+    pub cache_status: Option<CacheStatus>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Headers of the final response (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// How the final host was resolved (`None` for cached, local and replayed responses)
+    pub dns: Option<Resolution>,
+}
+
+impl StreamingFetchResult {
+    /// `Content-Range` of a partial (206) response.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        ContentRange::from_headers(&self.headers)
+    }
 }
 
 /// Response stream wrapper with size tracking
 pub struct ResponseStream {
-    inner: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    inner: Pin<Box<dyn Stream<Item = Result<Bytes, FetchError>> + Send>>,
     bytes_received: usize,
     max_size: usize,
     finished: bool,
+    /// Maximum idle time between two chunks
+    read_timeout: Option<Duration>,
+    idle_timer: Option<Pin<Box<Sleep>>>,
+    /// Bytes read ahead for charset detection, replayed before the rest
+    prefix: Option<Bytes>,
+    /// Decoder used when transcoding to UTF-8
+    decoder: Option<encoding_rs::Decoder>,
+    transcoding: bool,
+    /// Held for the lifetime of the stream (e.g. a per-host permit)
+    _guard: Option<Box<dyn Send>>,
 }
 
 impl ResponseStream {
     pub fn new(
         stream: impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
         max_size: usize,
+    ) -> Self {
+        Self::from_results(
+            stream.map(|chunk| chunk.map_err(|e| FetchError::NetworkError(e.to_string()))),
+            max_size,
+        )
+    }
+
+    /// Wrap a stream whose failures are already `FetchError`s.
+    pub fn from_results(
+        stream: impl Stream<Item = Result<Bytes, FetchError>> + Send + 'static,
+        max_size: usize,
     ) -> Self {
         Self {
             inner: Box::pin(stream),
             bytes_received: 0,
             max_size,
             finished: false,
+            read_timeout: None,
+            idle_timer: None,
+            prefix: None,
+            decoder: None,
+            transcoding: false,
+            _guard: None,
         }
     }
 
+    /// Keep `guard` alive until the stream is dropped.
+    pub fn with_guard(mut self, guard: impl Send + 'static) -> Self {
+        self._guard = Some(Box::new(guard));
+        self
+    }
+
+    /// Fail the stream if no chunk arrives within `timeout`.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self.idle_timer = Some(Box::pin(tokio::time::sleep(timeout)));
+        self
+    }
+
+    /// Push the idle deadline forward after receiving data.
+    fn reset_idle_timer(&mut self) {
+        if let (Some(timeout), Some(timer)) = (self.read_timeout, self.idle_timer.as_mut()) {
+            timer.as_mut().reset(Instant::now() + timeout);
+        }
+    }
+
+    /// Read ahead up to `PRESCAN_BYTES` and detect the charset.
+    ///
+    /// The bytes read here are replayed by the stream, so callers still see
+    /// the complete body.
+    pub async fn detect_encoding(
+        &mut self,
+        content_type: Option<&str>,
+    ) -> Result<DetectedEncoding, FetchError> {
+        let prefix = self.peek(PRESCAN_BYTES).await?;
+        Ok(detector::detect(content_type, &prefix))
+    }
+
+    /// Read ahead up to `limit` raw (untranscoded) bytes, or the whole body
+    /// if shorter.
+    ///
+    /// The bytes read here are replayed by the stream, so callers still see
+    /// the complete body.
+    pub async fn peek(&mut self, limit: usize) -> Result<Bytes, FetchError> {
+        let mut buffer = BytesMut::new();
+        if let Some(prefix) = self.prefix.take() {
+            buffer.extend_from_slice(&prefix);
+        }
+
+        // Read raw bytes; the decoder applies when they are replayed
+        let decoder = self.decoder.take();
+        while buffer.len() < limit {
+            match self.next().await {
+                Some(chunk) => buffer.extend_from_slice(&chunk?),
+                None => break,
+            }
+        }
+        self.decoder = decoder;
+
+        let prefix = buffer.freeze();
+        if !prefix.is_empty() {
+            self.prefix = Some(prefix.clone());
+        }
+        Ok(prefix)
+    }
+
+    /// Transcode everything not yet yielded from `detected` to UTF-8.
+    pub fn transcode_from(&mut self, detected: &DetectedEncoding) {
+        self.decoder = Some(detected.encoding.new_decoder_with_bom_removal());
+        self.transcoding = true;
+    }
+
+    /// Whether the stream yields transcoded UTF-8
+    pub fn is_transcoding(&self) -> bool {
+        self.transcoding
+    }
+
+    /// Run a chunk through the decoder, if any.
+    fn decode_chunk(&mut self, chunk: Bytes, last: bool) -> Bytes {
+        let Some(decoder) = self.decoder.as_mut() else {
+            return chunk;
+        };
+
+        let capacity = decoder
+            .max_utf8_buffer_length(chunk.len())
+            .unwrap_or(chunk.len() * 3);
+        let mut output = String::with_capacity(capacity);
+        let _ = decoder.decode_to_string(&chunk, &mut output, last);
+        Bytes::from(output)
+    }
+
+    /// Flush bytes buffered in the decoder at end of stream.
+    fn flush_decoder(&mut self) -> Option<Bytes> {
+        self.decoder.as_ref()?;
+        let tail = self.decode_chunk(Bytes::new(), true);
+        self.decoder = None;
+        (!tail.is_empty()).then_some(tail)
+    }
+
     /// Get total bytes received so far
     pub fn bytes_received(&self) -> usize {
         self.bytes_received
@@ -58,55 +216,160 @@ impl Stream for ResponseStream {
     type Item = Result<Bytes, FetchError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(prefix) = self.prefix.take() {
+            let chunk = self.decode_chunk(prefix, false);
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+
         if self.finished {
-            return Poll::Ready(None);
+            return Poll::Ready(self.flush_decoder().map(Ok));
         }
 
         match self.inner.as_mut().poll_next(cx) {
             Poll::Ready(Some(Ok(chunk))) => {
                 self.bytes_received += chunk.len();
+                self.reset_idle_timer();
 
                 if self.bytes_received > self.max_size {
                     self.finished = true;
+                    self.decoder = None;
                     Poll::Ready(Some(Err(FetchError::ContentTooLarge(format!(
                         "Content exceeded {} bytes (received {})",
                         self.max_size, self.bytes_received
                     )))))
                 } else {
+                    let chunk = self.decode_chunk(chunk, false);
                     Poll::Ready(Some(Ok(chunk)))
                 }
             }
             Poll::Ready(Some(Err(e))) => {
                 self.finished = true;
-                Poll::Ready(Some(Err(FetchError::NetworkError(e.to_string()))))
+                self.decoder = None;
+                Poll::Ready(Some(Err(e)))
             }
             Poll::Ready(None) => {
                 self.finished = true;
-                Poll::Ready(None)
+                Poll::Ready(self.flush_decoder().map(Ok))
+            }
+            Poll::Pending => {
+                let timed_out = match self.idle_timer.as_mut() {
+                    Some(timer) => timer.as_mut().poll(cx).is_ready(),
+                    None => false,
+                };
+
+                if timed_out {
+                    self.finished = true;
+                    self.decoder = None;
+                    Poll::Ready(Some(Err(FetchError::Timeout(format!(
+                        "No data received for {:?} (received {} bytes)",
+                        self.read_timeout.unwrap_or_default(),
+                        self.bytes_received
+                    )))))
+                } else {
+                    Poll::Pending
+                }
             }
-            Poll::Pending => Poll::Pending,
         }
     }
 }
 
 /// Streaming HTTP client
+///
+/// Every call applies the full `FetchConfig`: client-level settings select a
+/// pooled `reqwest::Client`, while the total timeout, read timeout and size
+/// limit are enforced per request.
 #[derive(Clone, Debug)]
 pub struct StreamingClient {
-    client: Client,
-    default_max_size: usize,
+    pool: ClientPool,
+    /// Optional HTTP response cache
+    cache: Option<Arc<HttpCache>>,
+    /// `file://`, `data:` and fixture sources served without the network
+    local: Arc<LocalSource>,
+    /// WARC writer and HAR recorder receiving every network exchange
+    recorders: Vec<Arc<dyn ExchangeSink>>,
+    /// Sends requests (live, recording or replaying fixtures)
+    transport: Arc<dyn Transport>,
+    /// Per-host circuit breakers every network request goes through
+    breakers: Option<Arc<CircuitBreakers>>,
+    /// robots.txt checked for every redirect target
+    robots: Option<Arc<RobotsCache>>,
 }
 
 impl StreamingClient {
-    /// Create new streaming client
-    pub fn new(default_max_size: usize) -> Result<Self, FetchError> {
-        let client = Client::builder()
-            .build()
-            .map_err(|e| FetchError::NetworkError(e.to_string()))?;
+    /// Create new streaming client with its own client pool
+    pub fn new() -> Result<Self, FetchError> {
+        Ok(Self::with_pool(ClientPool::default()))
+    }
 
-        Ok(Self {
-            client,
-            default_max_size,
-        })
+    /// Create a streaming client sharing an existing client pool
+    pub fn with_pool(pool: ClientPool) -> Self {
+        Self {
+            pool,
+            cache: None,
+            local: Arc::new(LocalSource::default()),
+            recorders: Vec::new(),
+            transport: Arc::new(LiveTransport),
+            breakers: None,
+            robots: None,
+        }
+    }
+
+    /// Send requests through `pool`, keeping the cache
+    pub fn with_client_pool(mut self, pool: ClientPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Serve and store responses through `cache`
+    pub fn with_cache(mut self, cache: Arc<HttpCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Get the HTTP cache, if configured
+    pub fn cache(&self) -> Option<&Arc<HttpCache>> {
+        self.cache.as_ref()
+    }
+
+    /// Serve `file://`, `data:` and fixture URLs from `local`
+    pub fn with_local_sources(mut self, local: Arc<LocalSource>) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// Record every network exchange to `warc`
+    pub fn with_warc(mut self, warc: Arc<WarcWriter>) -> Self {
+        self.recorders.push(warc);
+        self
+    }
+
+    /// Record every network exchange, with timings, to `har`
+    pub fn with_har(mut self, har: Arc<HarRecorder>) -> Self {
+        self.recorders.push(har);
+        self
+    }
+
+    /// Send requests through `transport`
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Send every request, redirect hops included, through `breakers`
+    pub fn with_breakers(mut self, breakers: Arc<CircuitBreakers>) -> Self {
+        self.breakers = Some(breakers);
+        self
+    }
+
+    /// Check every redirect target against its robots.txt in `robots`
+    pub fn with_robots(mut self, robots: Arc<RobotsCache>) -> Self {
+        self.robots = Some(robots);
+        self
+    }
+
+    /// Whether `url` is served by a local source rather than the network
+    pub fn is_local(&self, url: &str) -> bool {
+        url::Url::parse(url).is_ok_and(|url| self.local.handles(&url))
     }
 
     /// Fetch URL as a stream (memory efficient)
@@ -115,48 +378,468 @@ impl StreamingClient {
         url: &str,
         config: &FetchConfig,
     ) -> Result<StreamingFetchResult, FetchError> {
-        let response = self
-            .client
-            .get(url)
-            .timeout(config.timeout)
-            .send()
+        self.fetch_stream_limited(url, config, config.max_content_size)
             .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    FetchError::Timeout(e.to_string())
-                } else {
-                    FetchError::NetworkError(e.to_string())
+    }
+
+    /// Fetch URL as a stream, capping the body at `max_size` bytes
+    async fn fetch_stream_limited(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+        max_size: usize,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        if let Ok(parsed) = url::Url::parse(url)
+            && self.local.handles(&parsed)
+        {
+            let local = self.local.fetch(&parsed, max_size).await?;
+            return Self::from_local(parsed.as_str(), local, config, max_size).await;
+        }
+
+        if let Some(egress) = self.pool.egress() {
+            egress.check_target(url, config.proxy.as_ref()).await?;
+        }
+
+        let lookup = match &self.cache {
+            Some(cache) => Some(cache.lookup_async(url, config).await),
+            None => None,
+        };
+        let mut cache_status = match lookup {
+            Some(CacheLookup::Bypass) => Some(CacheStatus::Bypass),
+            Some(_) => Some(CacheStatus::Miss),
+            None => None,
+        };
+        let stale = match lookup {
+            Some(CacheLookup::Fresh(cached)) => {
+                tracing::debug!("Cache hit for {}", url);
+                return Self::from_cache(cached, CacheStatus::Hit, config, max_size).await;
+            }
+            Some(CacheLookup::Stale(cached)) => Some(cached),
+            _ => None,
+        };
+
+        let mut redirects = Vec::new();
+        let mut current_url = url.to_string();
+        let mut hop_config = Cow::Borrowed(config);
+        // Digest challenge of the current hop, answered on the next attempt
+        let mut digest: Option<DigestChallenge> = None;
+        let mut dns: Option<Resolution>;
+        let (response, exchange) = loop {
+            let mut request = self.build_request(&current_url, &hop_config)?;
+            if let (Some(challenge), Some(Credentials::Digest { username, password })) =
+                (&digest, &hop_config.auth)
+            {
+                let uri = url::Url::parse(&current_url)
+                    .map(|url| {
+                        url[url::Position::BeforePath..url::Position::AfterQuery].to_string()
+                    })
+                    .unwrap_or_else(|_| "/".to_string());
+                request = request.header(
+                    reqwest::header::AUTHORIZATION,
+                    challenge.authorization(username, password, hop_config.method.as_str(), &uri),
+                );
+            }
+            if redirects.is_empty()
+                && let Some(stale) = &stale
+            {
+                for (name, value) in stale.validators() {
+                    request = request.header(name, value);
                 }
-            })?;
+            }
+
+            let (client, request) = request.build_split();
+            let request = request.map_err(|e| FetchError::InvalidRequest(e.to_string()))?;
+            let exchange =
+                (!self.recorders.is_empty()).then(|| request_exchange(&request, &hop_config));
+
+            let started = Instant::now();
+            // Every hop goes through the breaker of its own host, so failures
+            // at a redirect target do not count against the origin
+            let permit = match &self.breakers {
+                Some(breakers) => breakers.allow(&current_url)?,
+                None => None,
+            };
+            let sent = async {
+                // Resolved up front for the metadata; the connector then answers
+                // from the resolver's cache. Proxies resolve hosts themselves.
+                let dns = match request.url().host() {
+                    Some(url::Host::Domain(host))
+                        if hop_config.proxy.is_none() && self.transport.connects() =>
+                    {
+                        Some(self.pool.dns().resolve(host).await?)
+                    }
+                    _ => None,
+                };
+                Ok::<_, FetchError>((self.transport.send(client, request).await?, dns))
+            }
+            .await;
+            if let Some(permit) = permit {
+                permit.record(match &sent {
+                    Ok((response, _)) => Ok(response.status().as_u16()),
+                    Err(e) => Err(e),
+                });
+            }
+            let response;
+            (response, dns) = sent?;
+            if let Some(resolution) = &mut dns {
+                resolution.remote_ip = response.remote_addr().map(|addr| addr.ip());
+            }
+
+            if let Some(jar) = &config.cookie_jar {
+                jar.store_response(
+                    response.url(),
+                    response
+                        .headers()
+                        .get_all(reqwest::header::SET_COOKIE)
+                        .iter()
+                        .filter_map(|value| value.to_str().ok()),
+                );
+            }
+
+            let status = response.status().as_u16();
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let exchange = exchange.map(|exchange| response_exchange(exchange, &response));
+            if status == 401
+                && digest.is_none()
+                && matches!(hop_config.auth, Some(Credentials::Digest { .. }))
+                && let Some(challenge) = DigestChallenge::from_headers(
+                    response
+                        .headers()
+                        .get_all(reqwest::header::WWW_AUTHENTICATE)
+                        .iter()
+                        .filter_map(|value| value.to_str().ok()),
+                )
+            {
+                tracing::debug!("Answering digest challenge from {}", current_url);
+                self.archive_unread(exchange, &response);
+                digest = Some(challenge);
+                continue;
+            }
+            let Some(location) =
+                location.filter(|_| config.follow_redirects && redirect::is_redirect(status))
+            else {
+                break (response, exchange);
+            };
+            self.archive_unread(exchange, &response);
+
+            if redirects.len() >= config.max_redirects {
+                return Err(FetchError::TooManyRedirects(format!(
+                    "{} redirects starting at {}",
+                    redirects.len() + 1,
+                    url
+                )));
+            }
+            let next = redirect::resolve_location(&current_url, &location)?;
+            if let Some(egress) = self.pool.egress() {
+                egress
+                    .check_target(next.as_str(), hop_config.proxy.as_ref())
+                    .await?;
+            }
+            if let Some(robots) = &self.robots {
+                robots.check(self, next.as_str(), &hop_config).await?;
+            }
+            tracing::debug!(
+                "Following {} redirect from {} to {}",
+                status,
+                current_url,
+                next
+            );
+            hop_config = Cow::Owned(redirect::redirect_config(
+                &hop_config,
+                status,
+                response.url(),
+                &next,
+            ));
+            digest = None;
+            redirects.push(RedirectHop {
+                url: std::mem::replace(&mut current_url, next.to_string()),
+                status_code: status,
+                location,
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                kind: RedirectKind::Http,
+            });
+        };
 
         let status = response.status();
-        if !status.is_success() {
-            return Err(FetchError::ServerError(format!("HTTP {}", status)));
+        if status == reqwest::StatusCode::NOT_MODIFIED
+            && redirects.is_empty()
+            && let (Some(cache), Some(stale)) = (&self.cache, stale)
+        {
+            tracing::debug!("Cache entry for {} revalidated", url);
+            self.archive_unread(exchange, &response);
+            let cached = cache.revalidated(url, stale, response.headers());
+            return Self::from_cache(cached, CacheStatus::Revalidated, config, max_size).await;
+        }
+
+        if !config.accepts_status(status.as_u16()) {
+            self.archive_unread(exchange, &response);
+            return Err(FetchError::ServerError {
+                status: status.as_u16(),
+                message: format!("HTTP {}", status),
+                retry_after: response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after),
+            });
         }
 
         let final_url = response.url().to_string();
         let content_length = response.content_length();
+        let headers = header_pairs(response.headers());
 
         // Check Content-Length upfront if available
         if let Some(len) = content_length {
-            if len > self.default_max_size as u64 {
+            if len > max_size as u64 {
                 return Err(FetchError::ContentTooLarge(format!(
                     "Content-Length {} exceeds limit {}",
-                    len, self.default_max_size
+                    len, max_size
                 )));
             }
         }
 
-        let stream = ResponseStream::new(response.bytes_stream(), self.default_max_size);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let body: BodyStream = match &self.cache {
+            // Unsafe methods invalidate what we hold for the URL
+            Some(cache) if matches!(config.method, HttpMethod::Post | HttpMethod::Put) => {
+                cache.invalidate(url);
+                response.bytes_stream()
+            }
+            Some(cache)
+                if cache_status == Some(CacheStatus::Miss)
+                    && cache.is_storable(status.as_u16(), response.headers()) =>
+            {
+                let mut cached = CachedResponse::from_response(
+                    status.as_u16(),
+                    &final_url,
+                    response.headers(),
+                    &hop_config,
+                );
+                cached.redirects = redirects.clone();
+                let body = response.bytes_stream();
+                Box::pin(CachingStream::new(body, cache.clone(), url, cached))
+            }
+            _ => {
+                if cache_status == Some(CacheStatus::Miss) {
+                    cache_status = Some(CacheStatus::Bypass);
+                }
+                response.bytes_stream()
+            }
+        };
+        let body: BodyStream = match exchange {
+            Some(exchange) => {
+                Box::pin(RecordingStream::new(body, self.recorders.clone(), exchange))
+            }
+            None => body,
+        };
+
+        let mut stream = ResponseStream::new(body, max_size).with_read_timeout(config.read_timeout);
+        let encoding = Self::detect_charset(&mut stream, content_type.as_deref(), config).await?;
 
         Ok(StreamingFetchResult {
             stream,
             status_code: status.as_u16(),
             final_url,
             content_length,
+            content_type,
+            encoding,
+            cache_status,
+            redirects,
+            headers,
+            dns,
+        })
+    }
+
+    /// Record an exchange whose body is not read (redirects, 304s, rejected statuses)
+    fn archive_unread(&self, exchange: Option<WarcExchange>, response: &TransportResponse) {
+        let Some(mut exchange) = exchange else {
+            return;
+        };
+        exchange.truncated = response.content_length() != Some(0);
+        for recorder in &self.recorders {
+            recorder.record(&exchange);
+        }
+    }
+
+    /// Build a result from a cached response
+    async fn from_cache(
+        cached: CachedResponse,
+        cache_status: CacheStatus,
+        config: &FetchConfig,
+        max_size: usize,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        if cached.body.len() > max_size {
+            return Err(FetchError::ContentTooLarge(format!(
+                "Cached body of {} bytes exceeds limit {}",
+                cached.body.len(),
+                max_size
+            )));
+        }
+
+        let content_type = cached.header("content-type").map(str::to_string);
+        let content_length = cached.body.len() as u64;
+        let body =
+            futures::stream::once(futures::future::ready(Ok::<_, reqwest::Error>(cached.body)));
+        let mut stream = ResponseStream::new(body, max_size);
+        let encoding = Self::detect_charset(&mut stream, content_type.as_deref(), config).await?;
+
+        Ok(StreamingFetchResult {
+            stream,
+            status_code: cached.status,
+            final_url: cached.final_url,
+            content_length: Some(content_length),
+            content_type,
+            encoding,
+            cache_status: Some(cache_status),
+            redirects: cached.redirects,
+            headers: cached.headers,
+            dns: None,
+        })
+    }
+
+    /// Build a result from a local source
+    async fn from_local(
+        url: &str,
+        local: LocalResponse,
+        config: &FetchConfig,
+        max_size: usize,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        if !config.accepts_status(local.status) {
+            return Err(FetchError::ServerError {
+                status: local.status,
+                message: format!("{} not found", url),
+                retry_after: None,
+            });
+        }
+
+        let content_length = local.body.len() as u64;
+        let mut headers = vec![("content-length".to_string(), content_length.to_string())];
+        if let Some(content_type) = &local.content_type {
+            headers.insert(0, ("content-type".to_string(), content_type.clone()));
+        }
+        let body =
+            futures::stream::once(futures::future::ready(Ok::<_, reqwest::Error>(local.body)));
+        let mut stream = ResponseStream::new(body, max_size);
+        let encoding =
+            Self::detect_charset(&mut stream, local.content_type.as_deref(), config).await?;
+
+        Ok(StreamingFetchResult {
+            stream,
+            status_code: local.status,
+            final_url: url.to_string(),
+            content_length: Some(content_length),
+            content_type: local.content_type,
+            encoding,
+            cache_status: None,
+            redirects: Vec::new(),
+            headers,
+            dns: None,
         })
     }
 
+    /// Detect the charset of textual bodies, transcoding if requested
+    async fn detect_charset(
+        stream: &mut ResponseStream,
+        content_type: Option<&str>,
+        config: &FetchConfig,
+    ) -> Result<Option<String>, FetchError> {
+        // Only sniff bodies that are meant to be read as text
+        if !is_textual(content_type) {
+            return Ok(None);
+        }
+
+        let detected = stream.detect_encoding(content_type).await?;
+        if config.transcode_to_utf8 {
+            stream.transcode_from(&detected);
+        }
+        Ok(Some(detected.name().to_string()))
+    }
+
+    /// Build the request for `url` with the method, headers, cookies and body from `config`
+    fn build_request(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+    ) -> Result<reqwest::RequestBuilder, FetchError> {
+        let method = match config.method {
+            HttpMethod::Get => reqwest::Method::GET,
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Head => reqwest::Method::HEAD,
+        };
+
+        let mut request = self
+            .pool
+            .client_without_redirects(config, url)?
+            .request(method, url)
+            .timeout(config.timeout);
+
+        let merge_cookies = !config.cookies.is_empty() || config.cookie_jar.is_some();
+        for (name, value) in &config.headers {
+            // An explicit Cookie header is merged with the jar and `config.cookies` below
+            if merge_cookies && name.eq_ignore_ascii_case("cookie") {
+                continue;
+            }
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| FetchError::InvalidRequest(format!("header {:?}: {}", name, e)))?;
+            let value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|e| FetchError::InvalidRequest(format!("header {:?}: {}", name, e)))?;
+            request = request.header(name, value);
+        }
+
+        if let Some((name, value)) = config.auth.as_ref().and_then(Credentials::header) {
+            // The error never includes the value, which is a secret
+            let invalid = || {
+                FetchError::InvalidRequest(format!(
+                    "{} credentials for {}: invalid header",
+                    config.auth.as_ref().map_or("", Credentials::scheme),
+                    name
+                ))
+            };
+            let name =
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?;
+            let mut value =
+                reqwest::header::HeaderValue::from_str(&value).map_err(|_| invalid())?;
+            value.set_sensitive(true);
+            request = request.header(name, value);
+        }
+
+        if merge_cookies && let Some(cookie_header) = cookie_header(config, url) {
+            let value = reqwest::header::HeaderValue::from_str(&cookie_header)
+                .map_err(|e| FetchError::InvalidRequest(format!("cookies: {}", e)))?;
+            request = request.header(reqwest::header::COOKIE, value);
+        }
+
+        request = match &config.body {
+            None => request,
+            Some(RequestBody::Raw {
+                content,
+                content_type,
+            }) => {
+                let request = match content_type {
+                    Some(content_type) => {
+                        request.header(reqwest::header::CONTENT_TYPE, content_type.as_str())
+                    }
+                    None => request,
+                };
+                request.body(content.clone())
+            }
+            Some(RequestBody::Form(fields)) => request.form(fields),
+            Some(RequestBody::Json(value)) => request.json(value),
+        };
+
+        Ok(request)
+    }
+
     /// Fetch to string with size limit (for small responses)
     pub async fn fetch_to_string(
         &self,
@@ -164,7 +847,8 @@ impl StreamingClient {
         config: &FetchConfig,
         max_size: usize,
     ) -> Result<(String, FetchMetadata), FetchError> {
-        let mut result = self.fetch_stream(url, config).await?;
+        let max_size = std::cmp::min(max_size, config.max_content_size);
+        let mut result = self.fetch_stream_limited(url, config, max_size).await?;
         let mut buffer = BytesMut::with_capacity(
             result
                 .content_length
@@ -177,8 +861,22 @@ impl StreamingClient {
             buffer.extend_from_slice(&chunk);
         }
 
-        let content = String::from_utf8(buffer.to_vec())
-            .map_err(|e| FetchError::Other(format!("Invalid UTF-8: {}", e)))?;
+        let (content, encoding) = if result.stream.is_transcoding() {
+            let content = String::from_utf8_lossy(&buffer).into_owned();
+            (content, result.encoding)
+        } else {
+            // The whole body is available, so sniff it rather than the prefix
+            let detected = detector::detect(result.content_type.as_deref(), &buffer);
+            let (content, had_errors) = detector::decode(&buffer, &detected);
+            if had_errors {
+                tracing::debug!(
+                    "Malformed {} sequences replaced while decoding {}",
+                    detected.name(),
+                    result.final_url
+                );
+            }
+            (content, Some(detected.name().to_string()))
+        };
 
         Ok((
             content,
@@ -186,25 +884,169 @@ impl StreamingClient {
                 length: result.stream.bytes_received(),
                 status_code: result.status_code,
                 final_url: result.final_url,
+                encoding,
+                cache_status: result.cache_status,
+                redirects: result.redirects,
+                headers: result.headers,
+                dns: result.dns,
             },
         ))
     }
 
+    /// Fetch bytes `range` of `url` as a stream.
+    ///
+    /// With `if_range` (an `ETag` or `Last-Modified` value) the server sends
+    /// the whole resource instead if it changed. Servers without range support
+    /// also answer with the whole resource, so check for status 206 and
+    /// `content_range()`. Partial bodies are never transcoded.
+    pub async fn fetch_range(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+        range: ByteRange,
+        if_range: Option<&str>,
+    ) -> Result<StreamingFetchResult, FetchError> {
+        let config = range_config(config, range, if_range);
+        self.fetch_stream(url, &config).await
+    }
+
     /// Fetch and write to a sink (file, buffer, etc.) - ZERO extra memory
+    ///
+    /// A connection dropped mid-body is resumed from the bytes already written
+    /// as often as `config.retry` allows, see `fetch_to_writer_from`.
     pub async fn fetch_to_writer<W: AsyncWrite + Unpin>(
         &self,
         url: &str,
         config: &FetchConfig,
         writer: &mut W,
     ) -> Result<FetchMetadata, FetchError> {
-        let mut result = self.fetch_stream(url, config).await?;
+        self.fetch_to_writer_from(url, config, writer, &mut ResumeState::default())
+            .await
+    }
+
+    /// Fetch into `writer`, continuing a download that stopped after `resume.offset` bytes.
+    ///
+    /// `writer` must already hold the first `resume.offset` bytes; the rest is
+    /// requested with `Range` and `If-Range: resume.validator`. Failed
+    /// attempts are retried under `config.retry`, each resuming where the
+    /// previous one stopped. `resume` tracks the progress, so a caller can
+    /// persist it and resume in a later call. Fails with `ResumeFailed` when
+    /// the resource changed since the bytes already written were fetched, or
+    /// when it has no validator to tell. `FetchMetadata::length` counts the
+    /// whole body and `FetchMetadata::status_code` is the status of the full
+    /// response.
+    pub async fn fetch_to_writer_from<W: AsyncWrite + Unpin>(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+        writer: &mut W,
+        resume: &mut ResumeState,
+    ) -> Result<FetchMetadata, FetchError> {
+        if resume.offset > 0 && (config.method != HttpMethod::Get || config.transcode_to_utf8) {
+            return Err(FetchError::InvalidRequest(
+                "only untranscoded GET downloads can be resumed".to_string(),
+            ));
+        }
+
+        let mut attempt = 1;
+        loop {
+            let error = match self.write_from(url, config, writer, resume).await {
+                Ok(metadata) => return Ok(metadata),
+                Err(e) => e,
+            };
+            // Transcoded output cannot be mapped back to a byte offset
+            if resume.offset > 0 && config.transcode_to_utf8 {
+                return Err(error);
+            }
+            let Some(delay) = config.retry.next_delay(&error, attempt, config.method) else {
+                return Err(error);
+            };
+            tracing::debug!(
+                "Resuming {} at byte {} after attempt {} failed: {}",
+                url,
+                resume.offset,
+                attempt,
+                error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// One attempt of `fetch_to_writer_from`, advancing `resume` as bytes are written
+    async fn write_from<W: AsyncWrite + Unpin>(
+        &self,
+        url: &str,
+        config: &FetchConfig,
+        writer: &mut W,
+        resume: &mut ResumeState,
+    ) -> Result<FetchMetadata, FetchError> {
+        let mut skip = 0;
+        let mut result = if resume.offset == 0 {
+            let result = self.fetch_stream(url, config).await?;
+            resume.validator = range::validator(&result.headers);
+            resume.status = Some(result.status_code);
+            result
+        } else {
+            // Without a validator a 206 could belong to another version
+            let Some(validator) = resume.validator.as_deref() else {
+                return Err(FetchError::ResumeFailed(format!(
+                    "{} has no ETag or Last-Modified to resume after {} bytes",
+                    url, resume.offset
+                )));
+            };
+            let result = self
+                .fetch_range(
+                    url,
+                    config,
+                    ByteRange::starting_at(resume.offset),
+                    Some(validator),
+                )
+                .await?;
+            if result.status_code == 206 {
+                let start = result.content_range().map(|range| range.start);
+                if start != Some(resume.offset) {
+                    return Err(FetchError::ResumeFailed(format!(
+                        "asked {} for bytes from {}, got Content-Range starting at {:?}",
+                        url, resume.offset, start
+                    )));
+                }
+            } else {
+                // The range was ignored or the validator no longer matches:
+                // only the same version of the resource may be continued
+                let current = range::validator(&result.headers);
+                if current.is_none() || current != resume.validator {
+                    return Err(FetchError::ResumeFailed(format!(
+                        "{} changed after {} bytes were written",
+                        url, resume.offset
+                    )));
+                }
+                skip = resume.offset;
+            }
+            result
+        };
 
         while let Some(chunk) = result.stream.next().await {
-            let chunk = chunk?;
+            let mut chunk = chunk?;
+            if skip > 0 {
+                let skipped = skip.min(chunk.len() as u64);
+                chunk = chunk.slice(skipped as usize..);
+                skip -= skipped;
+            }
+            if chunk.is_empty() {
+                continue;
+            }
             writer
                 .write_all(&chunk)
                 .await
                 .map_err(|e| FetchError::Other(e.to_string()))?;
+            resume.offset += chunk.len() as u64;
+            if resume.offset > config.max_content_size as u64 {
+                return Err(FetchError::ContentTooLarge(format!(
+                    "Content exceeded {} bytes (received {})",
+                    config.max_content_size, resume.offset
+                )));
+            }
         }
 
         writer
@@ -213,42 +1055,824 @@ impl StreamingClient {
             .map_err(|e| FetchError::Other(e.to_string()))?;
 
         Ok(FetchMetadata {
-            length: result.stream.bytes_received(),
-            status_code: result.status_code,
+            length: resume.offset as usize,
+            status_code: if result.status_code == 206 {
+                resume.status.unwrap_or(200)
+            } else {
+                result.status_code
+            },
             final_url: result.final_url,
+            encoding: result.encoding,
+            cache_status: result.cache_status,
+            redirects: result.redirects,
+            headers: result.headers,
+            dns: result.dns,
         })
     }
 }
 
+/// Config requesting `range`, replacing any `Range`/`If-Range` headers
+pub(crate) fn range_config(
+    config: &FetchConfig,
+    range: ByteRange,
+    if_range: Option<&str>,
+) -> FetchConfig {
+    let mut config = config.clone();
+    config.headers.retain(|name, _| {
+        !name.eq_ignore_ascii_case("range") && !name.eq_ignore_ascii_case("if-range")
+    });
+    config
+        .headers
+        .insert("Range".to_string(), range.to_string());
+    if let Some(validator) = if_range {
+        config
+            .headers
+            .insert("If-Range".to_string(), validator.to_string());
+    }
+    config.transcode_to_utf8 = false;
+    config
+}
+
 /// Fetch metadata (without content)
 #[derive(Debug, Clone)]
 pub struct FetchMetadata {
     pub length: usize,
     pub status_code: u16,
     pub final_url: String,
+    /// Detected charset of the body
+    pub encoding: Option<String>,
+    /// Cache outcome (`None` when no cache is configured)
+    pub cache_status: Option<CacheStatus>,
+    /// Redirects followed before the final response
+    pub redirects: Vec<RedirectHop>,
+    /// Headers of the final response (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// How the final host was resolved
+    pub dns: Option<Resolution>,
+}
+
+/// Start a WARC exchange for `request` as it is about to be sent
+fn request_exchange(request: &reqwest::Request, config: &FetchConfig) -> WarcExchange {
+    let mut request_headers = header_pairs(request.headers());
+    // Stored credentials must not end up in archives or HAR exports
+    if let Some(credentials) = &config.auth {
+        for (name, value) in &mut request_headers {
+            if name.eq_ignore_ascii_case(credentials.header_name()) {
+                *value = auth::REDACTED.to_string();
+            }
+        }
+    }
+    // The client adds its User-Agent when sending
+    if !request.headers().contains_key(reqwest::header::USER_AGENT) {
+        request_headers.push(("user-agent".to_string(), config.user_agent.clone()));
+    }
+    WarcExchange {
+        url: request.url().to_string(),
+        method: request.method().to_string(),
+        request_headers,
+        request_body: request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(Bytes::copy_from_slice),
+        version: format!("{:?}", request.version()),
+        status: 0,
+        response_headers: Vec::new(),
+        body: Bytes::new(),
+        truncated: false,
+        ip: None,
+        date: chrono::Utc::now(),
+        wait: Duration::ZERO,
+    }
+}
+
+/// Complete a WARC exchange with the response line, headers, peer address and wait time
+fn response_exchange(mut exchange: WarcExchange, response: &TransportResponse) -> WarcExchange {
+    exchange.version = format!("{:?}", response.version());
+    exchange.status = response.status().as_u16();
+    exchange.response_headers = header_pairs(response.headers());
+    exchange.ip = response.remote_addr().map(|addr| addr.ip());
+    exchange.wait = (chrono::Utc::now() - exchange.date)
+        .to_std()
+        .unwrap_or_default();
+    exchange
+}
+
+/// Response headers as `(name, value)` pairs; non-UTF-8 bytes are replaced
+pub(crate) fn header_pairs(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// `Cookie` header sent for `config` to `url`: explicit Cookie headers, then
+/// matching cookie jar entries, then `cookies` (which override the jar)
+pub(crate) fn cookie_header(config: &FetchConfig, url: &str) -> Option<String> {
+    let mut pairs: Vec<String> = config
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    if let Some(jar) = &config.cookie_jar
+        && let Ok(url) = url::Url::parse(url)
+    {
+        pairs.extend(
+            jar.cookies_for(&url)
+                .into_iter()
+                .filter(|(name, _)| !config.cookies.contains_key(name))
+                .map(|(name, value)| format!("{}={}", name, value)),
+        );
+    }
+
+    // Sorted so identical cookie maps produce identical requests
+    let mut cookies: Vec<_> = config.cookies.iter().collect();
+    cookies.sort();
+    pairs.extend(
+        cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value)),
+    );
+    (!pairs.is_empty()).then(|| pairs.join("; "))
+}
+
+/// Parse a `Retry-After` value given as delay-seconds or an HTTP-date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    // A date in the past means "retry now"
+    Some(delay.to_std().unwrap_or_default())
+}
+
+/// Whether a Content-Type denotes a body that should be decoded as text
+pub(crate) fn is_textual(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime.is_empty()
+        || mime.starts_with("text/")
+        || mime.contains("html")
+        || mime.contains("xml")
+        || mime.contains("json")
+        || mime.contains("javascript")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::infra::http::transport::{FixtureStore, RecordedResponse, ReplayTransport};
 
     #[tokio::test]
     async fn test_size_limit_enforcement() {
-        let client = StreamingClient::new(1024).unwrap(); // 1KB limit
+        // Replay a recorded 2KB response so the test runs offline
+        let dir = std::env::temp_dir().join(format!("scapi-replay-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(FixtureStore::new(dir.clone()).unwrap());
+        store
+            .put(
+                "GET https://httpbin.org/bytes/2048",
+                &RecordedResponse {
+                    status: 200,
+                    url: "https://httpbin.org/bytes/2048".to_string(),
+                    headers: vec![
+                        (
+                            "content-type".to_string(),
+                            "application/octet-stream".to_string(),
+                        ),
+                        ("content-length".to_string(), "2048".to_string()),
+                    ],
+                    body: Bytes::from(vec![0u8; 2048]),
+                },
+            )
+            .unwrap();
+        let client = StreamingClient::new()
+            .unwrap()
+            .with_transport(Arc::new(ReplayTransport::new(store.clone())));
 
-        // This should fail if the response is > 1KB
+        let config = FetchConfig {
+            max_content_size: 1024, // 1KB limit
+            ..FetchConfig::default()
+        };
+        let result = client
+            .fetch_to_string("https://httpbin.org/bytes/2048", &config, 1024)
+            .await;
+        assert!(matches!(result, Err(FetchError::ContentTooLarge(_))));
+
+        // Anything not recorded fails instead of going to the network
+        let result = client
+            .fetch_to_string("https://httpbin.org/bytes/16", &config, 1024)
+            .await;
+        assert!(matches!(result, Err(FetchError::ReplayMiss(_))));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_local_sources_bypass_the_network() {
+        let dir = std::env::temp_dir().join(format!("scapi-fixture-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "<html>saved</html>").unwrap();
+
+        let local = LocalSource::new(crate::infra::http::LocalSourceConfig {
+            fixtures: [("example.com".to_string(), dir.clone())].into(),
+            ..Default::default()
+        });
+        let client = StreamingClient::new()
+            .unwrap()
+            .with_local_sources(Arc::new(local));
         let config = FetchConfig::default();
-        let _ = client
-            .fetch_to_string(
-                "https://httpbin.org/bytes/2048", // 2KB response
-                &config,
-                1024,
+
+        let (content, metadata) = client
+            .fetch_to_string("https://example.com/", &config, 1024)
+            .await
+            .unwrap();
+        assert_eq!(content, "<html>saved</html>");
+        assert_eq!(metadata.status_code, 200);
+        assert_eq!(metadata.final_url, "https://example.com/");
+        assert!(
+            metadata
+                .headers
+                .contains(&("content-type".to_string(), "text/html".to_string()))
+        );
+
+        let (content, _) = client
+            .fetch_to_string("data:text/plain,caf%C3%A9", &config, 1024)
+            .await
+            .unwrap();
+        assert_eq!(content, "café");
+
+        assert!(matches!(
+            client
+                .fetch_to_string("https://example.com/missing", &config, 1024)
+                .await,
+            Err(FetchError::ServerError { status: 404, .. })
+        ));
+        let accepting = FetchConfig {
+            accept_statuses: vec!["404".parse().unwrap()],
+            ..FetchConfig::default()
+        };
+        let (_, metadata) = client
+            .fetch_to_string("https://example.com/missing", &accepting, 1024)
+            .await
+            .unwrap();
+        assert_eq!(metadata.status_code, 404);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_transcodes_split_multibyte_chunks() {
+        let html =
+            "<html><head><meta charset=\"windows-1251\"></head><body>Привет, мир</body></html>";
+        let (encoded, _, _) = encoding_rs::WINDOWS_1251.encode(html);
+
+        let chunks: Vec<Result<Bytes, reqwest::Error>> = encoded
+            .chunks(7)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        let mut stream = ResponseStream::new(futures::stream::iter(chunks), 1024 * 1024);
+        let detected = stream.detect_encoding(Some("text/html")).await.unwrap();
+        assert_eq!(detected.name(), "windows-1251");
+        stream.transcode_from(&detected);
+
+        let mut output = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            output.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), html);
+
+        // windows-1251 is single-byte, so also split a Shift_JIS body mid-character
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("日本");
+        let chunks: Vec<Result<Bytes, reqwest::Error>> = sjis
+            .chunks(1)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        let mut stream = ResponseStream::new(futures::stream::iter(chunks), 1024 * 1024);
+        stream.transcode_from(&detector::detect(Some("text/html; charset=shift_jis"), &[]));
+
+        let mut output = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            output.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "日本");
+    }
+
+    #[test]
+    fn test_build_request_applies_method_headers_cookies_and_body() {
+        let client = StreamingClient::new().unwrap();
+        let config = FetchConfig {
+            method: HttpMethod::Post,
+            headers: [
+                ("X-Test".to_string(), "1".to_string()),
+                ("Cookie".to_string(), "existing=1".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            cookies: [
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "1".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            body: Some(RequestBody::Json(serde_json::json!({ "q": "rust" }))),
+            ..FetchConfig::default()
+        };
+
+        let request = client
+            .build_request("http://example.com/search", &config)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.method(), reqwest::Method::POST);
+        assert_eq!(request.headers()["x-test"], "1");
+        assert_eq!(request.headers()["cookie"], "existing=1; a=1; b=2");
+        assert_eq!(request.headers()["content-type"], "application/json");
+        assert_eq!(
+            request.body().and_then(|b| b.as_bytes()),
+            Some(br#"{"q":"rust"}"#.as_slice())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_revalidates_with_etag() {
+        use axum::http::{HeaderMap, StatusCode, header};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = axum::Router::new().route(
+            "/page",
+            axum::routing::get(move |headers: HeaderMap| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let cache_headers = [
+                        (header::ETAG, "\"v1\""),
+                        (header::CACHE_CONTROL, "no-cache"),
+                        (header::CONTENT_TYPE, "text/html"),
+                    ];
+                    if headers
+                        .get(header::IF_NONE_MATCH)
+                        .is_some_and(|v| v == "\"v1\"")
+                    {
+                        (StatusCode::NOT_MODIFIED, cache_headers, "")
+                    } else {
+                        (StatusCode::OK, cache_headers, "<html>cached</html>")
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/page", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let cache = HttpCache::new(Default::default()).unwrap();
+        let client = StreamingClient::new().unwrap().with_cache(Arc::new(cache));
+        let config = FetchConfig::default();
+
+        let (body, metadata) = client.fetch_to_string(&url, &config, 1024).await.unwrap();
+        assert_eq!(body, "<html>cached</html>");
+        assert_eq!(metadata.cache_status, Some(CacheStatus::Miss));
+
+        let (body, metadata) = client.fetch_to_string(&url, &config, 1024).await.unwrap();
+        assert_eq!(body, "<html>cached</html>");
+        assert_eq!(metadata.cache_status, Some(CacheStatus::Revalidated));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain_is_recorded_and_sets_cookies_per_hop() {
+        use crate::domain::fetch::cookies::CookieJar;
+        use axum::http::{HeaderMap, StatusCode, header};
+
+        let app = axum::Router::new()
+            .route(
+                "/login",
+                axum::routing::post(|| async {
+                    (
+                        StatusCode::FOUND,
+                        [(header::LOCATION, "/home"), (header::SET_COOKIE, "sid=42")],
+                    )
+                }),
             )
-            .await;
+            .route(
+                "/home",
+                axum::routing::get(|headers: HeaderMap| async move {
+                    let cookie = headers
+                        .get(header::COOKIE)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    ([("x-seen-cookie", cookie)], "welcome")
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let warc_dir = std::env::temp_dir().join(format!("scapi-warc-{}", uuid::Uuid::new_v4()));
+        let warc = Arc::new(
+            WarcWriter::new(crate::infra::warc::WarcConfig {
+                directory: warc_dir.clone(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let client = StreamingClient::new().unwrap().with_warc(warc.clone());
+        let config = FetchConfig {
+            method: HttpMethod::Post,
+            cookie_jar: Some(Arc::new(CookieJar::new())),
+            ..FetchConfig::default()
+        };
+
+        let url = format!("{}/login", base);
+        let (body, metadata) = client.fetch_to_string(&url, &config, 1024).await.unwrap();
+        assert_eq!(body, "welcome");
+        assert_eq!(metadata.status_code, 200);
+        assert_eq!(metadata.final_url, format!("{}/home", base));
+        assert_eq!(metadata.redirects.len(), 1);
+        assert_eq!(metadata.redirects[0].url, url);
+        assert_eq!(metadata.redirects[0].status_code, 302);
+        assert_eq!(metadata.redirects[0].location, "/home");
+        assert!(
+            metadata
+                .headers
+                .contains(&("x-seen-cookie".to_string(), "sid=42".to_string()))
+        );
+
+        // Both hops are archived as request/response pairs after the warcinfo record
+        warc.flush();
+        let archive = crate::infra::warc::WarcArchive::new(&warc_dir);
+        let path = warc.current_path().unwrap();
+        let records: Vec<_> = archive
+            .records(path.file_name().unwrap().to_str().unwrap())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let targets: Vec<_> = records.iter().map(|r| r.target_uri()).collect();
+        assert_eq!(
+            targets,
+            [None, Some(url.as_str()), Some(url.as_str())]
+                .into_iter()
+                .chain([Some(metadata.final_url.as_str()); 2])
+                .collect::<Vec<_>>()
+        );
+        assert!(String::from_utf8_lossy(&records[1].block).starts_with("POST /login HTTP/1.1"));
+        let home = records[4].payload().unwrap();
+        assert_eq!(home.body, Bytes::from_static(b"welcome"));
+        assert!(
+            home.headers
+                .contains(&("x-seen-cookie".to_string(), "sid=42".to_string()))
+        );
+        assert_eq!(records[4].header("WARC-IP-Address"), Some("127.0.0.1"));
+
+        let limited = FetchConfig {
+            max_redirects: 0,
+            ..config
+        };
+        assert!(matches!(
+            client.fetch_to_string(&url, &limited, 1024).await,
+            Err(FetchError::TooManyRedirects(_))
+        ));
+        let _ = std::fs::remove_dir_all(warc_dir);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_redirect_chain_replays_offline() {
+        use crate::infra::http::transport::RecordTransport;
+        use axum::http::{StatusCode, header};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = axum::Router::new()
+            .route(
+                "/old",
+                axum::routing::get(|| async {
+                    (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, "/new")])
+                }),
+            )
+            .route(
+                "/new",
+                axum::routing::get(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    ([(header::CONTENT_TYPE, "text/html")], "<p>moved</p>")
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = std::env::temp_dir().join(format!("scapi-replay-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(FixtureStore::new(dir.clone()).unwrap());
+        let config = FetchConfig::default();
+        let url = format!("{}/old", base);
 
-        // Note: This test requires internet access and httpbin.org to be up.
-        // In a real environment, we should mock the request.
-        // For now, we assume failure path logic is correct.
-        // We can check if it compiles.
+        let recorder = StreamingClient::new()
+            .unwrap()
+            .with_transport(Arc::new(RecordTransport::new(store.clone())));
+        let (recorded, _) = recorder.fetch_to_string(&url, &config, 1024).await.unwrap();
+        store.flush().await;
+
+        let replayer = StreamingClient::new()
+            .unwrap()
+            .with_transport(Arc::new(ReplayTransport::new(store)));
+        let (replayed, metadata) = replayer.fetch_to_string(&url, &config, 1024).await.unwrap();
+        assert_eq!(replayed, recorded);
+        assert_eq!(replayed, "<p>moved</p>");
+        assert_eq!(metadata.final_url, format!("{}/new", base));
+        assert_eq!(metadata.redirects.len(), 1);
+        assert_eq!(metadata.redirects[0].status_code, 301);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_download_resumes_with_range() {
+        use axum::http::{HeaderMap, StatusCode, header};
+        use axum::response::IntoResponse;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let data: Bytes = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (body, drop_once) = (data.clone(), dropped.clone());
+        let app = axum::Router::new().route(
+            "/dump",
+            axum::routing::get(move |headers: HeaderMap| async move {
+                let etag = [(header::ETAG, "\"v1\"")];
+                let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+                let if_range = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok());
+                if let Some((start, end)) =
+                    range.and_then(|r| r.strip_prefix("bytes=")?.split_once('-'))
+                    && if_range.is_none_or(|validator| validator == "\"v1\"")
+                {
+                    let start: usize = start.parse().unwrap();
+                    let end: usize = end.parse().unwrap_or(body.len() - 1);
+                    let content_range = format!("bytes {}-{}/{}", start, end, body.len());
+                    return (
+                        StatusCode::PARTIAL_CONTENT,
+                        etag,
+                        [(header::CONTENT_RANGE, content_range)],
+                        body.slice(start..=end),
+                    )
+                        .into_response();
+                }
+                if drop_once.swap(true, Ordering::SeqCst) {
+                    return (etag, body.clone()).into_response();
+                }
+                // Send the first half, then fail the connection
+                let first = futures::stream::iter([Ok(body.slice(..2048))]);
+                let reset = futures::stream::once(async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Err(std::io::Error::other("connection reset"))
+                });
+                (
+                    etag,
+                    [
+                        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                        (header::CONTENT_LENGTH, body.len().to_string()),
+                    ],
+                    axum::body::Body::from_stream(first.chain(reset)),
+                )
+                    .into_response()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/dump", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = StreamingClient::new().unwrap();
+        let config = FetchConfig {
+            retry: crate::domain::fetch::RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+                ..Default::default()
+            },
+            ..FetchConfig::default()
+        };
+        let mut written = Vec::new();
+        let metadata = client
+            .fetch_to_writer(&url, &config, &mut written)
+            .await
+            .unwrap();
+        assert_eq!(written, data);
+        assert_eq!(metadata.length, data.len());
+        assert_eq!(metadata.status_code, 200);
+
+        let result = client
+            .fetch_range(&url, &config, ByteRange::new(100, 199).unwrap(), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 206);
+        assert_eq!(result.content_range().unwrap().total, Some(4096));
+        assert_eq!(result.content_length, Some(100));
+
+        // A changed resource is not stitched onto the old bytes
+        let mut resume = ResumeState::new(1024, Some("\"v0\"".to_string()));
+        let mut partial = data.slice(..1024).to_vec();
+        assert!(matches!(
+            client
+                .fetch_to_writer_from(&url, &config, &mut partial, &mut resume)
+                .await,
+            Err(FetchError::ResumeFailed(_))
+        ));
+        assert_eq!(partial.len(), 1024);
+
+        // Without a validator any version could answer the range
+        let mut resume = ResumeState::new(1024, None);
+        assert!(matches!(
+            client
+                .fetch_to_writer_from(&url, &config, &mut partial, &mut resume)
+                .await,
+            Err(FetchError::ResumeFailed(_))
+        ));
+        assert_eq!(partial.len(), 1024);
+    }
+
+    #[tokio::test]
+    async fn test_digest_challenge_is_answered_and_credentials_redacted() {
+        use axum::http::{HeaderMap, StatusCode, header};
+        use axum::response::IntoResponse;
+
+        let app = axum::Router::new()
+            .route(
+                "/portal",
+                axum::routing::get(|headers: HeaderMap| async move {
+                    let authorization = headers
+                        .get(header::AUTHORIZATION)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default();
+                    if authorization.starts_with("Digest username=\"scapi\"")
+                        && authorization.contains("nonce=\"n0nce\"")
+                        && authorization.contains("uri=\"/portal?page=2\"")
+                    {
+                        return "welcome".into_response();
+                    }
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        [(
+                            header::WWW_AUTHENTICATE,
+                            "Digest realm=\"portal\", qop=\"auth\", nonce=\"n0nce\"",
+                        )],
+                    )
+                        .into_response()
+                }),
+            )
+            .route(
+                "/api",
+                axum::routing::get(|headers: HeaderMap| async move {
+                    match headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+                        Some("k3y") => StatusCode::OK,
+                        _ => StatusCode::FORBIDDEN,
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let har = Arc::new(HarRecorder::new(10));
+        let client = StreamingClient::new().unwrap().with_har(har.clone());
+        let digest = FetchConfig {
+            auth: Some(Credentials::Digest {
+                username: "scapi".to_string(),
+                password: "s3cret".to_string(),
+            }),
+            ..FetchConfig::default()
+        };
+        let (body, metadata) = client
+            .fetch_to_string(&format!("{}/portal?page=2", base), &digest, 1024)
+            .await
+            .unwrap();
+        assert_eq!(body, "welcome");
+        assert_eq!(metadata.status_code, 200);
+
+        let api_key = FetchConfig {
+            auth: Some(Credentials::ApiKey {
+                header: "X-Api-Key".to_string(),
+                value: "k3y".to_string(),
+            }),
+            ..FetchConfig::default()
+        };
+        let result = client
+            .fetch_stream(&format!("{}/api", base), &api_key)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 200);
+        drop(result);
+
+        // The 401 and both authenticated requests are recorded without secrets
+        let entries = har.export().log.entries;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].response.status, 401);
+        let exported = serde_json::to_string(&entries).unwrap();
+        assert!(exported.contains(auth::REDACTED));
+        assert!(!exported.contains("response=") && !exported.contains("k3y"));
+    }
+
+    #[tokio::test]
+    async fn test_egress_policy_checks_resolved_addresses_and_redirects() {
+        use crate::domain::fetch::egress::{EgressConfig, EgressPolicy};
+        use axum::http::{StatusCode, header};
+
+        let app = axum::Router::new().route(
+            "/jump",
+            axum::routing::get(|| async {
+                (
+                    StatusCode::FOUND,
+                    [(header::LOCATION, "http://127.0.0.1:1/")],
+                )
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let config = FetchConfig::default();
+
+        // `localhost` passes the URL check and is caught by the resolver
+        let strict = ClientPool::default().with_egress(Arc::new(EgressPolicy::default()));
+        let client = StreamingClient::with_pool(strict);
+        let url = format!("http://localhost:{}/jump", port);
+        assert!(matches!(
+            client.fetch_to_string(&url, &config, 1024).await,
+            Err(FetchError::EgressDenied(_))
+        ));
+
+        // Redirect targets are checked before they are followed
+        let policy = EgressPolicy::new(EgressConfig {
+            allow_private: true,
+            denied_ports: vec![1],
+            ..Default::default()
+        });
+        let client =
+            StreamingClient::with_pool(ClientPool::default().with_egress(Arc::new(policy)));
+        let url = format!("http://127.0.0.1:{}/jump", port);
+        assert!(matches!(
+            client.fetch_to_string(&url, &config, 1024).await,
+            Err(FetchError::EgressDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dns_override_is_used_and_reported() {
+        use crate::domain::fetch::egress::EgressPolicy;
+        use crate::infra::http::resolver::{
+            DnsConfig, DnsResolver, IpPreference, ResolutionSource,
+        };
+
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "staging" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let loopback: std::net::IpAddr = "127.0.0.1".parse().unwrap();
+        let dns = DnsConfig {
+            overrides: [("staging.example.test".to_string(), vec![loopback])].into(),
+            prefer: IpPreference::Ipv4Only,
+            ..DnsConfig::default()
+        };
+        // Overridden hosts are trusted by the egress policy
+        let egress = EgressPolicy::default().with_trusted_hosts(dns.overrides.keys());
+        let pool = ClientPool::default()
+            .with_egress(Arc::new(egress))
+            .with_dns(Arc::new(DnsResolver::new(dns)));
+        let client = StreamingClient::with_pool(pool);
+
+        let url = format!("http://staging.example.test:{}/", port);
+        let (content, metadata) = client
+            .fetch_to_string(&url, &FetchConfig::default(), 1024)
+            .await
+            .unwrap();
+        assert_eq!(content, "staging");
+        let dns = metadata.dns.unwrap();
+        assert_eq!(dns.source, ResolutionSource::Override);
+        assert_eq!(dns.addresses, [loopback]);
+        assert_eq!(dns.remote_ip, Some(loopback));
     }
 }
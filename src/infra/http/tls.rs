@@ -0,0 +1,778 @@
+//! TLS settings for fetches: extra trusted roots, per-host client
+//! certificates (mTLS) and certificate pinning by SPKI hash.
+
+use base64::Engine;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::domain::fetch::auth::match_host;
+use crate::domain::fetch::error::FetchError;
+
+/// TLS configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// PEM bundles of extra trusted roots, for every host
+    pub ca_bundles: Vec<PathBuf>,
+    /// Trust the built-in web PKI roots as well as `ca_bundles`
+    pub builtin_roots: bool,
+    /// Per-host settings keyed by host, `host:port` or `*.domain`
+    pub hosts: HashMap<String, HostTlsConfig>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            ca_bundles: Vec::new(),
+            builtin_roots: true,
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Read per-host settings from a JSON object mapping host patterns to
+    /// `HostTlsConfig`.
+    ///
+    /// ```json
+    /// {
+    ///   "portal.partner.example": {
+    ///     "client_cert": "/etc/scapi/partner.pem",
+    ///     "client_key": "/etc/scapi/partner.key",
+    ///     "pins": ["sha256/n1atFX7hpbFHTwzUpGJUS/V1Ag0ofscEbFdyDqy6JCk="]
+    ///   },
+    ///   "*.staging.example.com": { "ca_bundles": ["/etc/scapi/staging-ca.pem"] }
+    /// }
+    /// ```
+    pub fn load_hosts(path: &Path) -> std::io::Result<HashMap<String, HostTlsConfig>> {
+        let hosts: HashMap<String, HostTlsConfig> =
+            serde_json::from_slice(&std::fs::read(path)?)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(hosts
+            .into_iter()
+            .map(|(pattern, host)| (pattern.trim().to_ascii_lowercase(), host))
+            .collect())
+    }
+}
+
+/// TLS settings for one host pattern.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HostTlsConfig {
+    /// PEM client certificate chain presented to the server
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key of the client certificate (defaults to `client_cert`)
+    pub client_key: Option<PathBuf>,
+    /// PEM bundles of extra trusted roots for this host
+    pub ca_bundles: Vec<PathBuf>,
+    /// Accepted SPKI hashes (`sha256/<base64>`) of the server's own key or
+    /// of a CA the server's certificate chains up to
+    pub pins: Vec<String>,
+}
+
+/// Loaded TLS settings, shared by the pooled clients.
+///
+/// Files are read once, when the settings are created, so a missing or
+/// malformed certificate fails at startup rather than on the first fetch.
+pub struct TlsSettings {
+    config: TlsConfig,
+    roots: Arc<RootCertStore>,
+    hosts: HashMap<String, HostTls>,
+}
+
+struct HostTls {
+    roots: Arc<RootCertStore>,
+    identity: Option<(Vec<Certificate>, PrivateKey)>,
+    pins: Vec<[u8; 32]>,
+    /// Trusted roots whose key is pinned, as the only anchors to verify with
+    pinned_roots: Option<Arc<RootCertStore>>,
+}
+
+impl TlsSettings {
+    /// Load the certificates, keys and pins of `config`.
+    pub fn new(config: TlsConfig) -> Result<Self, FetchError> {
+        let mut roots = RootCertStore::empty();
+        if config.builtin_roots {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            }));
+        }
+        for path in &config.ca_bundles {
+            add_roots(&mut roots, path)?;
+        }
+
+        let mut hosts = HashMap::new();
+        for (pattern, host) in &config.hosts {
+            let context = |e: FetchError| match e {
+                FetchError::TlsError(message) => {
+                    FetchError::TlsError(format!("{}: {}", pattern, message))
+                }
+                other => other,
+            };
+            let host_roots = if host.ca_bundles.is_empty() {
+                roots.clone()
+            } else {
+                let mut host_roots = roots.clone();
+                for path in &host.ca_bundles {
+                    add_roots(&mut host_roots, path).map_err(context)?;
+                }
+                host_roots
+            };
+            if host_roots.is_empty() {
+                return Err(context(FetchError::TlsError(
+                    "no trusted roots".to_string(),
+                )));
+            }
+            let identity = host
+                .client_cert
+                .as_deref()
+                .map(|cert| identity(cert, host.client_key.as_deref().unwrap_or(cert)))
+                .transpose()
+                .map_err(context)?;
+            let pins: Vec<[u8; 32]> = host
+                .pins
+                .iter()
+                .map(|pin| parse_pin(pin))
+                .collect::<Result<_, _>>()
+                .map_err(context)?;
+            let pinned_roots = pinned_roots(&config, host, &pins).map_err(context)?;
+            hosts.insert(
+                pattern.clone(),
+                HostTls {
+                    roots: Arc::new(host_roots),
+                    identity,
+                    pins,
+                    pinned_roots,
+                },
+            );
+        }
+
+        let settings = Self {
+            config,
+            roots: Arc::new(roots),
+            hosts,
+        };
+        // Surface invalid keys now rather than on first use
+        for pattern in settings.hosts.keys() {
+            settings.client_config(Some(pattern), true)?;
+        }
+        Ok(settings)
+    }
+
+    /// TLS configuration.
+    pub fn config(&self) -> &TlsConfig {
+        &self.config
+    }
+
+    /// Whether these are the client library's own defaults (built-in roots only).
+    pub fn is_default(&self) -> bool {
+        self.config.builtin_roots && self.config.ca_bundles.is_empty() && self.hosts.is_empty()
+    }
+
+    /// Host pattern whose settings apply to `url`, if any.
+    pub fn profile_for(&self, url: &str) -> Option<&str> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+        let url = url::Url::parse(url).ok()?;
+        match_host(&self.hosts, &url).map(|(pattern, _)| pattern)
+    }
+
+    /// Build the rustls configuration for host pattern `profile` (or the
+    /// global settings), verifying server certificates unless `verify` is off.
+    ///
+    /// Pins are enforced even when verification is off: a pinned CA must
+    /// then still have issued the server's certificate for the host.
+    pub fn client_config(
+        &self,
+        profile: Option<&str>,
+        verify: bool,
+    ) -> Result<ClientConfig, FetchError> {
+        let host = profile.and_then(|pattern| self.hosts.get(pattern));
+        let roots = host.map_or(&self.roots, |host| &host.roots);
+        let verifier = PinningVerifier {
+            webpki: verify.then(|| WebPkiVerifier::new(roots.clone(), None)),
+            pins: host.map(|host| host.pins.clone()).unwrap_or_default(),
+            pinned_roots: host
+                .and_then(|host| host.pinned_roots.clone())
+                .map(|roots| WebPkiVerifier::new(roots, None)),
+        };
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+        let mut config = match host.and_then(|host| host.identity.clone()) {
+            Some((chain, key)) => builder.with_client_auth_cert(chain, key).map_err(|e| {
+                FetchError::TlsError(format!(
+                    "{}: client key: {}",
+                    profile.unwrap_or_default(),
+                    e
+                ))
+            })?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+impl Default for TlsSettings {
+    fn default() -> Self {
+        Self::new(TlsConfig::default()).expect("built-in roots load")
+    }
+}
+
+impl std::fmt::Debug for TlsSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsSettings")
+            .field("config", &self.config)
+            .field("roots", &self.roots.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Verifies the chain against the trusted roots (unless disabled), then
+/// requires a pinned key, if any are set.
+///
+/// The server's own key matches directly. A pinned trusted root matches when
+/// the chain verifies with the pinned roots as the only anchors, as servers
+/// rarely send their root. Any other certificate the server sends only
+/// matches once the chain verifies with it as the sole trust anchor, so
+/// certificates merely appended to the handshake do not count.
+struct PinningVerifier {
+    webpki: Option<WebPkiVerifier>,
+    pins: Vec<[u8; 32]>,
+    pinned_roots: Option<WebPkiVerifier>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = match &self.webpki {
+            Some(webpki) => webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?,
+            None => ServerCertVerified::assertion(),
+        };
+        if self.pins.is_empty() {
+            return Ok(verified);
+        }
+
+        let bad_encoding =
+            || rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding);
+        let leaf = spki_sha256(&end_entity.0).ok_or_else(bad_encoding)?;
+        if self.pins.contains(&leaf) {
+            return Ok(verified);
+        }
+        if let Some(pinned_roots) = &self.pinned_roots
+            && pinned_roots
+                .verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    &mut std::iter::empty(),
+                    ocsp_response,
+                    now,
+                )
+                .is_ok()
+        {
+            return Ok(verified);
+        }
+        for (index, cert) in intermediates.iter().enumerate() {
+            let hash = spki_sha256(&cert.0).ok_or_else(bad_encoding)?;
+            if !self.pins.contains(&hash) {
+                continue;
+            }
+            let mut anchor = RootCertStore::empty();
+            if anchor.add(cert).is_err() {
+                continue;
+            }
+            let others: Vec<Certificate> = intermediates
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, cert)| cert.clone())
+                .collect();
+            if WebPkiVerifier::new(anchor, None)
+                .verify_server_cert(
+                    end_entity,
+                    &others,
+                    server_name,
+                    &mut std::iter::empty(),
+                    ocsp_response,
+                    now,
+                )
+                .is_ok()
+            {
+                return Ok(verified);
+            }
+        }
+        Err(rustls::Error::General(format!(
+            "certificate pin mismatch: no pinned key in the verified chain (server key {})",
+            format_pin(&leaf)
+        )))
+    }
+}
+
+/// Recover a TLS failure from a client error's source chain.
+pub(crate) fn tls_failure(error: &reqwest::Error, host: &str) -> Option<FetchError> {
+    let mut source = std::error::Error::source(error);
+    while let Some(error) = source {
+        if let Some(tls) = error.downcast_ref::<rustls::Error>() {
+            return Some(FetchError::TlsError(format!(
+                "handshake with {} failed: {}",
+                host, tls
+            )));
+        }
+        // I/O errors skip the error they wrap in `source()`
+        source = match error
+            .downcast_ref::<std::io::Error>()
+            .and_then(|e| e.get_ref())
+        {
+            Some(inner) => Some(inner),
+            None => error.source(),
+        };
+    }
+    None
+}
+
+/// Format a SHA-256 SPKI hash as a pin (`sha256/<base64>`).
+pub fn format_pin(hash: &[u8; 32]) -> String {
+    format!(
+        "sha256/{}",
+        base64::engine::general_purpose::STANDARD.encode(hash)
+    )
+}
+
+/// Parse a `sha256/<base64>` pin (curl's `sha256//<base64>` is accepted too).
+fn parse_pin(pin: &str) -> Result<[u8; 32], FetchError> {
+    let invalid = || FetchError::TlsError(format!("invalid pin {:?}", pin));
+    let encoded = pin
+        .trim()
+        .strip_prefix("sha256/")
+        .ok_or_else(invalid)?
+        .trim_start_matches('/');
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(invalid)
+}
+
+/// Add the certificates of the PEM bundle at `path` to `roots`.
+fn add_roots(roots: &mut RootCertStore, path: &Path) -> Result<(), FetchError> {
+    let certs = read_certs(path)?;
+    let (added, _) = roots.add_parsable_certificates(&certs);
+    if added == 0 {
+        return Err(FetchError::TlsError(format!(
+            "CA bundle {}: no usable certificates",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// The roots trusted for `host` whose key is one of `pins`, if any.
+fn pinned_roots(
+    config: &TlsConfig,
+    host: &HostTlsConfig,
+    pins: &[[u8; 32]],
+) -> Result<Option<Arc<RootCertStore>>, FetchError> {
+    if pins.is_empty() {
+        return Ok(None);
+    }
+    let mut roots = RootCertStore::empty();
+    if config.builtin_roots {
+        roots.add_trust_anchors(
+            webpki_roots::TLS_SERVER_ROOTS
+                .iter()
+                .filter(|anchor| pins.contains(&Sha256::digest(der_sequence(anchor.spki)).into()))
+                .map(|anchor| {
+                    OwnedTrustAnchor::from_subject_spki_name_constraints(
+                        anchor.subject,
+                        anchor.spki,
+                        anchor.name_constraints,
+                    )
+                }),
+        );
+    }
+    for path in config.ca_bundles.iter().chain(&host.ca_bundles) {
+        for der in read_certs(path)? {
+            if spki_sha256(&der).is_some_and(|hash| pins.contains(&hash)) {
+                let _ = roots.add(&Certificate(der));
+            }
+        }
+    }
+    Ok((!roots.is_empty()).then(|| Arc::new(roots)))
+}
+
+/// Read the DER certificates of the PEM bundle at `path`.
+fn read_certs(path: &Path) -> Result<Vec<Vec<u8>>, FetchError> {
+    read_pem(path, |item| match item {
+        rustls_pemfile::Item::X509Certificate(der) => Some(der),
+        _ => None,
+    })
+}
+
+/// Read a client certificate chain and its private key.
+fn identity(cert: &Path, key: &Path) -> Result<(Vec<Certificate>, PrivateKey), FetchError> {
+    let chain: Vec<Certificate> = read_pem(cert, |item| match item {
+        rustls_pemfile::Item::X509Certificate(der) => Some(Certificate(der)),
+        _ => None,
+    })?;
+    if chain.is_empty() {
+        return Err(FetchError::TlsError(format!(
+            "client certificate {}: no certificates",
+            cert.display()
+        )));
+    }
+    let key = read_pem(key, |item| match item {
+        rustls_pemfile::Item::PKCS8Key(der)
+        | rustls_pemfile::Item::RSAKey(der)
+        | rustls_pemfile::Item::ECKey(der) => Some(PrivateKey(der)),
+        _ => None,
+    })?
+    .into_iter()
+    .next()
+    .ok_or_else(|| FetchError::TlsError(format!("client key {}: no private key", key.display())))?;
+    Ok((chain, key))
+}
+
+/// Read the PEM items of `path` that `select` keeps.
+fn read_pem<T>(
+    path: &Path,
+    select: impl FnMut(rustls_pemfile::Item) -> Option<T>,
+) -> Result<Vec<T>, FetchError> {
+    let error = |e: std::io::Error| FetchError::TlsError(format!("{}: {}", path.display(), e));
+    let file = std::fs::File::open(path).map_err(error)?;
+    let items = rustls_pemfile::read_all(&mut std::io::BufReader::new(file)).map_err(error)?;
+    Ok(items.into_iter().filter_map(select).collect())
+}
+
+/// SHA-256 of the DER SubjectPublicKeyInfo of a DER X.509 certificate.
+pub fn spki_sha256(cert: &[u8]) -> Option<[u8; 32]> {
+    // Certificate ::= SEQUENCE { tbsCertificate SEQUENCE { [0] version OPTIONAL,
+    //   serialNumber, signature, issuer, validity, subject, subjectPublicKeyInfo, ... } }
+    let (_, certificate, _) = der_element(cert)?;
+    let (_, mut tbs, _) = der_element(certificate)?;
+    let (tag, _, rest) = der_element(tbs)?;
+    if tag == 0xa0 {
+        tbs = rest;
+    }
+    for _ in 0..5 {
+        tbs = der_element(tbs)?.2;
+    }
+    let spki_len = tbs.len() - der_element(tbs)?.2.len();
+    Some(Sha256::digest(&tbs[..spki_len]).into())
+}
+
+/// Wrap `contents` in a DER SEQUENCE (trust anchors store their SPKI without it).
+fn der_sequence(contents: &[u8]) -> Vec<u8> {
+    let mut der = vec![0x30];
+    if contents.len() < 0x80 {
+        der.push(contents.len() as u8);
+    } else {
+        let len = contents.len().to_be_bytes();
+        let len = &len[len.iter().take_while(|&&byte| byte == 0).count()..];
+        der.push(0x80 | len.len() as u8);
+        der.extend_from_slice(len);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// Split one DER element off `input`: its tag, contents and the remainder.
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, &byte| (len << 8) | byte as usize);
+        (len, &rest[count..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::fetch::config::FetchConfig;
+    use crate::infra::http::pool::ClientPool;
+    use crate::infra::http::streaming::StreamingClient;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const SERVER_PIN: &str = "sha256/n1atFX7hpbFHTwzUpGJUS/V1Ag0ofscEbFdyDqy6JCk=";
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/tls")
+            .join(name)
+    }
+
+    fn pin_of(name: &str) -> String {
+        format_pin(&spki_sha256(&read_certs(&fixture(name)).unwrap()[0]).unwrap())
+    }
+
+    /// HTTPS server that requires a client certificate issued by the test CA,
+    /// sending `extra` certificates after its own.
+    async fn mtls_server(extra: &[&str]) -> u16 {
+        let chain: Vec<&str> = ["server.pem"].into_iter().chain(extra.to_vec()).collect();
+        tls_server(&chain, "server.key").await
+    }
+
+    /// HTTPS server that requires a client certificate issued by the test CA,
+    /// sending the certificates of `chain` with `key`.
+    async fn tls_server(chain: &[&str], key: &str) -> u16 {
+        let mut client_roots = RootCertStore::empty();
+        add_roots(&mut client_roots, &fixture("ca.pem")).unwrap();
+        let (_, key) = identity(&fixture(chain[0]), &fixture(key)).unwrap();
+        let chain = chain
+            .iter()
+            .flat_map(|name| read_certs(&fixture(name)).unwrap())
+            .map(Certificate)
+            .collect();
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(
+                rustls::server::AllowAnyAuthenticatedClient::new(client_roots).boxed(),
+            )
+            .with_single_cert(chain, key)
+            .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buffer[..n]),
+                        }
+                    }
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 7\r\nconnection: close\r\n\r\npartner")
+                        .await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        port
+    }
+
+    async fn fetch(config: TlsConfig, port: u16) -> Result<String, FetchError> {
+        fetch_with(config, port, true).await
+    }
+
+    async fn fetch_with(config: TlsConfig, port: u16, verify: bool) -> Result<String, FetchError> {
+        let fetch_config = FetchConfig {
+            verify_tls: verify,
+            ..FetchConfig::default()
+        };
+        let pool = ClientPool::default().with_tls(Arc::new(TlsSettings::new(config)?));
+        let url = format!("https://localhost:{}/", port);
+        StreamingClient::with_pool(pool)
+            .fetch_to_string(&url, &fetch_config, 1024)
+            .await
+            .map(|(content, _)| content)
+    }
+
+    #[test]
+    fn test_spki_pin_of_certificate() {
+        let cert = read_certs(&fixture("server.pem")).unwrap();
+        // openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256
+        assert_eq!(format_pin(&spki_sha256(&cert[0]).unwrap()), SERVER_PIN);
+        assert_eq!(
+            parse_pin(&SERVER_PIN.replacen("/", "//", 1)).unwrap(),
+            parse_pin(SERVER_PIN).unwrap()
+        );
+        assert!(parse_pin("sha1/AAAA").is_err());
+        assert!(spki_sha256(b"\x30\x03\x02\x01").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_private_ca_and_pins() {
+        let port = mtls_server(&[]).await;
+        let partner = HostTlsConfig {
+            client_cert: Some(fixture("client.pem")),
+            client_key: Some(fixture("client.key")),
+            pins: vec![SERVER_PIN.to_string()],
+            ..HostTlsConfig::default()
+        };
+        let config = TlsConfig {
+            ca_bundles: vec![fixture("ca.pem")],
+            builtin_roots: false,
+            hosts: [("localhost".to_string(), partner.clone())].into(),
+        };
+        assert_eq!(fetch(config.clone(), port).await.unwrap(), "partner");
+
+        // Untrusted issuer
+        let untrusted = TlsConfig {
+            ca_bundles: Vec::new(),
+            builtin_roots: true,
+            ..config.clone()
+        };
+        let error = fetch(untrusted, port).await.unwrap_err();
+        assert!(
+            matches!(&error, FetchError::TlsError(message) if message.contains("UnknownIssuer")),
+            "{}",
+            error
+        );
+
+        // No client certificate
+        let anonymous = TlsConfig {
+            hosts: HashMap::new(),
+            ..config.clone()
+        };
+        assert!(matches!(
+            fetch(anonymous, port).await,
+            Err(FetchError::TlsError(_))
+        ));
+
+        // Pin of another key
+        let mut pinned = config.clone();
+        pinned.hosts.get_mut("localhost").unwrap().pins = vec![format_pin(&[0; 32])];
+        let error = fetch(pinned, port).await.unwrap_err();
+        assert!(
+            matches!(&error, FetchError::TlsError(message) if message.contains(SERVER_PIN)),
+            "{}",
+            error
+        );
+
+        // Unreadable key files fail up front
+        let mut broken = config;
+        broken.hosts.get_mut("localhost").unwrap().client_key = Some(fixture("missing.key"));
+        assert!(matches!(
+            TlsSettings::new(broken),
+            Err(FetchError::TlsError(message)) if message.starts_with("localhost: ")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pins_ignore_appended_certificates() {
+        // The server appends another CA-issued certificate and the CA itself
+        let port = mtls_server(&["client.pem", "ca.pem"]).await;
+        let pinned = |pin: String| TlsConfig {
+            ca_bundles: vec![fixture("ca.pem")],
+            builtin_roots: false,
+            hosts: [(
+                "localhost".to_string(),
+                HostTlsConfig {
+                    client_cert: Some(fixture("client.pem")),
+                    client_key: Some(fixture("client.key")),
+                    pins: vec![pin],
+                    ..HostTlsConfig::default()
+                },
+            )]
+            .into(),
+        };
+
+        for verify in [true, false] {
+            let error = fetch_with(pinned(pin_of("client.pem")), port, verify)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&error, FetchError::TlsError(message) if message.contains("pin mismatch")),
+                "{}",
+                error
+            );
+            // The CA the server's certificate chains up to
+            assert_eq!(
+                fetch_with(pinned(pin_of("ca.pem")), port, verify)
+                    .await
+                    .unwrap(),
+                "partner"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pinned_root_the_server_does_not_send() {
+        // Leaf and intermediate only, as servers usually send
+        let port = tls_server(
+            &["chain-server.pem", "chain-intermediate.pem"],
+            "chain-server.key",
+        )
+        .await;
+        let pinned = |pin: String| TlsConfig {
+            ca_bundles: vec![fixture("chain-root.pem")],
+            builtin_roots: false,
+            hosts: [(
+                "localhost".to_string(),
+                HostTlsConfig {
+                    client_cert: Some(fixture("client.pem")),
+                    client_key: Some(fixture("client.key")),
+                    pins: vec![pin],
+                    ..HostTlsConfig::default()
+                },
+            )]
+            .into(),
+        };
+
+        for verify in [true, false] {
+            for ca in ["chain-root.pem", "chain-intermediate.pem"] {
+                assert_eq!(
+                    fetch_with(pinned(pin_of(ca)), port, verify).await.unwrap(),
+                    "partner"
+                );
+            }
+            // A CA outside the chain
+            assert!(matches!(
+                fetch_with(pinned(pin_of("ca.pem")), port, verify).await,
+                Err(FetchError::TlsError(message)) if message.contains("pin mismatch")
+            ));
+        }
+
+        // Built-in roots are matched by their key too (ISRG Root X1)
+        let settings = TlsSettings::new(TlsConfig {
+            hosts: [(
+                "letsencrypt.org".to_string(),
+                HostTlsConfig {
+                    pins: vec!["sha256/C5+lpZ7tcVwmwQIMcRtPbsQtWLABXhQzejna0wHFr8M=".to_string()],
+                    ..HostTlsConfig::default()
+                },
+            )]
+            .into(),
+            ..TlsConfig::default()
+        })
+        .unwrap();
+        let host = &settings.hosts["letsencrypt.org"];
+        assert_eq!(host.pinned_roots.as_ref().map(|roots| roots.len()), Some(1));
+    }
+}
@@ -0,0 +1,519 @@
+//! Pluggable HTTP transport: live, record and replay.
+//!
+//! The streaming client hands every prepared request (one per redirect hop)
+//! to a `Transport`. `LiveTransport` sends it with `reqwest`,
+//! `RecordTransport` does the same and saves the response to a
+//! `FixtureStore`, and `ReplayTransport` answers only from the store, so
+//! tests and offline runs are deterministic.
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::stream::Stream;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+
+use crate::common::fs;
+use crate::domain::fetch::error::FetchError;
+use crate::infra::http::resolver::egress_violation;
+use crate::infra::http::streaming::header_pairs;
+use crate::infra::http::tls::tls_failure;
+
+/// Response body as read from the network
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>;
+
+/// Which transport the streaming client uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportMode {
+    /// Send requests over the network
+    #[default]
+    Live,
+    /// Send requests over the network and save the responses as fixtures
+    Record,
+    /// Serve responses only from fixtures; a request without one fails
+    Replay,
+}
+
+/// Transport configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransportConfig {
+    /// Transport used for requests
+    pub mode: TransportMode,
+    /// Directory fixtures are recorded to and replayed from
+    pub fixture_dir: PathBuf,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            mode: TransportMode::Live,
+            fixture_dir: PathBuf::from("fixtures/http"),
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Build the configured transport.
+    pub fn build(&self) -> std::io::Result<Arc<dyn Transport>> {
+        Ok(match self.mode {
+            TransportMode::Live => Arc::new(LiveTransport),
+            TransportMode::Record => Arc::new(RecordTransport::new(Arc::new(FixtureStore::new(
+                self.fixture_dir.clone(),
+            )?))),
+            TransportMode::Replay => Arc::new(ReplayTransport::new(Arc::new(FixtureStore::new(
+                self.fixture_dir.clone(),
+            )?))),
+        })
+    }
+}
+
+/// Response status, headers and body as produced by a transport.
+pub struct TransportResponse {
+    status: reqwest::StatusCode,
+    version: reqwest::Version,
+    url: url::Url,
+    headers: HeaderMap,
+    remote_addr: Option<SocketAddr>,
+    body: BodyStream,
+}
+
+impl TransportResponse {
+    /// Create an HTTP/1.1 response for `url`.
+    pub fn new(
+        status: reqwest::StatusCode,
+        url: url::Url,
+        headers: HeaderMap,
+        body: BodyStream,
+    ) -> Self {
+        Self {
+            status,
+            version: reqwest::Version::HTTP_11,
+            url,
+            headers,
+            remote_addr: None,
+            body,
+        }
+    }
+
+    /// Wrap a response received by `reqwest`.
+    pub fn from_reqwest(response: reqwest::Response) -> Self {
+        Self {
+            status: response.status(),
+            version: response.version(),
+            url: response.url().clone(),
+            headers: response.headers().clone(),
+            remote_addr: response.remote_addr(),
+            body: Box::pin(response.bytes_stream()),
+        }
+    }
+
+    /// Response status code.
+    pub fn status(&self) -> reqwest::StatusCode {
+        self.status
+    }
+
+    /// HTTP version of the response.
+    pub fn version(&self) -> reqwest::Version {
+        self.version
+    }
+
+    /// URL the response was received from.
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Response headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Address the response came from, if known.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// `Content-Length` header, if present and valid.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    }
+
+    /// Consume the response, returning its body.
+    pub fn bytes_stream(self) -> BodyStream {
+        self.body
+    }
+}
+
+/// Sends prepared requests.
+///
+/// `client` is the pooled client selected for the request's configuration;
+/// transports that do not touch the network may ignore it.
+pub trait Transport: Send + Sync + std::fmt::Debug {
+    /// Send `request`, resolving once the response headers are available.
+    fn send(
+        &self,
+        client: reqwest::Client,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<TransportResponse, FetchError>>;
+
+    /// Whether requests reach the network (and their hosts need resolving).
+    fn connects(&self) -> bool {
+        true
+    }
+}
+
+/// Transport that sends requests over the network.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiveTransport;
+
+impl Transport for LiveTransport {
+    fn send(
+        &self,
+        client: reqwest::Client,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<TransportResponse, FetchError>> {
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        Box::pin(async move {
+            let response = client.execute(request).await.map_err(|e| {
+                if let Some(violation) = egress_violation(&e) {
+                    violation
+                } else if let Some(failure) = tls_failure(&e, &host) {
+                    failure
+                } else if e.is_timeout() {
+                    FetchError::Timeout(e.to_string())
+                } else {
+                    FetchError::NetworkError(e.to_string())
+                }
+            })?;
+            Ok(TransportResponse::from_reqwest(response))
+        })
+    }
+}
+
+/// Transport that sends requests over the network and records the responses.
+///
+/// A response is saved once its body has been read to the end, or with the
+/// part that was read when the body is dropped early (e.g. redirect hops or
+/// bodies over the size limit), so replay reproduces what the client saw.
+#[derive(Debug, Clone)]
+pub struct RecordTransport {
+    store: Arc<FixtureStore>,
+}
+
+impl RecordTransport {
+    /// Record responses to `store`.
+    pub fn new(store: Arc<FixtureStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Transport for RecordTransport {
+    fn send(
+        &self,
+        client: reqwest::Client,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<TransportResponse, FetchError>> {
+        let store = self.store.clone();
+        let key = FixtureStore::key(&request);
+        let live = LiveTransport.send(client, request);
+        Box::pin(async move {
+            let response = live.await?;
+            let recorded = RecordedResponse {
+                status: response.status.as_u16(),
+                url: response.url.to_string(),
+                headers: header_pairs(&response.headers),
+                body: Bytes::new(),
+            };
+            let body = response.body;
+            Ok(TransportResponse {
+                body: Box::pin(RecordingBody {
+                    inner: body,
+                    buffer: BytesMut::new(),
+                    pending: Some((store, key, recorded)),
+                }),
+                ..response
+            })
+        })
+    }
+}
+
+/// Transport that serves responses only from recorded fixtures.
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    store: Arc<FixtureStore>,
+}
+
+impl ReplayTransport {
+    /// Replay responses from `store`.
+    pub fn new(store: Arc<FixtureStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(
+        &self,
+        _client: reqwest::Client,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<TransportResponse, FetchError>> {
+        let store = self.store.clone();
+        Box::pin(async move {
+            let key = FixtureStore::key(&request);
+            let recorded = store
+                .get(&key)
+                .map_err(|e| FetchError::Other(format!("fixture for {}: {}", key, e)))?
+                .ok_or_else(|| FetchError::ReplayMiss(key.clone()))?;
+            recorded.into_response()
+        })
+    }
+
+    fn connects(&self) -> bool {
+        false
+    }
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// Status code
+    pub status: u16,
+    /// URL the response was received from
+    pub url: String,
+    /// Response headers (lowercase names, in received order)
+    pub headers: Vec<(String, String)>,
+    /// Body (stored after the JSON header line)
+    #[serde(skip)]
+    pub body: Bytes,
+}
+
+impl RecordedResponse {
+    fn into_response(self) -> Result<TransportResponse, FetchError> {
+        let status = reqwest::StatusCode::from_u16(self.status)
+            .map_err(|e| FetchError::Other(format!("recorded status {}: {}", self.status, e)))?;
+        let url = url::Url::parse(&self.url).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()),
+                reqwest::header::HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        let body = futures::stream::once(futures::future::ready(Ok(self.body)));
+        Ok(TransportResponse::new(status, url, headers, Box::pin(body)))
+    }
+}
+
+/// Directory of recorded responses, one file per request.
+///
+/// Requests are keyed by method, URL and a digest of the body. Each file
+/// starts with a JSON line holding the key and the response metadata,
+/// followed by the raw body.
+#[derive(Debug)]
+pub struct FixtureStore {
+    directory: PathBuf,
+    /// Number of recordings being written in the background
+    saving: watch::Sender<usize>,
+}
+
+/// Header line of a fixture file.
+#[derive(Serialize, Deserialize)]
+struct FixtureHeader {
+    key: String,
+    response: RecordedResponse,
+    body_length: usize,
+}
+
+impl FixtureStore {
+    /// Create a store under `directory`, creating it if needed.
+    pub fn new(directory: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            saving: watch::Sender::new(0),
+        })
+    }
+
+    /// Fixture key of `request`.
+    pub fn key(request: &reqwest::Request) -> String {
+        let mut key = format!("{} {}", request.method(), request.url());
+        if let Some(body) = request.body().and_then(|body| body.as_bytes())
+            && !body.is_empty()
+        {
+            key.push(' ');
+            key.push_str(&hex(&Sha1::digest(body)));
+        }
+        key
+    }
+
+    /// Recorded response for `key`, if any.
+    pub fn get(&self, key: &str) -> std::io::Result<Option<RecordedResponse>> {
+        let file = match std::fs::File::open(self.path_for(key)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let header: FixtureHeader = serde_json::from_str(&line)?;
+        // Hash collision: the file belongs to another request
+        if header.key != key {
+            return Ok(None);
+        }
+
+        let mut body = vec![0; header.body_length];
+        reader.read_exact(&mut body)?;
+        let mut response = header.response;
+        response.body = Bytes::from(body);
+        Ok(Some(response))
+    }
+
+    /// Save `response` under `key`, replacing any earlier recording.
+    pub fn put(&self, key: &str, response: &RecordedResponse) -> std::io::Result<()> {
+        let header = FixtureHeader {
+            key: key.to_string(),
+            response: response.clone(),
+            body_length: response.body.len(),
+        };
+
+        fs::write_atomic(&self.path_for(key), |file| {
+            serde_json::to_writer(&mut *file, &header)?;
+            file.write_all(b"\n")?;
+            file.write_all(&response.body)
+        })
+    }
+
+    /// Save `response` like [`FixtureStore::put`], off the runtime when
+    /// called from it; [`FixtureStore::flush`] waits for the write.
+    pub fn put_in_background(self: &Arc<Self>, key: String, response: RecordedResponse) {
+        self.saving.send_modify(|saving| *saving += 1);
+        let store = self.clone();
+        fs::spawn_blocking_io(move || {
+            if let Err(e) = store.put(&key, &response) {
+                tracing::warn!("Failed to record fixture for {}: {}", key, e);
+            }
+            store.saving.send_modify(|saving| *saving -= 1);
+        });
+    }
+
+    /// Wait until every recording started so far is written.
+    pub async fn flush(&self) {
+        let _ = self
+            .saving
+            .subscribe()
+            .wait_for(|&saving| saving == 0)
+            .await;
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        // FNV-1a: stable across builds, unlike std's default hasher
+        let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        self.directory.join(format!("{:016x}.fixture", hash))
+    }
+}
+
+/// Body wrapper that saves the recorded response when the body ends or is dropped.
+struct RecordingBody {
+    inner: BodyStream,
+    buffer: BytesMut,
+    pending: Option<(Arc<FixtureStore>, String, RecordedResponse)>,
+}
+
+impl RecordingBody {
+    fn save(&mut self) {
+        if let Some((store, key, mut response)) = self.pending.take() {
+            response.body = std::mem::take(&mut self.buffer).freeze();
+            store.put_in_background(key, response);
+        }
+    }
+}
+
+impl Stream for RecordingBody {
+    type Item = Result<Bytes, reqwest::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = this.inner.as_mut().poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(chunk))) if this.pending.is_some() => {
+                this.buffer.extend_from_slice(chunk);
+            }
+            // A failed read is not worth replaying
+            Poll::Ready(Some(Err(_))) => this.pending = None,
+            Poll::Ready(None) => this.save(),
+            _ => {}
+        }
+        item
+    }
+}
+
+impl Drop for RecordingBody {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_replay_serves_recorded_responses_and_fails_on_miss() {
+        let dir = std::env::temp_dir().join(format!("scapi-fixtures-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(FixtureStore::new(dir.clone()).unwrap());
+        let client = reqwest::Client::new();
+
+        let request = client.get("https://example.com/page").build().unwrap();
+        store
+            .put(
+                &FixtureStore::key(&request),
+                &RecordedResponse {
+                    status: 200,
+                    url: "https://example.com/page".to_string(),
+                    headers: vec![("content-type".to_string(), "text/html".to_string())],
+                    body: Bytes::from_static(b"<p>recorded</p>"),
+                },
+            )
+            .unwrap();
+
+        let replay = ReplayTransport::new(store);
+        let response = replay.send(client.clone(), request).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/html");
+        let body: Vec<_> = response.bytes_stream().collect().await;
+        assert_eq!(body[0].as_ref().unwrap(), "<p>recorded</p>");
+
+        // Method and body are part of the key
+        let post = client
+            .post("https://example.com/page")
+            .body("a=1")
+            .build()
+            .unwrap();
+        assert!(FixtureStore::key(&post).starts_with("POST https://example.com/page "));
+        assert!(matches!(
+            replay.send(client, post).await,
+            Err(FetchError::ReplayMiss(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -2,7 +2,10 @@
 //!
 //! External integrations and utilities.
 
+pub mod encoding;
+pub mod har;
 pub mod http;
 pub mod parser;
 pub mod logging;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod warc;
\ No newline at end of file
@@ -0,0 +1,35 @@
+//! Configuration for WARC archives.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Configuration for WARC writing and `warc://` sources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarcConfig {
+    /// Record every network fetch
+    pub enabled: bool,
+    /// Directory archives are written to and `warc://` sources are read from
+    pub directory: PathBuf,
+    /// File name prefix of written archives
+    pub prefix: String,
+    /// Compress each record as its own gzip member (`.warc.gz`)
+    pub gzip: bool,
+    /// Size after which a new archive file is started
+    pub max_file_size: u64,
+    /// Response bodies are cut at this many bytes (`WARC-Truncated`)
+    pub max_record_size: usize,
+}
+
+impl Default for WarcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("warc"),
+            prefix: "scapi".to_string(),
+            gzip: true,
+            max_file_size: 1024 * 1024 * 1024,
+            max_record_size: 10 * 1024 * 1024,
+        }
+    }
+}
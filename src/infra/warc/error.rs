@@ -0,0 +1,23 @@
+//! Error types for WARC archives.
+
+use thiserror::Error;
+
+/// Errors that can occur while writing or reading WARC archives.
+#[derive(Debug, Error)]
+pub enum WarcError {
+    /// Reading or writing an archive file failed
+    #[error("WARC I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The archive is not valid WARC
+    #[error("Malformed WARC record: {0}")]
+    Format(String),
+
+    /// No archive or record matches the request
+    #[error("WARC record not found: {0}")]
+    NotFound(String),
+
+    /// A `warc://` source could not be parsed or points outside the archive directory
+    #[error("Invalid WARC source: {0}")]
+    InvalidSource(String),
+}
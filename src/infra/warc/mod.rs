@@ -0,0 +1,19 @@
+//! WARC 1.1 archives.
+//!
+//! Network fetches can be recorded as WARC `request`/`response` record pairs
+//! in rotating, optionally gzip-compressed files. The reader resolves
+//! `warc://file#record-id` sources so archived pages can be selected from and
+//! extracted again without re-crawling.
+
+pub mod config;
+pub mod error;
+pub mod reader;
+pub mod record;
+pub mod writer;
+
+// Re-exports
+pub use config::WarcConfig;
+pub use error::WarcError;
+pub use reader::{WarcArchive, WarcReader, WarcSource};
+pub use record::{HttpMessage, WarcRecord, WarcRecordType};
+pub use writer::{ExchangeSink, RecordingStream, WarcExchange, WarcWriter};
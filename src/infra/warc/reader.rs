@@ -0,0 +1,378 @@
+//! WARC reader and `warc://` sources.
+
+use bytes::Bytes;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use super::error::WarcError;
+use super::record::{WARC_VERSION, WarcRecord, WarcRecordType};
+
+/// Largest record block the reader accepts.
+const MAX_BLOCK_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Iterator over the records of a WARC file.
+pub struct WarcReader<R> {
+    inner: R,
+    failed: bool,
+}
+
+impl WarcReader<Box<dyn BufRead + Send>> {
+    /// Open a `.warc` or `.warc.gz` file; compression is detected from the content.
+    pub fn open(path: &Path) -> Result<Self, WarcError> {
+        let mut file = BufReader::new(File::open(path)?);
+        let gzipped = file.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+        let inner: Box<dyn BufRead + Send> = if gzipped {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+        Ok(Self::new(inner))
+    }
+}
+
+impl<R: BufRead> WarcReader<R> {
+    /// Read records from an uncompressed WARC stream.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            failed: false,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<WarcRecord>, WarcError> {
+        // Skip the blank lines that end the previous record
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.inner.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        if !line.trim_end().starts_with("WARC/") {
+            return Err(WarcError::Format(format!(
+                "expected {} version line, found {:?}",
+                WARC_VERSION,
+                line.trim_end()
+            )));
+        }
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        loop {
+            line.clear();
+            if self.inner.read_line(&mut line)? == 0 {
+                return Err(WarcError::Format("truncated record header".to_string()));
+            }
+            let trimmed = line.trim_end_matches(['\r', '\n']);
+            if trimmed.is_empty() {
+                break;
+            }
+            if trimmed.starts_with([' ', '\t']) {
+                // Folded continuation of the previous field
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(trimmed.trim());
+                }
+                continue;
+            }
+            let (name, value) = trimmed
+                .split_once(':')
+                .ok_or_else(|| WarcError::Format(format!("invalid field {:?}", trimmed)))?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        let length: u64 = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.parse().ok())
+            .ok_or_else(|| WarcError::Format("record without Content-Length".to_string()))?;
+        if length > MAX_BLOCK_SIZE {
+            return Err(WarcError::Format(format!(
+                "record block of {} bytes is too large",
+                length
+            )));
+        }
+
+        let mut block = Vec::with_capacity(length as usize);
+        (&mut self.inner).take(length).read_to_end(&mut block)?;
+        if (block.len() as u64) < length {
+            return Err(WarcError::Format("truncated record block".to_string()));
+        }
+        Ok(Some(WarcRecord {
+            headers,
+            block: Bytes::from(block),
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for WarcReader<R> {
+    type Item = Result<WarcRecord, WarcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let record = self.read_record();
+        self.failed = record.is_err();
+        record.transpose()
+    }
+}
+
+/// A `warc://file[#record-id]` source.
+///
+/// `file` is relative to the archive directory. Without a record id the
+/// source stands for every response and resource record in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarcSource {
+    /// Archive file, relative to the archive directory
+    pub file: String,
+    /// Record to read (`<urn:uuid:...>`, `urn:uuid:...` or the bare UUID)
+    pub record_id: Option<String>,
+}
+
+impl WarcSource {
+    /// Whether `source` uses the `warc://` scheme.
+    pub fn is_warc(source: &str) -> bool {
+        source
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("warc://"))
+    }
+}
+
+impl FromStr for WarcSource {
+    type Err = WarcError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if !Self::is_warc(source) {
+            return Err(WarcError::InvalidSource(format!(
+                "{} is not a warc:// URL",
+                source
+            )));
+        }
+        let rest = &source[7..];
+        let (file, record_id) = match rest.split_once('#') {
+            Some((file, id)) => (file, Some(id)),
+            None => (rest, None),
+        };
+        let decode = |value: &str| {
+            percent_encoding::percent_decode_str(value)
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+
+        let file = decode(file);
+        if file.is_empty() {
+            return Err(WarcError::InvalidSource(format!(
+                "{} names no archive file",
+                source
+            )));
+        }
+        Ok(Self {
+            file,
+            record_id: record_id.map(decode).filter(|id| !id.is_empty()),
+        })
+    }
+}
+
+/// Directory of WARC files that `warc://` sources are resolved against.
+#[derive(Debug, Clone)]
+pub struct WarcArchive {
+    root: PathBuf,
+}
+
+impl WarcArchive {
+    /// Resolve sources against `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Archive directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of `file`, which must stay inside the archive directory.
+    pub fn path(&self, file: &str) -> Result<PathBuf, WarcError> {
+        let relative = Path::new(file);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(WarcError::InvalidSource(format!(
+                "{} is outside the archive directory",
+                file
+            )));
+        }
+        let path = self.root.join(relative);
+        if !path.is_file() {
+            return Err(WarcError::NotFound(format!("archive {}", file)));
+        }
+        Ok(path)
+    }
+
+    /// Iterate over the records of `file`.
+    pub fn records(&self, file: &str) -> Result<WarcReader<Box<dyn BufRead + Send>>, WarcError> {
+        WarcReader::open(&self.path(file)?)
+    }
+
+    /// Records holding a document (`response` and `resource` records) in `file`.
+    pub fn documents(
+        &self,
+        file: &str,
+    ) -> Result<impl Iterator<Item = Result<WarcRecord, WarcError>> + use<>, WarcError> {
+        Ok(self.records(file)?.filter(|record| {
+            record.as_ref().map_or(true, |record| {
+                matches!(
+                    record.record_type(),
+                    Some(WarcRecordType::Response | WarcRecordType::Resource)
+                )
+            })
+        }))
+    }
+
+    /// Find the record with `record_id` in `file`.
+    pub fn find(&self, file: &str, record_id: &str) -> Result<WarcRecord, WarcError> {
+        for record in self.records(file)? {
+            let record = record?;
+            if record.has_id(record_id) {
+                return Ok(record);
+            }
+        }
+        Err(WarcError::NotFound(format!("{} in {}", record_id, file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::WarcConfig;
+    use super::super::writer::{WarcExchange, WarcWriter};
+    use super::*;
+
+    fn exchange(url: &str, body: &'static [u8]) -> WarcExchange {
+        WarcExchange {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            request_headers: vec![("user-agent".to_string(), "test".to_string())],
+            request_body: None,
+            version: "HTTP/1.1".to_string(),
+            status: 200,
+            response_headers: vec![
+                ("content-type".to_string(), "text/html".to_string()),
+                ("transfer-encoding".to_string(), "chunked".to_string()),
+            ],
+            body: Bytes::from_static(body),
+            truncated: false,
+            ip: Some("93.184.216.34".parse().unwrap()),
+            date: chrono::Utc::now(),
+            wait: std::time::Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_write_rotate_and_read_back() {
+        for gzip in [true, false] {
+            let dir = std::env::temp_dir().join(format!("scapi-warc-{}", uuid::Uuid::new_v4()));
+            let writer = WarcWriter::new(WarcConfig {
+                directory: dir.clone(),
+                gzip,
+                max_file_size: 1,
+                ..Default::default()
+            })
+            .unwrap();
+
+            let first = exchange("https://example.com/a?x=1", b"<p>a</p>");
+            writer.write_exchange(&first).unwrap();
+            let first_file = writer.current_path().unwrap();
+            writer
+                .write_exchange(&exchange("https://example.com/b", b"<p>b</p>"))
+                .unwrap();
+            assert_ne!(writer.current_path().unwrap(), first_file);
+
+            let archive = WarcArchive::new(&dir);
+            let name = first_file.file_name().unwrap().to_str().unwrap();
+            let records: Vec<WarcRecord> =
+                archive.records(name).unwrap().map(Result::unwrap).collect();
+            let types: Vec<_> = records.iter().map(|r| r.record_type().unwrap()).collect();
+            assert_eq!(
+                types,
+                [
+                    WarcRecordType::Warcinfo,
+                    WarcRecordType::Request,
+                    WarcRecordType::Response
+                ]
+            );
+            let request = String::from_utf8_lossy(&records[1].block).into_owned();
+            assert!(request.starts_with("GET /a?x=1 HTTP/1.1\r\nhost: example.com\r\n"));
+            assert_eq!(records[2].header("WARC-IP-Address"), Some("93.184.216.34"));
+            assert_eq!(
+                records[1].header("WARC-Concurrent-To"),
+                records[2].record_id()
+            );
+
+            let source: WarcSource = format!("warc://{}#{}", name, records[2].record_id().unwrap())
+                .parse()
+                .unwrap();
+            let found = archive
+                .find(&source.file, source.record_id.as_deref().unwrap())
+                .unwrap();
+            let payload = found.payload().unwrap();
+            assert_eq!(payload.body, Bytes::from_static(b"<p>a</p>"));
+            assert_eq!(payload.header("transfer-encoding"), None);
+            assert_eq!(archive.documents(name).unwrap().count(), 1);
+
+            assert!(matches!(
+                archive.find(name, "urn:uuid:00000000-0000-0000-0000-000000000000"),
+                Err(WarcError::NotFound(_))
+            ));
+            assert!(matches!(
+                archive.records("../etc/passwd"),
+                Err(WarcError::InvalidSource(_))
+            ));
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+    #[tokio::test]
+    async fn test_recorded_bodies_are_cut_and_written_in_background() {
+        use super::super::writer::{ExchangeSink, RecordingStream};
+        use futures::StreamExt;
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("scapi-warc-{}", uuid::Uuid::new_v4()));
+        let writer = Arc::new(
+            WarcWriter::new(WarcConfig {
+                directory: dir.clone(),
+                max_record_size: 4,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let chunks = futures::stream::iter(
+            [&b"<p>"[..], &b"long</p>"[..]].map(|chunk| Ok(Bytes::from_static(chunk))),
+        );
+        let sinks: Vec<Arc<dyn ExchangeSink>> = vec![writer.clone()];
+        let recording =
+            RecordingStream::new(chunks, sinks, exchange("https://example.com/long", b""));
+        // The reader still gets the whole body
+        let read: Vec<_> = recording.map(Result::unwrap).collect().await;
+        assert_eq!(read.concat(), b"<p>long</p>");
+
+        writer.flush();
+        let path = writer.current_path().unwrap();
+        let records: Vec<WarcRecord> = WarcArchive::new(&dir)
+            .records(path.file_name().unwrap().to_str().unwrap())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records[2].header("WARC-Truncated"), Some("unspecified"));
+        assert_eq!(
+            records[2].payload().unwrap().body,
+            Bytes::from_static(b"<p>l")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
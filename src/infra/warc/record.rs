@@ -0,0 +1,310 @@
+//! WARC 1.1 records and the HTTP messages they carry.
+
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha1::{Digest, Sha1};
+
+use super::error::WarcError;
+
+/// Version line written at the start of every record.
+pub const WARC_VERSION: &str = "WARC/1.1";
+
+/// Content type of request records.
+pub const HTTP_REQUEST_TYPE: &str = "application/http; msgtype=request";
+
+/// Content type of response records.
+pub const HTTP_RESPONSE_TYPE: &str = "application/http; msgtype=response";
+
+/// WARC record types (WARC 1.1 section 6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarcRecordType {
+    /// Describes the records that follow it in the file
+    Warcinfo,
+    /// Complete response as received, e.g. an HTTP status line, headers and body
+    Response,
+    /// Document obtained without a protocol response
+    Resource,
+    /// Complete request as sent
+    Request,
+    /// Information about another record
+    Metadata,
+    /// Revisit of content that was already archived
+    Revisit,
+    /// Alternative version of another record's content
+    Conversion,
+    /// Remainder of a record segmented across files
+    Continuation,
+}
+
+impl WarcRecordType {
+    /// Value of the `WARC-Type` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarcRecordType::Warcinfo => "warcinfo",
+            WarcRecordType::Response => "response",
+            WarcRecordType::Resource => "resource",
+            WarcRecordType::Request => "request",
+            WarcRecordType::Metadata => "metadata",
+            WarcRecordType::Revisit => "revisit",
+            WarcRecordType::Conversion => "conversion",
+            WarcRecordType::Continuation => "continuation",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value.trim().to_ascii_lowercase().as_str() {
+            "warcinfo" => WarcRecordType::Warcinfo,
+            "response" => WarcRecordType::Response,
+            "resource" => WarcRecordType::Resource,
+            "request" => WarcRecordType::Request,
+            "metadata" => WarcRecordType::Metadata,
+            "revisit" => WarcRecordType::Revisit,
+            "conversion" => WarcRecordType::Conversion,
+            "continuation" => WarcRecordType::Continuation,
+            _ => return None,
+        })
+    }
+}
+
+/// A WARC record: named fields followed by a content block.
+///
+/// `Content-Length` is derived from the block when the record is serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarcRecord {
+    /// Named fields in file order (excluding `Content-Length` for new records)
+    pub headers: Vec<(String, String)>,
+    /// Content block
+    pub block: Bytes,
+}
+
+impl WarcRecord {
+    /// Create a record of `record_type` with a fresh id and the current date.
+    pub fn new(record_type: WarcRecordType, date: DateTime<Utc>) -> Self {
+        Self {
+            headers: vec![
+                ("WARC-Type".to_string(), record_type.as_str().to_string()),
+                (
+                    "WARC-Record-ID".to_string(),
+                    format!("<urn:uuid:{}>", uuid::Uuid::new_v4()),
+                ),
+                (
+                    "WARC-Date".to_string(),
+                    date.to_rfc3339_opts(SecondsFormat::Secs, true),
+                ),
+            ],
+            block: Bytes::new(),
+        }
+    }
+
+    /// Add a named field.
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Set the content block with its type and block digest.
+    pub fn with_block(self, content_type: &str, block: Bytes) -> Self {
+        let mut record = self
+            .with_header("Content-Type", content_type)
+            .with_header("WARC-Block-Digest", sha1_digest(&block));
+        record.block = block;
+        record
+    }
+
+    /// Value of the named field (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Record type, if known.
+    pub fn record_type(&self) -> Option<WarcRecordType> {
+        self.header("WARC-Type").and_then(WarcRecordType::parse)
+    }
+
+    /// `WARC-Record-ID`, including the angle brackets.
+    pub fn record_id(&self) -> Option<&str> {
+        self.header("WARC-Record-ID")
+    }
+
+    /// `WARC-Target-URI`.
+    pub fn target_uri(&self) -> Option<&str> {
+        self.header("WARC-Target-URI")
+    }
+
+    /// Whether this record's id is `id`, written with or without `<urn:uuid:...>`.
+    pub fn has_id(&self, id: &str) -> bool {
+        self.record_id()
+            .is_some_and(|record_id| normalize_id(record_id) == normalize_id(id))
+    }
+
+    /// Serialize the record, including the trailing blank lines.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.block.len() + 512);
+        out.extend_from_slice(WARC_VERSION.as_bytes());
+        out.extend_from_slice(b"\r\n");
+        for (name, value) in &self.headers {
+            if name.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+            out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        out.extend_from_slice(format!("Content-Length: {}\r\n\r\n", self.block.len()).as_bytes());
+        out.extend_from_slice(&self.block);
+        out.extend_from_slice(b"\r\n\r\n");
+        out
+    }
+
+    /// The archived document: the HTTP body of `response` records and the
+    /// block of `resource` records.
+    pub fn payload(&self) -> Result<HttpMessage, WarcError> {
+        match self.record_type() {
+            Some(WarcRecordType::Response) => HttpMessage::parse(&self.block),
+            Some(WarcRecordType::Resource) => Ok(HttpMessage {
+                start_line: String::new(),
+                headers: self
+                    .header("Content-Type")
+                    .map(|content_type| {
+                        vec![("content-type".to_string(), content_type.to_string())]
+                    })
+                    .unwrap_or_default(),
+                body: self.block.clone(),
+            }),
+            other => Err(WarcError::Format(format!(
+                "{} record has no payload",
+                other.map_or("unknown", |t| t.as_str())
+            ))),
+        }
+    }
+}
+
+/// An HTTP message as stored in a request or response record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpMessage {
+    /// Request or status line (empty for `resource` records)
+    pub start_line: String,
+    /// Header fields (lowercase names)
+    pub headers: Vec<(String, String)>,
+    /// Entity body
+    pub body: Bytes,
+}
+
+impl HttpMessage {
+    /// Serialize a message with the given start line, headers and body.
+    pub fn to_bytes(start_line: &str, headers: &[(String, String)], body: &[u8]) -> Bytes {
+        let mut out = Vec::with_capacity(body.len() + 512);
+        out.extend_from_slice(start_line.as_bytes());
+        out.extend_from_slice(b"\r\n");
+        for (name, value) in headers {
+            out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(body);
+        Bytes::from(out)
+    }
+
+    /// Split a serialized message into start line, headers and body.
+    pub fn parse(block: &Bytes) -> Result<Self, WarcError> {
+        let end = block
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|pos| (pos, pos + 4))
+            .or_else(|| {
+                block
+                    .windows(2)
+                    .position(|window| window == b"\n\n")
+                    .map(|pos| (pos, pos + 2))
+            })
+            .ok_or_else(|| WarcError::Format("HTTP message without header end".to_string()))?;
+
+        let head = String::from_utf8_lossy(&block[..end.0]);
+        let mut lines = head.lines();
+        let start_line = lines.next().unwrap_or_default().trim().to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+
+        Ok(Self {
+            start_line,
+            headers,
+            body: block.slice(end.1..),
+        })
+    }
+
+    /// Value of the named header (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Status code from a response status line.
+    pub fn status_code(&self) -> Option<u16> {
+        self.start_line.split_whitespace().nth(1)?.parse().ok()
+    }
+}
+
+/// `sha1:<base32>` digest as used by `WARC-Block-Digest` and `WARC-Payload-Digest`.
+pub fn sha1_digest(data: &[u8]) -> String {
+    format!("sha1:{}", base32(&Sha1::digest(data)))
+}
+
+/// RFC 4648 base32 without padding.
+fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for &byte in data {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+/// Record id without angle brackets or `urn:uuid:` prefix, lowercased.
+fn normalize_id(id: &str) -> String {
+    let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+    id.strip_prefix("urn:uuid:")
+        .unwrap_or(id)
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_and_payload() {
+        // Digest of the empty string from the WARC specification's examples
+        assert_eq!(sha1_digest(b""), "sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ");
+
+        let block = HttpMessage::to_bytes(
+            "HTTP/1.1 200 OK",
+            &[("Content-Type".to_string(), "text/html".to_string())],
+            b"<p>hi</p>",
+        );
+        let record = WarcRecord::new(WarcRecordType::Response, Utc::now())
+            .with_header("WARC-Target-URI", "https://example.com/")
+            .with_block(HTTP_RESPONSE_TYPE, block);
+
+        let id = record.record_id().unwrap().to_string();
+        assert!(record.has_id(id.trim_matches(|c| c == '<' || c == '>')));
+        assert!(record.has_id(&id["<urn:uuid:".len()..id.len() - 1].to_uppercase()));
+
+        let payload = record.payload().unwrap();
+        assert_eq!(payload.status_code(), Some(200));
+        assert_eq!(payload.header("content-type"), Some("text/html"));
+        assert_eq!(payload.body, Bytes::from_static(b"<p>hi</p>"));
+    }
+}
@@ -0,0 +1,397 @@
+//! Rotating WARC writer and the stream adapter that records fetches.
+
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use futures::stream::Stream;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::config::WarcConfig;
+use super::error::WarcError;
+use super::record::{
+    HTTP_REQUEST_TYPE, HTTP_RESPONSE_TYPE, HttpMessage, WarcRecord, WarcRecordType, sha1_digest,
+};
+
+/// One HTTP request/response pair to archive.
+#[derive(Debug, Clone)]
+pub struct WarcExchange {
+    /// Requested URL
+    pub url: String,
+    /// Request method
+    pub method: String,
+    /// Request headers as sent
+    pub request_headers: Vec<(String, String)>,
+    /// Request body, if any
+    pub request_body: Option<Bytes>,
+    /// HTTP version of the response (e.g. `HTTP/1.1`)
+    pub version: String,
+    /// Response status code
+    pub status: u16,
+    /// Response headers as received
+    pub response_headers: Vec<(String, String)>,
+    /// Response body (possibly partial, see `truncated`)
+    pub body: Bytes,
+    /// Whether `body` is not the complete response body
+    pub truncated: bool,
+    /// Address the response came from, if known
+    pub ip: Option<IpAddr>,
+    /// Time the request was sent
+    pub date: DateTime<Utc>,
+    /// Time between sending the request and receiving the response headers
+    pub wait: Duration,
+}
+
+impl WarcExchange {
+    /// Build the request and response records for this exchange.
+    pub fn to_records(&self) -> [WarcRecord; 2] {
+        let target = url::Url::parse(&self.url).ok();
+        let path = target.as_ref().map_or("/".to_string(), |url| {
+            let mut path = url.path().to_string();
+            if let Some(query) = url.query() {
+                path.push('?');
+                path.push_str(query);
+            }
+            path
+        });
+        let mut request_headers = self.request_headers.clone();
+        if let Some(host) = target.as_ref().and_then(|url| url.host_str())
+            && !request_headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("host"))
+        {
+            let host = match target.as_ref().and_then(|url| url.port()) {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            };
+            request_headers.insert(0, ("host".to_string(), host));
+        }
+        let request_block = HttpMessage::to_bytes(
+            &format!("{} {} {}", self.method, path, self.version),
+            &request_headers,
+            self.request_body.as_deref().unwrap_or_default(),
+        );
+
+        // The client has already removed any chunked framing from the body
+        let response_headers: Vec<(String, String)> = self
+            .response_headers
+            .iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("transfer-encoding"))
+            .cloned()
+            .collect();
+        let reason = reqwest::StatusCode::from_u16(self.status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("");
+        let response_block = HttpMessage::to_bytes(
+            format!("{} {} {}", self.version, self.status, reason).trim_end(),
+            &response_headers,
+            &self.body,
+        );
+
+        let mut response = WarcRecord::new(WarcRecordType::Response, self.date)
+            .with_header("WARC-Target-URI", &self.url)
+            .with_header("WARC-Payload-Digest", sha1_digest(&self.body));
+        if let Some(ip) = self.ip {
+            response = response.with_header("WARC-IP-Address", ip.to_string());
+        }
+        if self.truncated {
+            response = response.with_header("WARC-Truncated", "unspecified");
+        }
+        let response = response.with_block(HTTP_RESPONSE_TYPE, response_block);
+
+        let mut request = WarcRecord::new(WarcRecordType::Request, self.date)
+            .with_header("WARC-Target-URI", &self.url)
+            .with_header(
+                "WARC-Concurrent-To",
+                response.record_id().unwrap_or_default(),
+            );
+        if let Some(ip) = self.ip {
+            request = request.with_header("WARC-IP-Address", ip.to_string());
+        }
+        let request = request.with_block(HTTP_REQUEST_TYPE, request_block);
+
+        [request, response]
+    }
+}
+
+/// Exchanges waiting for the writer thread before new ones are dropped.
+const QUEUE_CAPACITY: usize = 256;
+
+/// File currently being appended to.
+struct OpenFile {
+    file: File,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Work for the writer thread.
+enum Queued {
+    Exchange(Box<WarcExchange>),
+    /// Acknowledge once everything queued before is written
+    Flush(mpsc::Sender<()>),
+}
+
+/// Appends records to rotating WARC files under `WarcConfig::directory`.
+///
+/// Every file starts with a `warcinfo` record. With `gzip` set, each record is
+/// its own gzip member, so standard tools can seek to any record.
+///
+/// Exchanges recorded as an [`ExchangeSink`] are encoded and written on a
+/// dedicated thread, so fetches never wait for compression or disk I/O.
+pub struct WarcWriter {
+    files: Arc<WarcFiles>,
+    queue: SyncSender<Queued>,
+}
+
+/// Rotating files of a writer, shared with its writer thread.
+struct WarcFiles {
+    config: WarcConfig,
+    current: Mutex<Option<OpenFile>>,
+}
+
+impl std::fmt::Debug for WarcWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WarcWriter")
+            .field("config", &self.files.config)
+            .finish()
+    }
+}
+
+impl WarcWriter {
+    /// Create a writer, creating the output directory if needed.
+    pub fn new(config: WarcConfig) -> Result<Self, WarcError> {
+        std::fs::create_dir_all(&config.directory)?;
+        let files = Arc::new(WarcFiles {
+            config,
+            current: Mutex::new(None),
+        });
+
+        let (queue, queued) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let writer = files.clone();
+        std::thread::Builder::new()
+            .name("warc-writer".to_string())
+            .spawn(move || {
+                // Ends once the writer and with it the sender are dropped
+                for item in queued {
+                    match item {
+                        Queued::Exchange(exchange) => {
+                            if let Err(e) = writer.write(&exchange.to_records()) {
+                                tracing::warn!("Failed to archive {}: {}", exchange.url, e);
+                            }
+                        }
+                        Queued::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+        Ok(Self { files, queue })
+    }
+
+    /// Writer configuration.
+    pub fn config(&self) -> &WarcConfig {
+        &self.files.config
+    }
+
+    /// Path of the file records are currently appended to.
+    pub fn current_path(&self) -> Option<PathBuf> {
+        self.files
+            .current
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|open| open.path.clone())
+    }
+
+    /// Append `records` to the current file, rotating it first if it is full.
+    ///
+    /// The records are written together, so related records (a request and
+    /// its response) never end up in different files.
+    pub fn write(&self, records: &[WarcRecord]) -> Result<(), WarcError> {
+        self.files.write(records)
+    }
+
+    /// Archive a request/response pair.
+    pub fn write_exchange(&self, exchange: &WarcExchange) -> Result<(), WarcError> {
+        self.write(&exchange.to_records())
+    }
+
+    /// Block until every exchange recorded so far is written.
+    pub fn flush(&self) {
+        let (done, written) = mpsc::channel();
+        if self.queue.send(Queued::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+}
+
+impl WarcFiles {
+    fn write(&self, records: &[WarcRecord]) -> Result<(), WarcError> {
+        let mut data = Vec::new();
+        for record in records {
+            data.extend(self.encode(record)?);
+        }
+
+        let mut current = self.current.lock().unwrap();
+        if current
+            .as_ref()
+            .is_none_or(|open| open.size >= self.config.max_file_size)
+        {
+            *current = Some(self.open_file()?);
+        }
+        let open = current.as_mut().expect("file opened above");
+        open.file.write_all(&data)?;
+        open.file.flush()?;
+        open.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Start a new file with its `warcinfo` record.
+    fn open_file(&self) -> Result<OpenFile, WarcError> {
+        let now = Utc::now();
+        let extension = if self.config.gzip { "warc.gz" } else { "warc" };
+        let name = format!(
+            "{}-{}-{}.{}",
+            self.config.prefix,
+            now.format("%Y%m%d%H%M%S%3f"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8],
+            extension
+        );
+        let path = self.config.directory.join(&name);
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)?;
+
+        let info = format!(
+            "software: scapi/{}\r\nformat: WARC File Format 1.1\r\nconformsTo: http://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        let warcinfo = WarcRecord::new(WarcRecordType::Warcinfo, now)
+            .with_header("WARC-Filename", name)
+            .with_block("application/warc-fields", Bytes::from(info));
+        let data = self.encode(&warcinfo)?;
+        file.write_all(&data)?;
+
+        tracing::info!("Writing WARC records to {}", path.display());
+        Ok(OpenFile {
+            file,
+            path,
+            size: data.len() as u64,
+        })
+    }
+
+    fn encode(&self, record: &WarcRecord) -> Result<Vec<u8>, WarcError> {
+        let bytes = record.to_bytes();
+        if !self.config.gzip {
+            return Ok(bytes);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+/// Receiver of completed exchanges (the WARC writer, the HAR recorder).
+pub trait ExchangeSink: Send + Sync + std::fmt::Debug {
+    /// Record `exchange`; failures are logged rather than returned.
+    ///
+    /// Called from stream polling, so this must not block.
+    fn record(&self, exchange: &WarcExchange);
+
+    /// Body bytes this sink keeps; longer bodies may arrive cut, marked
+    /// `truncated`.
+    fn max_body(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl ExchangeSink for WarcWriter {
+    fn record(&self, exchange: &WarcExchange) {
+        let mut exchange = exchange.clone();
+        let limit = self.files.config.max_record_size;
+        if exchange.body.len() > limit {
+            exchange.body = exchange.body.slice(..limit);
+            exchange.truncated = true;
+        }
+        match self.queue.try_send(Queued::Exchange(Box::new(exchange))) {
+            Ok(()) => {}
+            Err(TrySendError::Full(Queued::Exchange(exchange))) => {
+                tracing::warn!("WARC writer is behind, not archiving {}", exchange.url);
+            }
+            Err(_) => tracing::warn!("WARC writer stopped, not archiving"),
+        }
+    }
+
+    fn max_body(&self) -> usize {
+        self.files.config.max_record_size
+    }
+}
+
+/// Stream adapter that records a response once its body has been read.
+///
+/// Bodies are recorded only when the stream completes; a failed or abandoned
+/// read leaves no response record behind. Only as much of the body as the
+/// sinks keep is buffered.
+pub struct RecordingStream<S> {
+    inner: S,
+    buffer: BytesMut,
+    limit: usize,
+    pending: Option<(Vec<Arc<dyn ExchangeSink>>, WarcExchange)>,
+}
+
+impl<S> RecordingStream<S> {
+    /// Wrap `inner`, passing `exchange` with the body to `sinks` when it completes.
+    pub fn new(inner: S, sinks: Vec<Arc<dyn ExchangeSink>>, exchange: WarcExchange) -> Self {
+        Self {
+            inner,
+            buffer: BytesMut::new(),
+            limit: sinks.iter().map(|sink| sink.max_body()).max().unwrap_or(0),
+            pending: Some((sinks, exchange)),
+        }
+    }
+}
+
+impl<S> Stream for RecordingStream<S>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Unpin,
+{
+    type Item = Result<Bytes, reqwest::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = Pin::new(&mut this.inner).poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some((_, exchange)) = &mut this.pending {
+                    let room = this.limit.saturating_sub(this.buffer.len());
+                    if chunk.len() > room {
+                        exchange.truncated = true;
+                    }
+                    this.buffer
+                        .extend_from_slice(&chunk[..chunk.len().min(room)]);
+                }
+            }
+            Poll::Ready(Some(Err(_))) => this.pending = None,
+            Poll::Ready(None) => {
+                if let Some((sinks, mut exchange)) = this.pending.take() {
+                    exchange.body = std::mem::take(&mut this.buffer).freeze();
+                    for sink in &sinks {
+                        sink.record(&exchange);
+                    }
+                }
+            }
+            _ => {}
+        }
+        item
+    }
+}
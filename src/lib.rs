@@ -30,7 +30,7 @@
 //!     let config = config::AppConfig::from_env()?;
 //!
 //!     // Create application state
-//!     let state = AppState::new()?;
+//!     let state = AppState::with_config(&config)?;
 //!
 //!     // Create router
 //!     let app = api::create_router(state);
@@ -66,16 +66,122 @@ pub struct AppState {
 
     /// Select service
     pub select_service: std::sync::Arc<domain::select::service::DefaultSelectService>,
+
+    /// Named sessions (cookie jars, default headers, sticky proxies)
+    pub session_store: std::sync::Arc<domain::session::SessionStore>,
+
+    /// WARC files that `warc://` sources are read from
+    pub warc_archive: std::sync::Arc<infra::warc::WarcArchive>,
+
+    /// HAR files that `har://` sources are read from
+    pub har_archive: std::sync::Arc<infra::har::HarArchive>,
+
+    /// Recorded fetches for HAR export, if enabled
+    pub har_recorder: Option<std::sync::Arc<infra::har::HarRecorder>>,
+
+    /// Sitemap discovery
+    pub sitemap_service: std::sync::Arc<domain::sitemap::SitemapService>,
+
+    /// Configured retry policy that per-request overrides apply to
+    pub retry_policy: domain::fetch::RetryPolicy,
 }
 
 impl AppState {
-    /// Create a new application state.
+    /// Create a new application state with default settings.
     pub fn new() -> Result<Self, CommonError> {
         let http_client = infra::http::HttpClient::new()
-            .map_err(|e| CommonError::config(format!("Failed to create HTTP client: {}", e)))?;
+            .map_err(|e| CommonError::config(format!("Failed to create HTTP client: {}", e)))?
+            .with_egress(domain::fetch::EgressPolicy::default());
+
+        Ok(Self::with_services(
+            domain::fetch::service::DefaultFetchService::new(http_client),
+            domain::session::SessionStore::default(),
+            infra::warc::WarcArchive::new(infra::warc::WarcConfig::default().directory),
+            infra::har::HarArchive::new(infra::har::HarConfig::default().directory),
+            None,
+            domain::sitemap::SitemapConfig::default(),
+            domain::fetch::RetryPolicy::default(),
+        ))
+    }
+
+    /// Create a new application state from the loaded configuration.
+    pub fn with_config(config: &infra::config::AppConfig) -> Result<Self, CommonError> {
+        let egress = domain::fetch::EgressPolicy::new(config.egress.clone()).with_trusted_proxies(
+            config
+                .proxy
+                .default
+                .iter()
+                .chain(config.proxy.hosts.values())
+                .chain(&config.proxy.pool),
+        )
+        .with_trusted_hosts(config.dns.overrides.keys());
+        let mut http_client = infra::http::HttpClient::new()
+            .map_err(|e| CommonError::config(format!("Failed to create HTTP client: {}", e)))?
+            .with_egress(egress)
+            .with_dns(infra::http::DnsResolver::new(config.dns.clone()))
+            .with_tls(
+                infra::http::TlsSettings::new(config.tls.clone()).map_err(|e| {
+                    CommonError::config(format!("Invalid TLS configuration: {}", e))
+                })?,
+            )
+            .with_local_sources(infra::http::LocalSource::new(config.local.clone()))
+            .with_transport(config.transport.build().map_err(|e| {
+                CommonError::config(format!("Failed to create HTTP transport: {}", e))
+            })?);
+        if config.warc.enabled {
+            let warc = infra::warc::WarcWriter::new(config.warc.clone())
+                .map_err(|e| CommonError::config(format!("Failed to create WARC writer: {}", e)))?;
+            http_client = http_client.with_warc(warc);
+        }
+        let har_recorder = config.har.enabled.then(|| {
+            std::sync::Arc::new(
+                infra::har::HarRecorder::new(config.har.max_entries)
+                    .with_max_bytes(config.har.max_entry_bytes, config.har.max_total_bytes)
+                    .with_export_token(config.har.export_token.clone()),
+            )
+        });
+        if let Some(recorder) = &har_recorder {
+            http_client = http_client.with_har(recorder.clone());
+        }
+        if config.cache.enabled {
+            let cache = infra::http::HttpCache::new(config.cache.clone())
+                .map_err(|e| CommonError::config(format!("Failed to create HTTP cache: {}", e)))?;
+            http_client = http_client.with_cache(cache);
+        }
+
+        let fetch_service = domain::fetch::service::DefaultFetchService::new(http_client)
+            .with_politeness(config.politeness.clone())
+            .with_robots(config.robots.clone())
+            .with_coalescing(config.coalesce.clone())
+            .with_circuit_breaker(config.breaker.clone())
+            .with_proxies(config.proxy.clone())
+            .with_credentials(config.credentials.clone());
+        let session_store = domain::session::SessionStore::open(config.session.clone())
+            .map_err(|e| CommonError::config(format!("Failed to open session store: {}", e)))?;
+        Ok(Self::with_services(
+            fetch_service,
+            session_store,
+            infra::warc::WarcArchive::new(config.warc.directory.clone()),
+            infra::har::HarArchive::new(config.har.directory.clone()),
+            har_recorder,
+            config.sitemap.clone(),
+            config.fetch.retry.clone(),
+        ))
+    }
 
-        let fetch_service = std::sync::Arc::new(domain::fetch::service::DefaultFetchService::new(
-            http_client,
+    fn with_services(
+        fetch_service: domain::fetch::service::DefaultFetchService,
+        session_store: domain::session::SessionStore,
+        warc_archive: infra::warc::WarcArchive,
+        har_archive: infra::har::HarArchive,
+        har_recorder: Option<std::sync::Arc<infra::har::HarRecorder>>,
+        sitemap: domain::sitemap::SitemapConfig,
+        retry_policy: domain::fetch::RetryPolicy,
+    ) -> Self {
+        let fetch_service = std::sync::Arc::new(fetch_service);
+        let sitemap_service = std::sync::Arc::new(domain::sitemap::SitemapService::new(
+            fetch_service.clone(),
+            sitemap,
         ));
         let parse_service = std::sync::Arc::new(domain::parse::service::DefaultParseService::new());
 
@@ -86,11 +192,17 @@ impl AppState {
         let select_service =
             std::sync::Arc::new(domain::select::service::DefaultSelectService::default());
 
-        Ok(Self {
+        Self {
             fetch_service,
             parse_service,
             extract_service,
             select_service,
-        })
+            session_store: std::sync::Arc::new(session_store),
+            warc_archive: std::sync::Arc::new(warc_archive),
+            har_archive: std::sync::Arc::new(har_archive),
+            har_recorder,
+            sitemap_service,
+            retry_policy,
+        }
     }
 }
@@ -22,7 +22,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::debug!("Configuration loaded successfully");
 
     // Initialize application state
-    let state = AppState::new()?;
+    let state = AppState::with_config(&config)?;
 
     // Create router
     let app = api::create_router(state);